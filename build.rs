@@ -0,0 +1,82 @@
+use std::{env, fs, path::{Path, PathBuf}};
+
+use sha2::{Digest, Sha256};
+
+/// 嵌入二进制的模板: Move包目录和单独的模板文件
+const TEMPLATE_PACKAGES: [&str; 2] = ["bassinet_coin", "bassinet"];
+const TEMPLATE_FILES: [&str; 6] = [
+    "bassinet_coin_template",
+    "bassinet_coin_move_template",
+    "bassinet_coin_move_publish_template",
+    "bassinet_nft_move_template",
+    "bassinet_nft_move_publish_template",
+    "bassinet_nft_royalty_template",
+];
+/// 包内由模板文件提供的源码(包路径, 模板文件): 仓库中的源码按占位值渲染, 供单独编译和测试; 嵌入时替换为模板, 写出后总是按创作者参数渲染覆盖
+const PACKAGE_TEMPLATES: [(&str, &str); 1] = [
+    ("bassinet_coin/sources/bassinet_coin.move", "bassinet_coin_template"),
+];
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let templates_dir = manifest_dir.join("templates");
+    println!("cargo:rerun-if-changed={}", templates_dir.display());
+
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    for package in TEMPLATE_PACKAGES {
+        let package_dir = templates_dir.join(package);
+        if !package_dir.is_dir() {
+            panic!("template package {} not found in {}", package, templates_dir.display());
+        }
+        collect(&templates_dir, &package_dir, &mut files);
+        for (name, template) in PACKAGE_TEMPLATES {
+            if name.starts_with(&format!("{}/", package)) {
+                files.retain(|(file, _)| file != name);
+                files.push((name.to_owned(), templates_dir.join(template)));
+            }
+        }
+    }
+    for name in TEMPLATE_FILES {
+        files.push((name.to_owned(), templates_dir.join(name)));
+    }
+    files.sort();
+
+    // 模板版本: 全部文件路径和内容的 sha256
+    let mut hasher = Sha256::new();
+    let mut entries = String::new();
+    for (name, path) in &files {
+        let content = fs::read(path).unwrap_or_else(|e| panic!("read template {} failed: {}", path.display(), e));
+        hasher.update(name.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(&content);
+        entries.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", name, path.display().to_string()));
+    }
+    let digest = hasher.finalize();
+    let version: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+
+    let generated = format!(
+        "/// 嵌入的模板文件(相对 templates 目录的路径, 内容)\npub static EMBEDDED_FILES: &[(&str, &[u8])] = &[\n{}];\n\n/// 嵌入模板的版本\npub const EMBEDDED_VERSION: &str = {:?};\n",
+        entries, version
+    );
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("embedded_templates.rs"), generated).unwrap();
+}
+
+fn collect(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            // Move 编译产物不嵌入
+            if path.file_name().is_some_and(|name| name == "build") {
+                continue;
+            }
+            collect(root, &path, files);
+        } else {
+            let name = path.strip_prefix(root).unwrap().components()
+                .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<String>>()
+                .join("/");
+            files.push((name, path));
+        }
+    }
+}
@@ -1,8 +1,11 @@
 // use std::{fs::File, path::PathBuf, str::FromStr};
 // use flate2::{read::GzEncoder, Compression};
 // use tar::{Archive, Builder};
-use std::{fs::File, path::{Path, PathBuf}};
-use tar::{Archive};
+use std::{fs::{self, File}, io, path::{Path, PathBuf}};
+use flate2::{write::GzEncoder, Compression};
+use tar::{Archive, Builder, EntryType};
+
+use crate::{template::source::{override_dir, write_embedded_package}, workspace::safe_entry_path};
 
 // /// 归档
 // pub fn archive() -> Result<(), std::io::Error> {
@@ -15,16 +18,14 @@ use tar::{Archive};
 //     Ok(())
 // }
 
-/// 解压到
+/// 解压到(dest_apth/bassinet_coin)
 pub fn unpack(dest_apth: &PathBuf) -> Result<(), std::io::Error> {
-    let template_path = std::env::var("BASSINET_TEMPLATE_PATH").expect("BASSINET_TEMPLATE_PATH must be set");
-    let template_path = Path::new(&template_path);
+    let Some(template_path) = override_dir() else {
+        return write_embedded_package("bassinet_coin", dest_apth, false)
+    };
     // let path = "templates/bassinet_coin.tar.gz";
     let path = template_path.join("bassinet_coin.tar.gz");
-    let tar_gz = File::open(path)?;
-    let mut archive = Archive::new(tar_gz);
-    archive.unpack(dest_apth)?;
-    Ok(())
+    unpack_confined(&path, dest_apth)
 }
 
 // /// 归档bassinet_nft
@@ -40,12 +41,153 @@ pub fn unpack(dest_apth: &PathBuf) -> Result<(), std::io::Error> {
 
 /// 解压到
 pub fn unpack_bassinet(dest_apth: &PathBuf) -> Result<(), std::io::Error> {
-    let template_path = std::env::var("BASSINET_TEMPLATE_PATH").expect("BASSINET_TEMPLATE_PATH must be set");
-    let template_path = Path::new(&template_path);
+    let Some(template_path) = override_dir() else {
+        return write_embedded_package("bassinet", dest_apth, true)
+    };
     // let path = "templates/bassinet.tar.gz";
     let path = template_path.join("bassinet.tar.gz");
-    let tar_gz = File::open(path)?;
+    unpack_confined(&path, dest_apth)
+}
+
+/// 解压到dest, 只接受普通文件和目录; 拒绝链接、绝对路径和包含..的条目
+pub fn unpack_confined(archive_path: &Path, dest: &Path) -> Result<(), io::Error> {
+    let tar_gz = File::open(archive_path)?;
     let mut archive = Archive::new(tar_gz);
-    archive.unpack(dest_apth)?;
+    let dest = dest.canonicalize()?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let entry_type = entry.header().entry_type();
+        if entry_type == EntryType::XGlobalHeader {
+            continue;
+        }
+        if !matches!(entry_type, EntryType::Regular | EntryType::Directory) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported archive entry {:?}: {}", entry_type, entry_path.display())))
+        }
+        if !safe_entry_path(&entry_path) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsafe archive entry path: {}", entry_path.display())))
+        }
+        // unpack_in 同样拒绝越界路径, 返回false表示条目被跳过
+        if !entry.unpack_in(&dest)? {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("archive entry outside destination: {}", entry_path.display())))
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// 将目录归档为 tar.gz(条目以 prefix 为顶层目录), 先写临时文件, 完成后再改名
+pub fn archive_dir(dir: &Path, prefix: &str, file: &Path) -> Result<(), io::Error> {
+    let tmp_file = file.with_extension("tmp");
+    let encoder = GzEncoder::new(File::create(&tmp_file)?, Compression::default());
+    let mut builder = Builder::new(encoder);
+    builder.follow_symlinks(false);
+    builder.append_dir_all(prefix, dir)?;
+    builder.into_inner()?.finish()?;
+    fs::rename(&tmp_file, file)
+}
+
+#[cfg(test)]
+mod tests {
+    use tar::Header;
+
+    use super::*;
+
+    struct TempDir {
+        dir: PathBuf,
+    }
+
+    impl TempDir {
+
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("bassinet-archive-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(dir.join("dest")).unwrap();
+            Self { dir }
+        }
+
+        fn dest(&self) -> PathBuf {
+            self.dir.join("dest")
+        }
+
+        /// 写出只含一个条目的tar; 直接写入头部的路径, 绕过 tar::Builder 对路径的检查
+        fn archive(&self, entry_type: EntryType, path: &str, link: Option<&str>, data: &[u8]) -> PathBuf {
+            let mut header = Header::new_old();
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            if let Some(link) = link {
+                header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+            }
+            header.set_entry_type(entry_type);
+            header.set_mode(0o644);
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            let file = self.dir.join(format!("{}.tar", uuid::Uuid::new_v4()));
+            let mut builder = Builder::new(File::create(&file).unwrap());
+            builder.append(&header, data).unwrap();
+            builder.finish().unwrap();
+            file
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn unpacks_regular_entries() {
+        let temp = TempDir::new();
+        let archive = temp.archive(EntryType::Regular, "bassinet_coin/Move.toml", None, b"[package]");
+        unpack_confined(&archive, &temp.dest()).unwrap();
+        assert_eq!(fs::read(temp.dest().join("bassinet_coin/Move.toml")).unwrap(), b"[package]");
+    }
+
+    #[test]
+    fn rejects_parent_and_absolute_entries() {
+        let temp = TempDir::new();
+        for path in ["../escaped", "bassinet_coin/../../escaped", "/tmp/bassinet-escaped"] {
+            let archive = temp.archive(EntryType::Regular, path, None, b"x");
+            let err = unpack_confined(&archive, &temp.dest()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", path);
+        }
+        assert!(!temp.dir.join("escaped").exists());
+        assert!(!Path::new("/tmp/bassinet-escaped").exists());
+    }
+
+    #[test]
+    fn rejects_link_entries() {
+        let temp = TempDir::new();
+        for entry_type in [EntryType::Symlink, EntryType::Link] {
+            let archive = temp.archive(entry_type, "link", Some("../outside"), b"");
+            let err = unpack_confined(&archive, &temp.dest()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(fs::symlink_metadata(temp.dest().join("link")).is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn does_not_write_through_symlinked_directories() {
+        let temp = TempDir::new();
+        fs::create_dir_all(temp.dir.join("outside")).unwrap();
+        std::os::unix::fs::symlink(temp.dir.join("outside"), temp.dest().join("sources")).unwrap();
+        let archive = temp.archive(EntryType::Regular, "sources/escaped.move", None, b"x");
+        assert!(unpack_confined(&archive, &temp.dest()).is_err());
+        assert!(!temp.dir.join("outside/escaped.move").exists());
+    }
+
+    #[test]
+    fn archive_dir_round_trips() {
+        let temp = TempDir::new();
+        let source = temp.dir.join("source");
+        fs::create_dir_all(source.join("sources")).unwrap();
+        fs::write(source.join("sources/a.move"), b"module a::a;").unwrap();
+        let file = temp.dir.join("source.tar.gz");
+        archive_dir(&source, "bassinet_coin", &file).unwrap();
+
+        let mut archive = Archive::new(flate2::read::GzDecoder::new(File::open(&file).unwrap()));
+        let paths: Vec<String> = archive.entries().unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert!(paths.contains(&"bassinet_coin/sources/a.move".to_owned()));
+    }
+}
@@ -0,0 +1,350 @@
+use std::{collections::HashSet, fs::{self, File}, io::{BufRead, BufReader, BufWriter, Write}, net::{SocketAddr, TcpStream}, path::{Path, PathBuf}, time::Duration};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tokio::{io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader}, net::TcpListener, time::sleep};
+use tracing::{error, info};
+
+use crate::{kv_store::{now_millis, Column, KVStore, MemoryStore, RocksDB, WriteBatch}, migration::{migrate, open_migrated, SCHEMA_VERSION_KEY}, repository::{CoinPackageRepository, DeferredMessage, NftPackageRepository, WorkflowRecord}};
+
+const USAGE: &str = "usage: bassinet-sui store <command>
+  checkpoint <dir>                 create a checkpoint, through the running service when STORE_CONTROL_ADDR is set
+  export <file>                    export all records of the (running) store to JSON Lines
+  import <file> [--target <dir>]   import an export into a fresh store";
+
+/// 定期检查点默认间隔(秒)
+const DEFAULT_CHECKPOINT_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// 保留的定期检查点数量
+const CHECKPOINT_KEEP: usize = 7;
+
+/// 导出文件中的一行
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportRecord {
+    pub column: String,
+    pub key: String,
+    pub value: String,
+}
+
+/// 存储备份子命令
+pub fn store(args: &[String], rocksdb_dir_path: &str) -> Result<(), anyhow::Error> {
+    let command = args.first().ok_or(anyhow!(USAGE))?;
+    match command.as_str() {
+        "checkpoint" => {
+            let dir = args.get(1).ok_or(anyhow!(USAGE))?;
+            // 服务运行中时存储被占用, 通过服务的控制端口创建检查点
+            if let Some(addr) = control_addr()? {
+                if let Some(path) = request_checkpoint(addr, dir)? {
+                    println!("checkpoint created at {} by the running service", path);
+                    return Ok(())
+                }
+            }
+            let db = RocksDB::open(rocksdb_dir_path)
+                .map_err(|e| anyhow!("{}, stop the service, set STORE_CONTROL_ADDR or use `store export`", e))?;
+            db.checkpoint(Path::new(dir))?;
+            println!("checkpoint created at {}", dir);
+            Ok(())
+        }
+        "export" => {
+            let file = args.get(1).ok_or(anyhow!(USAGE))?;
+            let secondary_path = std::env::temp_dir().join(format!("bassinet-store-secondary-{}", std::process::id()));
+            let db = RocksDB::open_secondary(rocksdb_dir_path, secondary_path.to_str().unwrap())?;
+            let result = export(&db, Path::new(file));
+            drop(db);
+            let _ = fs::remove_dir_all(&secondary_path);
+            println!("exported {} records to {}", result?, file);
+            Ok(())
+        }
+        "import" => {
+            let file = args.get(1).ok_or(anyhow!(USAGE))?;
+            let target = match args.get(2).map(|arg| arg.as_str()) {
+                Some("--target") => args.get(3).ok_or(anyhow!(USAGE))?.as_str(),
+                Some(arg) => return Err(anyhow!("Unknown argument:{}\n{}", arg, USAGE)),
+                None => rocksdb_dir_path,
+            };
+            let db = open_migrated(target)?;
+            let warnings = import(Path::new(file), &db)?;
+            for warning in &warnings {
+                println!("warning: {}", warning);
+            }
+            println!("imported {} into {}", file, target);
+            Ok(())
+        }
+        command => Err(anyhow!("Unknown command:{}\n{}", command, USAGE)),
+    }
+}
+
+/// 将存储中同一时刻的全部记录导出为JSON Lines, 返回记录数
+pub fn export<S: KVStore>(store: &S, file: &Path) -> Result<usize, anyhow::Error> {
+    let records = store.dump()?;
+    // 先写临时文件, 完成后再改名, 避免留下不完整的导出
+    let tmp_file = file.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp_file)?);
+    for (column, key, value) in &records {
+        let record = ExportRecord { column: column.name().to_owned(), key: key.clone(), value: value.clone() };
+        writeln!(writer, "{}", serde_json::to_string(&record)?)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&tmp_file, file)?;
+    Ok(records.len())
+}
+
+/// 校验导出文件并导入到空存储, 全部记录在一个批次中写入; 返回校验警告
+pub fn import<S: KVStore>(file: &Path, target: &S) -> Result<Vec<String>, anyhow::Error> {
+    // 新存储打开时已写入结构版本, 不算作已有记录
+    if target.dump()?.iter().any(|(column, key, _)| !(*column == Column::Default && key == SCHEMA_VERSION_KEY)) {
+        return Err(anyhow!("Target store is not empty"))
+    }
+    let staging = load(file)?;
+    // 旧版本导出先迁移到当前结构
+    let report = migrate(&staging, false)?;
+    info!("导入记录迁移完成:\n{}", report);
+    let warnings = validate(&staging)?;
+
+    let mut batch = WriteBatch::new();
+    for (column, key, value) in staging.dump()? {
+        batch.put(column, &key, &value);
+    }
+    target.write(batch)?;
+    Ok(warnings)
+}
+
+/// 逐行解析导出文件到内存存储
+fn load(file: &Path) -> Result<MemoryStore, anyhow::Error> {
+    let staging = MemoryStore::new();
+    let mut seen = HashSet::new();
+    let reader = BufReader::new(File::open(file)?);
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_no = index + 1;
+        let record: ExportRecord = serde_json::from_str(&line).map_err(|e| anyhow!("line {}: {}", line_no, e))?;
+        let column = Column::from_name(&record.column).ok_or(anyhow!("line {}: unknown column {}", line_no, record.column))?;
+        if !seen.insert((column, record.key.clone())) {
+            return Err(anyhow!("line {}: duplicate key {}/{}", line_no, record.column, record.key))
+        }
+        staging.save(column, &record.key, &record.value)?;
+    }
+    Ok(staging)
+}
+
+/// 校验记录格式; 格式错误时失败, 引用缺失时返回警告
+fn validate(staging: &MemoryStore) -> Result<Vec<String>, anyhow::Error> {
+    let coin_packages = CoinPackageRepository::new(staging.clone()).list()?;
+    let nft_packages = NftPackageRepository::new(staging.clone()).list()?;
+    for (key, value) in staging.list(Column::Workflows)? {
+        let valid = if key.starts_with("deferred_nft_launched:") {
+            serde_json::from_str::<Vec<DeferredMessage>>(&value).is_ok()
+        } else {
+            serde_json::from_str::<WorkflowRecord>(&value).is_ok()
+        };
+        if !valid {
+            return Err(anyhow!("invalid workflow record {}", key))
+        }
+    }
+    for (key, value) in staging.list(Column::Dedup)? {
+        if value.parse::<u64>().is_err() {
+            return Err(anyhow!("invalid dedup mark {}:{}", key, value))
+        }
+    }
+    for (key, value) in staging.list(Column::Default)? {
+        if key != SCHEMA_VERSION_KEY {
+            return Err(anyhow!("unrecognized record {}:{}", key, value))
+        }
+    }
+
+    let mut warnings = Vec::new();
+    for (wallet_address, package_id) in staging.list(Column::Creators)? {
+        if !coin_packages.iter().any(|result| result.package_id == package_id) {
+            warnings.push(format!("creator {} references missing coin package {}", wallet_address, package_id));
+        }
+    }
+    for (collection_id, package_id) in staging.list(Column::Collections)? {
+        if !nft_packages.iter().any(|result| result.package_id == package_id) {
+            warnings.push(format!("collection {} references missing nft package {}", collection_id, package_id));
+        }
+    }
+    Ok(warnings)
+}
+
+/// 定期在dir下创建检查点, 保留最近的CHECKPOINT_KEEP个
+pub async fn checkpoint_periodically(db: RocksDB, dir: String) {
+    let interval = std::env::var("STORE_CHECKPOINT_INTERVAL_SECS")
+        .map(|s| s.parse::<u64>().expect("can't parse STORE_CHECKPOINT_INTERVAL_SECS"))
+        .unwrap_or(DEFAULT_CHECKPOINT_INTERVAL_SECS);
+    loop {
+        sleep(Duration::from_secs(interval)).await;
+        let db = db.clone();
+        let dir = PathBuf::from(&dir);
+        match tokio::task::spawn_blocking(move || create_checkpoint(&db, &dir)).await {
+            Ok(Ok(path)) => info!("存储检查点已创建:{}", path.display()),
+            Ok(Err(err)) => error!("存储检查点创建失败:{err:?}"),
+            Err(err) => error!("存储检查点创建失败:{err:?}"),
+        }
+    }
+}
+
+fn create_checkpoint(db: &RocksDB, dir: &Path) -> Result<PathBuf, anyhow::Error> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("checkpoint-{}", now_millis()));
+    db.checkpoint(&path)?;
+    // 清理旧的检查点(目录名按时间排序)
+    let mut checkpoints: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with("checkpoint-")))
+        .collect();
+    checkpoints.sort();
+    while checkpoints.len() > CHECKPOINT_KEEP {
+        fs::remove_dir_all(checkpoints.remove(0))?;
+    }
+    Ok(path)
+}
+
+/// 存储控制端口(STORE_CONTROL_ADDR), 只允许回环地址
+pub fn control_addr() -> Result<Option<SocketAddr>, anyhow::Error> {
+    let Ok(addr) = std::env::var("STORE_CONTROL_ADDR") else {
+        return Ok(None)
+    };
+    let addr: SocketAddr = addr.parse().map_err(|e| anyhow!("invalid STORE_CONTROL_ADDR {}: {}", addr, e))?;
+    if !addr.ip().is_loopback() {
+        return Err(anyhow!("STORE_CONTROL_ADDR must be a loopback address: {}", addr))
+    }
+    Ok(Some(addr))
+}
+
+/// 请求运行中的服务在dir创建检查点; 服务未运行时返回None
+fn request_checkpoint(addr: SocketAddr, dir: &str) -> Result<Option<String>, anyhow::Error> {
+    let mut stream = match TcpStream::connect_timeout(&addr, Duration::from_secs(1)) {
+        Ok(stream) => stream,
+        Err(_) => return Ok(None),
+    };
+    // 服务端可能落后于CLI, 使用绝对路径
+    let dir = std::path::absolute(dir)?;
+    writeln!(stream, "checkpoint {}", dir.display())?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    match response.trim_end().split_once(' ') {
+        Some(("ok", path)) => Ok(Some(path.to_owned())),
+        Some(("error", err)) => Err(anyhow!("service checkpoint failed: {}", err)),
+        _ => Err(anyhow!("unexpected control response: {}", response.trim_end())),
+    }
+}
+
+/// 服务内的控制端口, 接收 `checkpoint <dir>` 请求并在运行中的存储上创建检查点
+pub async fn serve_control(db: RocksDB, addr: SocketAddr) {
+    let listener = match TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            error!("存储控制端口{}监听失败:{err:?}", addr);
+            return
+        }
+    };
+    info!("存储控制端口监听:{}", addr);
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                error!("存储控制端口连接失败:{err:?}");
+                continue;
+            }
+        };
+        let db = db.clone();
+        tokio::spawn(async move {
+            let (reader, mut writer) = stream.into_split();
+            let mut line = String::new();
+            if AsyncBufReader::new(reader).read_line(&mut line).await.is_err() {
+                return
+            }
+            let response = match line.trim_end().split_once(' ') {
+                Some(("checkpoint", dir)) => {
+                    let dir = PathBuf::from(dir);
+                    let path = dir.clone();
+                    match tokio::task::spawn_blocking(move || db.checkpoint(&dir)).await {
+                        Ok(Ok(())) => {
+                            info!("存储检查点已创建:{}", path.display());
+                            format!("ok {}", path.display())
+                        }
+                        Ok(Err(err)) => format!("error {}", err),
+                        Err(err) => format!("error {}", err),
+                    }
+                }
+                _ => format!("error unknown request: {}", line.trim_end()),
+            };
+            if let Err(err) = writer.write_all((response + "\n").as_bytes()).await {
+                error!("存储控制端口响应失败:{err:?}");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{CreatorRepository, WorkflowRepository, WorkflowStatus};
+
+    struct TempFile {
+        path: PathBuf,
+    }
+
+    impl TempFile {
+
+        fn new() -> Self {
+            Self { path: std::env::temp_dir().join(format!("bassinet-export-{}.jsonl", uuid::Uuid::new_v4())) }
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    fn current_store() -> MemoryStore {
+        let store = MemoryStore::new();
+        migrate(&store, false).unwrap();
+        store
+    }
+
+    #[test]
+    fn export_import_round_trip() {
+        let source = current_store();
+        CreatorRepository::new(source.clone()).save_coin_package_id("0x1", "0xc01").unwrap();
+        WorkflowRepository::new(source.clone()).save(&WorkflowRecord::new("coin_publish", "0x1", WorkflowStatus::Completed, None)).unwrap();
+        source.save(Column::Dedup, "event-1", "1000").unwrap();
+        let file = TempFile::new();
+        assert_eq!(export(&source, &file.path).unwrap(), source.dump().unwrap().len());
+
+        let target = current_store();
+        let warnings = import(&file.path, &target).unwrap();
+        assert_eq!(warnings, vec!["creator 0x1 references missing coin package 0xc01".to_owned()]);
+        assert_eq!(target.dump().unwrap(), source.dump().unwrap());
+
+        // 目标存储已有记录时拒绝导入
+        assert!(import(&file.path, &target).is_err());
+    }
+
+    #[test]
+    fn import_rejects_malformed_lines() {
+        let valid = serde_json::to_string(&ExportRecord { column: Column::Dedup.name().to_owned(), key: "event-1".to_owned(), value: "1000".to_owned() }).unwrap();
+        let unknown_column = r#"{"column":"unknown","key":"k","value":"v"}"#;
+        let invalid_mark = r#"{"column":"dedup","key":"event-2","value":"yesterday"}"#;
+        let cases = [
+            (format!("{}\nnot json\n", valid), "line 2"),
+            (format!("{}\n{}\n", valid, unknown_column), "line 2: unknown column unknown"),
+            (format!("{}\n\n{}\n", valid, valid), "line 3: duplicate key"),
+            (format!("{}\n", invalid_mark), "invalid dedup mark"),
+        ];
+        for (content, expected) in cases {
+            let file = TempFile::new();
+            fs::write(&file.path, content).unwrap();
+            let target = current_store();
+            let err = import(&file.path, &target).unwrap_err().to_string();
+            assert!(err.contains(expected), "{}", err);
+            // 校验失败时不写入任何记录
+            assert_eq!(target.dump().unwrap(), current_store().dump().unwrap());
+        }
+    }
+}
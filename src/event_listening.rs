@@ -1,12 +1,21 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::{HashMap, HashSet}, sync::Arc, time::Duration};
 // use futures::StreamExt;
-use sui_sdk::{rpc_types::{EventFilter, Page, SuiEvent}, types::{event::{EventID}, parse_sui_struct_tag}, SuiClient, SuiClientBuilder};
+use sui_sdk::{rpc_types::{CheckpointId, EventFilter, Page, SuiEvent, SuiTransactionBlockResponseOptions}, types::{digests::TransactionDigest, event::{EventID}, parse_sui_struct_tag}, SuiClient, SuiClientBuilder};
 use tokio::time;
+use anyhow::anyhow;
 
-use crate::{events_mq::{publish_events, Config}, kv_store::{KVStore, RocksDB}};
+use crate::{events_mq::{publish_events, Config}, kv_store::KVStore, repository::CursorRepository};
+
+/// 监听的事件类型及其游标键, 按依赖顺序排列
+const EVENT_STREAMS: [(&str, &str); 3] = [
+    ("AccountBound", "bassinet_account_bound"),
+    ("DigitalServiceOpened", "bassinet_service_opened"),
+    ("NftLaunched", "bassinet_nft_launched"),
+];
 
 /// 轮询查询事件
-pub async  fn listening(package_id: &str, db: RocksDB, coinfig: Arc<Config>) -> Result<(), anyhow::Error>{
+pub async  fn listening<S: KVStore>(package_id: &str, db: S, coinfig: Arc<Config>) -> Result<(), anyhow::Error>{
+    let cursors = CursorRepository::new(db.clone());
     loop {
         let client = get_client().await;
         if client.is_err() {
@@ -15,75 +24,46 @@ pub async  fn listening(package_id: &str, db: RocksDB, coinfig: Arc<Config>) ->
         }
         let client = client.unwrap();
 
-        // 账户绑定事件
-        let mut account_bound_event_id: Option<EventID> = Option::None;
-        let accoount_bound_cursor = db.find("bassinet_account_bound");
-        if accoount_bound_cursor.is_some() {
-            account_bound_event_id = Some(EventID::try_from(accoount_bound_cursor.unwrap()).unwrap());
-        }
-        let account_bound_events = listening_account_bound_events(&client, package_id, account_bound_event_id, Option::Some(10)).await;
-        if account_bound_events.is_err() {
-            tracing::warn!("{:?}", account_bound_events.err());
-        }else {
-            let account_bound_events = account_bound_events.unwrap();
-            // 事件发布到Rabbitmq
-            if !account_bound_events.data.is_empty() {
-                // for event in account_bound_events.data {
-                //     tracing::info!("event_id:{},package_id:{},module:{},sender:{},type_:{},event:{}", String::from(event.id), event.package_id, event.transaction_module, event.sender, event.type_, serde_json::to_string_pretty(&event.parsed_json).unwrap());
-                // }
-                let _ = publish_events(coinfig.clone(), &account_bound_events.data, package_id, db.clone()).await;
-            }
-            // 存储游标
-            if account_bound_events.has_next_page && account_bound_events.next_cursor.is_some() {
-                db.save("bassinet_account_bound", String::from(account_bound_events.next_cursor.unwrap()).as_str());
+        // 查询各类型事件, 任一失败则本轮不发布, 避免跨类型乱序
+        let mut pages = Vec::new();
+        for (event_type, cursor_key) in EVENT_STREAMS {
+            let cursor = cursors.find(cursor_key);
+            if cursor.is_err() {
+                tracing::warn!("{:?}", cursor.err());
+                break;
             }
-        }
-
-        // 开通数字服务事件
-        let mut service_opened_event_id: Option<EventID> = Option::None;
-        let service_opened_cursor = db.find("bassinet_service_opened");
-        if service_opened_cursor.is_some() {
-            service_opened_event_id = Some(EventID::try_from(service_opened_cursor.unwrap()).unwrap());
-        }
-        let service_opened_events = listening_service_opened_events(&client, package_id, service_opened_event_id, Option::Some(10)).await;
-        if service_opened_events.is_err() {
-            tracing::warn!("{:?}", service_opened_events.err());
-        }else {
-            let service_opened_events = service_opened_events.unwrap();
-            // 事件发布到Rabbitmq
-            if !service_opened_events.data.is_empty() {
-                let _ = publish_events(coinfig.clone(), &service_opened_events.data, package_id, db.clone()).await;
-                // for event in service_opened_events.data {
-                //     tracing::info!("event_id:{},package_id:{},module:{},sender:{},type_:{},event:{}", String::from(event.id), event.package_id, event.transaction_module, event.sender, event.type_, serde_json::to_string_pretty(&event.parsed_json).unwrap());
-                // }
+            let mut event_id: Option<EventID> = Option::None;
+            if let Some(cursor) = cursor.unwrap() {
+                event_id = Some(EventID::try_from(cursor).unwrap());
             }
-            // 存储游标
-            if service_opened_events.has_next_page && service_opened_events.next_cursor.is_some() {
-                db.save("bassinet_service_opened", String::from(service_opened_events.next_cursor.unwrap()).as_str());
+            let events = listening_events(&client, package_id, event_type, event_id, Option::Some(10), false).await;
+            if events.is_err() {
+                tracing::warn!("{:?}", events.err());
+                break;
             }
+            pages.push((cursor_key, events.unwrap()));
         }
 
-        // 发行NFT事件
-        let mut nft_launched_event_id: Option<EventID> = Option::None;
-        let nft_launched_cursor = db.find("bassinet_nft_launched");
-        if nft_launched_cursor.is_some() {
-            nft_launched_event_id = Some(EventID::try_from(nft_launched_cursor.unwrap()).unwrap());
-        }
-        let nft_launched_events = listening_nft_launched_events(&client, package_id, nft_launched_event_id, Option::Some(10)).await;
-        if nft_launched_events.is_err() {
-            tracing::warn!("{:?}", nft_launched_events.err());
-        }else {
-            let nft_launched_events = nft_launched_events.unwrap();
-            // 事件发布到Rabbitmq
-            if !nft_launched_events.data.is_empty() {
-                // for event in nft_launched_events.data {
-                //     tracing::info!("event_id:{},package_id:{},module:{},sender:{},type_:{},event:{}", String::from(event.id), event.package_id, event.transaction_module, event.sender, event.type_, serde_json::to_string_pretty(&event.parsed_json).unwrap());
-                // }
-                let _ = publish_events(coinfig.clone(), &nft_launched_events.data, package_id, db.clone()).await;
-            }
-            // 存储游标
-            if nft_launched_events.has_next_page && nft_launched_events.next_cursor.is_some() {
-                db.save("bassinet_nft_launched", String::from(nft_launched_events.next_cursor.unwrap()).as_str());
+        if pages.len() == EVENT_STREAMS.len() {
+            let ordered = order_events(&client, pages).await;
+            if ordered.is_err() {
+                tracing::warn!("{:?}", ordered.err());
+            }else {
+                let ordered = ordered.unwrap();
+                // 事件按链上顺序发布到Rabbitmq
+                if !ordered.is_empty() {
+                    let events: Vec<SuiEvent> = ordered.iter().map(|(_, event)| event.clone()).collect();
+                    let _ = publish_events(coinfig.clone(), &events, package_id, db.clone()).await;
+                }
+                // 存储游标(各类型最后发布的事件)
+                for (cursor_key, _) in EVENT_STREAMS {
+                    let last = ordered.iter().rev().find(|(key, _)| *key == cursor_key);
+                    if let Some((_, event)) = last {
+                        if let Err(err) = cursors.save(cursor_key, String::from(event.id).as_str()) {
+                            tracing::error!("save cursor {} failed: {:?}", cursor_key, err);
+                        }
+                    }
+                }
             }
         }
         // 暂停60s
@@ -92,6 +72,53 @@ pub async  fn listening(package_id: &str, db: RocksDB, coinfig: Arc<Config>) ->
     Ok(())
 }
 
+/// 合并各类型事件并按(checkpoint, checkpoint内的交易位置, 事件序号)排序
+/// 仍有下一页的类型, 只发布不晚于其本页最后一条事件的其他事件, 其余留到下一轮
+async fn order_events(client: &SuiClient, pages: Vec<(&'static str, Page<SuiEvent, EventID>)>) -> Result<Vec<(&'static str, SuiEvent)>, anyhow::Error> {
+    let digests: Vec<TransactionDigest> = pages.iter()
+        .flat_map(|(_, page)| page.data.iter().map(|event| event.id.tx_digest))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    let mut checkpoints: HashMap<TransactionDigest, u64> = HashMap::new();
+    for chunk in digests.chunks(50) {
+        let responses = client.read_api()
+            .multi_get_transactions_with_options(chunk.to_vec(), SuiTransactionBlockResponseOptions::new())
+            .await?;
+        for response in responses {
+            if let Some(checkpoint) = response.checkpoint {
+                checkpoints.insert(response.digest, checkpoint);
+            }
+        }
+    }
+    // checkpoint内的交易顺序以checkpoint记录的交易列表为准(digest顺序不是执行顺序)
+    let mut positions: HashMap<TransactionDigest, u64> = HashMap::new();
+    for checkpoint in checkpoints.values().copied().collect::<HashSet<_>>() {
+        let checkpoint = client.read_api().get_checkpoint(CheckpointId::SequenceNumber(checkpoint)).await?;
+        for (position, digest) in checkpoint.transactions.iter().enumerate() {
+            positions.insert(*digest, position as u64);
+        }
+    }
+    let order_key = |event: &SuiEvent| {
+        (
+            checkpoints.get(&event.id.tx_digest).copied().unwrap_or(u64::MAX),
+            positions.get(&event.id.tx_digest).copied().unwrap_or(u64::MAX),
+            event.id.event_seq,
+        )
+    };
+
+    let horizon = pages.iter()
+        .filter(|(_, page)| page.has_next_page)
+        .filter_map(|(_, page)| page.data.last().map(order_key))
+        .min();
+    let mut events: Vec<(&'static str, SuiEvent)> = pages.into_iter()
+        .flat_map(|(cursor_key, page)| page.data.into_iter().map(move |event| (cursor_key, event)))
+        .filter(|(_, event)| horizon.as_ref().map(|horizon| order_key(event) <= *horizon).unwrap_or(true))
+        .collect();
+    events.sort_by_key(|(_, event)| order_key(event));
+    Ok(events)
+}
+
 // /// 订阅指定module的事件
 // pub async fn subscribe(client: SuiClient, package_id: &str, module: &str) -> Result<(), anyhow::Error> {
 //     let mut subscribe_all = client.event_api()
@@ -122,8 +149,18 @@ pub async fn get_client() -> Result<SuiClient, anyhow::Error> {
     Ok(client)
 }
 
+/// 按事件类型查询事件; descending 时从最新的事件向前查询
+pub async fn listening_events(client: &SuiClient, package_id: &str, event_type: &str, event_id: Option<EventID>, limit: Option<usize>, descending: bool) -> Result<Page<SuiEvent, EventID>, anyhow::Error>{
+    match event_type {
+        "AccountBound" => listening_account_bound_events(client, package_id, event_id, limit, descending).await,
+        "DigitalServiceOpened" => listening_service_opened_events(client, package_id, event_id, limit, descending).await,
+        "NftLaunched" => listening_nft_launched_events(client, package_id, event_id, limit, descending).await,
+        _ => Err(anyhow!("Unknown event type:{}", event_type)),
+    }
+}
+
 /// 绑定账户事件
-pub async fn listening_account_bound_events(client: &SuiClient, package_id: &str, event_id: Option<EventID>, limit: Option<usize>) -> Result<Page<SuiEvent, EventID>, anyhow::Error>{
+pub async fn listening_account_bound_events(client: &SuiClient, package_id: &str, event_id: Option<EventID>, limit: Option<usize>, descending: bool) -> Result<Page<SuiEvent, EventID>, anyhow::Error>{
     let mut tag_str = String::from(package_id);
     tag_str.push_str("::");
     tag_str.push_str("digital_service");
@@ -138,7 +175,7 @@ pub async fn listening_account_bound_events(client: &SuiClient, package_id: &str
         struct_tag_filter,
         event_id,
         limit,
-        false,
+        descending,
     )
     .await?;
 
@@ -148,7 +185,7 @@ pub async fn listening_account_bound_events(client: &SuiClient, package_id: &str
 }
 
 /// 监听开通数字服务事件
-pub async fn listening_service_opened_events(client: &SuiClient, package_id: &str, event_id: Option<EventID>, limit: Option<usize>) -> Result<Page<SuiEvent, EventID>, anyhow::Error>{
+pub async fn listening_service_opened_events(client: &SuiClient, package_id: &str, event_id: Option<EventID>, limit: Option<usize>, descending: bool) -> Result<Page<SuiEvent, EventID>, anyhow::Error>{
     let mut tag_str = String::from(package_id);
     tag_str.push_str("::");
     tag_str.push_str("digital_service");
@@ -162,7 +199,7 @@ pub async fn listening_service_opened_events(client: &SuiClient, package_id: &st
         struct_tag_filter,
         event_id,
         limit,
-        false,
+        descending,
     )
     .await?;
 
@@ -170,7 +207,7 @@ pub async fn listening_service_opened_events(client: &SuiClient, package_id: &st
 }
 
 /// 监听发行NFT事件
-pub async fn listening_nft_launched_events(client: &SuiClient, package_id: &str, event_id: Option<EventID>, limit: Option<usize>) -> Result<Page<SuiEvent, EventID>, anyhow::Error>{
+pub async fn listening_nft_launched_events(client: &SuiClient, package_id: &str, event_id: Option<EventID>, limit: Option<usize>, descending: bool) -> Result<Page<SuiEvent, EventID>, anyhow::Error>{
     let mut tag_str = String::from(package_id);
     tag_str.push_str("::");
     tag_str.push_str("launch_service");
@@ -184,7 +221,7 @@ pub async fn listening_nft_launched_events(client: &SuiClient, package_id: &str,
         struct_tag_filter,
         event_id,
         limit,
-        false,
+        descending,
     )
     .await?;
 
@@ -8,16 +8,23 @@ use amqprs::{
     BasicProperties
 };
 use anyhow::{Context};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
 use tokio::time::{self, sleep, Duration};
 use tracing::{debug, error, info};
 
-use crate::kv_store::{KVStore, RocksDB};
+use crate::kv_store::{KVStore, StoreError};
 
 pub mod service_opened_consumer;
 pub mod nft_launched_consumer;
 pub mod coin_published_producer;
 pub mod nft_published_producer;
+pub mod nft_launched_producer;
+pub mod authorization_consumer;
+pub mod authorization_updated_producer;
+pub mod rewards_harvested_producer;
+pub mod treasury_snapshot_producer;
 
 /// Load the application configuration.
 /// Uses environment variable, but in reality it might use some other external configuration source.
@@ -63,6 +70,63 @@ pub enum RabbitError {
     ConnectionLost(String),
 }
 
+/// 事件消息信封
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub event_id: String,
+    pub tx_digest: String,
+    pub event_seq: u64,
+    pub sender: String,
+    pub timestamp_ms: Option<u64>,
+    pub package_id: String,
+    pub module: String,
+    pub event_type: String,
+    pub struct_type: String,
+    pub payload: Value,
+}
+
+impl EventEnvelope {
+
+    pub fn new(event: &SuiEvent, event_type: &str) -> Self {
+        Self {
+            event_id: String::from(event.id),
+            tx_digest: event.id.tx_digest.to_string(),
+            event_seq: event.id.event_seq,
+            sender: event.sender.to_string(),
+            timestamp_ms: event.timestamp_ms,
+            package_id: event.package_id.to_hex_literal(),
+            module: event.transaction_module.to_string(),
+            event_type: event_type.to_owned(),
+            struct_type: event.type_.to_string(),
+            payload: event.parsed_json.clone(),
+        }
+    }
+
+    /// AMQP消息属性
+    pub fn properties(&self) -> BasicProperties {
+        let mut properties = BasicProperties::default();
+        properties
+            .with_persistence(true)
+            .with_content_type("application/json")
+            .with_message_id(&self.event_id)
+            .with_message_type(&self.event_type);
+        if let Some(timestamp_ms) = self.timestamp_ms {
+            // AMQP timestamp以秒为单位
+            properties.with_timestamp(timestamp_ms / 1000);
+        }
+        properties.finish()
+    }
+}
+
+/// 解析事件消息, 兼容未封装信封的旧消息
+pub fn event_payload(json: &str) -> Result<Value, serde_json::Error> {
+    let value: Value = serde_json::from_str(json)?;
+    if value.get("event_id").is_some() && value.get("payload").is_some() {
+        return Ok(value["payload"].clone())
+    }
+    Ok(value)
+}
+
 pub async fn declare_exchange(cfg: Arc<Config>) -> anyhow::Result<()> {
     let connection = Connection::open(
         &OpenConnectionArguments::new(&cfg.host, cfg.port, &cfg.username, &cfg.password)
@@ -143,9 +207,14 @@ pub async fn delete_exchange(cfg: Arc<Config>) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn publish_events(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id: &str, db: RocksDB) -> anyhow::Result<()> {
+pub async fn publish_events<S: KVStore>(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id: &str, db: S) -> anyhow::Result<()> {
+    replay_events(cfg, events, package_id, Some(db)).await
+}
+
+/// 重新发布事件, db为None时忽略去重标记且不写入标记
+pub async fn replay_events<S: KVStore>(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id: &str, db: Option<S>) -> anyhow::Result<()> {
     loop {
-        let result = process(cfg.clone(), &events, package_id, db.clone()).await;
+        let result = process(cfg.clone(), &events, package_id, db.as_ref()).await;
         match result {
             Ok(value) => {
                 // Not actually implemented right now.
@@ -161,7 +230,7 @@ pub async fn publish_events(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id
     }
 }
 
-pub async fn process(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id: &str, db: RocksDB) -> anyhow::Result<()> {
+pub async fn process<S: KVStore>(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id: &str, db: Option<&S>) -> anyhow::Result<()> {
     debug!("starting producer task");
 
     let connection = Connection::open(
@@ -198,22 +267,30 @@ pub async fn process(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id: &str,
         if event_type.is_none() {
             continue;
         }
-        if event_exists(&event, &db) {
-            continue;
+        if let Some(db) = db {
+            if event_exists(&event, db)? {
+                continue;
+            }
         }
-        let routing_key = "bassinet.".to_owned() + &event_type.unwrap();
+        let event_type = event_type.unwrap();
+        let routing_key = "bassinet.".to_owned() + &event_type;
         // create arguments for basic_publish
         let args = BasicPublishArguments::new(exchange_name, routing_key.as_str());
-        let content = serde_json::to_string_pretty(&event.parsed_json).unwrap();
+        let envelope = EventEnvelope::new(event, &event_type);
+        let content = serde_json::to_string_pretty(&envelope).unwrap();
         channel
         .basic_publish(
-            BasicProperties::default().with_persistence(true).finish(),
+            envelope.properties(),
             content.as_bytes().to_vec(),
             args,
         )
         .await
         .unwrap();
-        mark_event(&event, &db);
+        if let Some(db) = db {
+            if let Err(err) = mark_event(&event, db) {
+                error!("mark event {} failed: {err:?}", String::from(event.id));
+            }
+        }
         tracing::info!("发布事件:{}, routing_key:{}", content, routing_key);
     }
 
@@ -233,7 +310,7 @@ pub async fn process(cfg: Arc<Config>, events: &Vec<SuiEvent>, package_id: &str,
     // }
 }
 
-fn event_type(event: &SuiEvent, package_id: &str) -> Option<String> {
+pub fn event_type(event: &SuiEvent, package_id: &str) -> Option<String> {
     if event.package_id.to_string().as_str() == package_id {
         let event_type = event.type_.name.as_str();
         if event_type == "AccountBound" {
@@ -247,16 +324,16 @@ fn event_type(event: &SuiEvent, package_id: &str) -> Option<String> {
     Option::None
 }
 
-fn event_exists(event: &SuiEvent, db: &RocksDB) -> bool {
+pub fn event_exists<S: KVStore>(event: &SuiEvent, db: &S) -> Result<bool, StoreError> {
     // let event_type = event.type_.name.as_str();
     // if event_type == "NftLaunched" {
     //     return false
     // }
-    db.find(String::from(event.id).as_str()).is_some()
+    db.event_marked(String::from(event.id).as_str())
 }
 
-fn mark_event(event: &SuiEvent, db: &RocksDB) {
-    db.save(String::from(event.id).as_str(), "1");
+fn mark_event<S: KVStore>(event: &SuiEvent, db: &S) -> Result<(), StoreError> {
+    db.mark_event(String::from(event.id).as_str())
 }
 
 // pub struct MyConsumer {
@@ -0,0 +1,234 @@
+use std::sync::Arc;
+use amqprs::{
+    callbacks::{DefaultChannelCallback, DefaultConnectionCallback},
+    channel::{
+        BasicAckArguments, BasicCancelArguments, BasicNackArguments, BasicConsumeArguments, QueueBindArguments, QueueDeclareArguments
+    },
+    connection::{Connection, OpenConnectionArguments},
+};
+use anyhow::{anyhow, Context};
+use serde_json::Value;
+use tokio::time::{sleep, Duration};
+use tracing::{debug, error, info};
+
+use crate::{events_mq::{authorization_updated_producer, event_payload, nft_launched_consumer::get_collection}, kv_store::{now_millis, KVStore, StoreError, WriteBatch}, repository::{CoinPackageRepository, CollectionRepository, CreatorCollection, CreatorRepository, LaunchParams, NftPackageRepository, OperationRecord, OperationRepository, PortfolioRepository, WorkflowStatus}, sui_service::{authorization::{authorize_app, reauthorize_collection, revoke_app, revoke_collection, AppTarget, AuthorizationCommand}, nft_service::NftConfigInfo, BassinetCoinPublishedResult, NftPublishedResult}, validation::validate_address};
+
+use super::Config;
+
+pub async fn authorization_consume<S: KVStore>(cfg: Arc<Config>, db: S) -> anyhow::Result<()> {
+    loop {
+        let result = process(cfg.clone(), db.clone()).await;
+        match result {
+            Ok(value) => {
+                return Ok(value);
+            }
+            Err(err) => {
+                error!("RabbitMQ connection returned error: {err:?}");
+                sleep(Duration::from_millis(1000)).await;
+                info!("ready to restart consumer task");
+            }
+        }
+    }
+}
+
+async fn process<S: KVStore>(cfg: Arc<Config>, db: S) -> anyhow::Result<()> {
+    debug!("starting authorization task");
+
+    let connection = Connection::open(
+        &OpenConnectionArguments::new(&cfg.host, cfg.port, &cfg.username, &cfg.password)
+            .virtual_host(&cfg.virtual_host),
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "can't connect to RabbitMQ server at {}:{}",
+            cfg.host, cfg.port
+        )
+    })?;
+
+    connection
+        .register_callback(DefaultConnectionCallback)
+        .await
+        .context("registering connection callback failed")?;
+
+    let channel = connection
+        .open_channel(None)
+        .await
+        .context("opening channel failed")?;
+    channel
+        .register_callback(DefaultChannelCallback)
+        .await
+        .context("registering channel callback failed")?;
+
+    let (queue_name, _, _) = channel
+        .queue_declare(QueueDeclareArguments::durable_client_named("sui_authorization_command").durable(true).exclusive(true).auto_delete(false).finish())
+        .await
+        .context("failed to declare queue")?
+        .expect("when no_wait is false (default) then we should have a value");
+    debug!("declared queue '{queue_name}'");
+
+    let exchange_name = "bassinet.topic";
+    debug!("binding exchange {exchange_name} -> queue {queue_name}");
+    channel
+        .queue_bind(QueueBindArguments::new(&queue_name, exchange_name, "bassinet.AuthorizationCommand"))
+        .await
+        .context("queue binding failed")?;
+
+    let consume_args = BasicConsumeArguments::new(&queue_name, "AuthorizationCommand").auto_ack(false).finish();
+    let (ctag, mut rx) = channel.basic_consume_rx(consume_args).await.unwrap();
+    let new_channel = channel.clone();
+    let operations = OperationRepository::new(db.clone());
+    let portfolios = PortfolioRepository::new(db.clone());
+    let jh = tokio::spawn(async move {
+        let provider = std::env::var("PROVIDER").expect("PROVIDER must be set");
+        let host = std::env::var("HOST").expect("HOST must be set");
+        let key_store_path = std::env::var("KEY_STORE_PATH").expect("KEY_STORE_PATH must be set");
+        while let Some(msg) = rx.recv().await {
+            let content = msg.content.unwrap();
+            let deliver = msg.deliver.unwrap();
+            let json = std::str::from_utf8(&content).unwrap();
+            info!("consume delivery {}, content: {}", deliver, json);
+
+            // 处理命令(address,command,request_id及命令参数), 每个命令都记录为一条操作记录
+            let value: Value = event_payload(json).unwrap_or(Value::Null);
+            let address = value.get("address").and_then(|address| address.as_str()).unwrap_or("unknown");
+            let source = value.get("request_id").and_then(|request_id| request_id.as_str()).unwrap_or("mq");
+            let command = serde_json::from_value::<AuthorizationCommand>(value.clone());
+            let mut record = match &command {
+                Ok(command) => OperationRecord::new(command.kind(), address, command.target(), value.clone(), source),
+                Err(_) => OperationRecord::new("invalid", address, "", value.clone(), source),
+            };
+            let result = match command {
+                Ok(command) => execute_command(&db, &provider, &host, &key_store_path, address, &command).await,
+                Err(err) => Err(anyhow!("invalid authorization command: {}", err)),
+            };
+
+            let collection = match result {
+                Ok((tx_digest, collection)) => {
+                    record.status = WorkflowStatus::Completed;
+                    record.tx_digest = Some(tx_digest);
+                    collection
+                }
+                Err(err) => {
+                    tracing::error!("message:{}, error:{:?}", json, err);
+                    record.status = WorkflowStatus::Failed;
+                    record.error = Some(err.to_string());
+                    None
+                }
+            };
+            if let Err(err) = save_operation(&db, &operations, &portfolios, &record, collection.as_ref()) {
+                tracing::error!("message:{}, save operation:{} failed:{:?}", json, record.id, err);
+                if record.tx_digest.is_none() {
+                    // 未发送交易, 不确认消息, 由RabbitMQ重新投递
+                    let args = BasicNackArguments::new(deliver.delivery_tag(), false, true);
+                    new_channel.basic_nack(args).await.unwrap();
+                    sleep(Duration::from_secs(10)).await;
+                    continue;
+                }
+                // 交易已执行, 重新投递会再次发送交易: 重试保存直到成功
+                loop {
+                    sleep(Duration::from_secs(10)).await;
+                    match save_operation(&db, &operations, &portfolios, &record, collection.as_ref()) {
+                        Ok(()) => break,
+                        Err(err) => tracing::error!("message:{}, retry save operation:{} failed:{:?}", json, record.id, err),
+                    }
+                }
+            }
+
+            // 发送操作结果
+            let _ = authorization_updated_producer::produce_authorization_updated(cfg.clone(), &record).await;
+
+            // 失败的命令同样确认, 由请求方根据结果重新发起
+            let args = BasicAckArguments::new(deliver.delivery_tag(), false);
+            new_channel.basic_ack(args).await.unwrap();
+        }
+    });
+    assert!(jh.await.is_err());
+    channel.basic_cancel(BasicCancelArguments::new(&ctag)).await.unwrap();
+
+    Err(anyhow!("consumer panic"))
+}
+
+/// 在同一批次中保存操作记录和更新的collection记录
+fn save_operation<S: KVStore>(db: &S, operations: &OperationRepository<S>, portfolios: &PortfolioRepository<S>, record: &OperationRecord, collection: Option<&CreatorCollection>) -> Result<(), StoreError> {
+    let mut batch = WriteBatch::new();
+    if let Some(collection) = collection {
+        portfolios.stage_collection(&mut batch, collection)?;
+    }
+    operations.stage_save(&mut batch, record)?;
+    db.write(batch)
+}
+
+/// 执行授权命令, 返回交易digest和需要更新的collection记录
+pub async fn execute_command<S: KVStore>(db: &S, provider: &str, host: &str, key_store_path: &str, address: &str, command: &AuthorizationCommand) -> Result<(String, Option<CreatorCollection>), anyhow::Error> {
+    validate_address("address", address)?;
+    let coin = creator_coin(db, address)?;
+    match command {
+        AuthorizationCommand::RevokeCollection { collection_id } => {
+            let (mut collection, nft) = creator_collection(db, address, collection_id)?;
+            if collection.revoked_at.is_some() {
+                return Err(anyhow!("collection {} already revoked", collection_id))
+            }
+            let tx_digest = revoke_collection(provider, &coin, &nft, key_store_path).await?;
+            collection.revoked_at = Some(now_millis());
+            collection.updated_at = now_millis();
+            Ok((tx_digest, Some(collection)))
+        }
+        AuthorizationCommand::ReauthorizeCollection { collection_id, limit, rewards_quantity, minting_price } => {
+            let (mut collection, nft) = creator_collection(db, address, collection_id)?;
+            let (collection_url, description) = get_collection(collection_id, host).await?;
+            let nft_config = NftConfigInfo {
+                description,
+                collection_id: collection_id.to_owned(),
+                collection_url,
+                limit: *limit,
+                rewards_quantity: *rewards_quantity,
+                minting_price: *minting_price,
+            };
+            let revoke_first = collection.revoked_at.is_none();
+            let tx_digest = reauthorize_collection(provider, &coin, &nft, &nft_config, revoke_first, key_store_path).await?;
+            collection.launch = Some(LaunchParams { limit: *limit, rewards_quantity: *rewards_quantity, minting_price: *minting_price });
+            collection.revoked_at = None;
+            collection.updated_at = now_millis();
+            Ok((tx_digest, Some(collection)))
+        }
+        AuthorizationCommand::AuthorizeApp { app, app_name, rewards_quantity, minting_limit } => {
+            validate_app(app)?;
+            let tx_digest = authorize_app(provider, &coin, app, app_name, *rewards_quantity, *minting_limit, key_store_path).await?;
+            Ok((tx_digest, None))
+        }
+        AuthorizationCommand::RevokeApp { app } => {
+            validate_app(app)?;
+            let tx_digest = revoke_app(provider, &coin, app, key_store_path).await?;
+            Ok((tx_digest, None))
+        }
+    }
+}
+
+/// 创作者的代币合约(使用其中的AdminCap ID)
+fn creator_coin<S: KVStore>(db: &S, address: &str) -> Result<BassinetCoinPublishedResult, anyhow::Error> {
+    let package_id = CreatorRepository::new(db.clone()).coin_package_id(address)?
+        .ok_or(anyhow!("no coin package recorded for {}", address))?;
+    CoinPackageRepository::new(db.clone()).find(&package_id)?
+        .ok_or(anyhow!("coin package {} record not found", package_id))
+}
+
+/// 创作者名下的collection及其NFT合约
+fn creator_collection<S: KVStore>(db: &S, address: &str, collection_id: &str) -> Result<(CreatorCollection, NftPublishedResult), anyhow::Error> {
+    let collection = PortfolioRepository::new(db.clone()).find_collection(collection_id)?
+        .ok_or(anyhow!("collection {} not found", collection_id))?;
+    if collection.wallet_address != address {
+        return Err(anyhow!("collection {} belongs to {}", collection_id, collection.wallet_address))
+    }
+    let package_id = CollectionRepository::new(db.clone()).package_id(collection_id)?
+        .ok_or(anyhow!("collection {} has no nft package", collection_id))?;
+    let nft = NftPackageRepository::new(db.clone()).find(&package_id)?
+        .ok_or(anyhow!("nft package {} record not found", package_id))?;
+    Ok((collection, nft))
+}
+
+fn validate_app(app: &AppTarget) -> Result<(), anyhow::Error> {
+    validate_address("app.package_id", &app.package_id)?;
+    validate_address("app.object_id", &app.object_id)?;
+    Ok(())
+}
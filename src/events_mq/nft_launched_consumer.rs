@@ -1,22 +1,22 @@
-use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, str::FromStr, sync::{Arc, Mutex}};
 use amqprs::{
     callbacks::{DefaultChannelCallback, DefaultConnectionCallback},
     channel::{
-        BasicAckArguments, BasicCancelArguments, BasicConsumeArguments, QueueBindArguments, QueueDeclareArguments
+        BasicAckArguments, BasicCancelArguments, BasicNackArguments, BasicConsumeArguments, QueueBindArguments, QueueDeclareArguments
     },
     connection::{Connection, OpenConnectionArguments},
 };
 use anyhow::{anyhow, Context};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use serde_json::{de, Value};
+use serde_json::Value;
 use sui_sdk::types::base_types::ObjectID;
 use tokio::time::{sleep, Duration};
 use tracing::{debug, error, info, warn};
 
 // use super::RabbitError;
 
-use crate::{events_mq::nft_published_producer, kv_store::{KVStore, RocksDB}, sui_service::{nft_service::{NftConfigInfo, NftServiceConfig}, BassinetCoinPublishedResult}};
+use crate::{events_mq::{event_payload, nft_launched_producer, nft_published_producer}, kv_store::{KVStore, StoreError, WriteBatch}, move_build::cached_builder_from_env, repository::{CoinPackageRepository, CollectionRepository, CreatorCollection, CreatorRepository, DeferredMessage, NftPackageRepository, PortfolioRepository, WorkflowRecord, WorkflowRepository, WorkflowStatus}, sui_service::nft_service::{NftConfigInfo, NftServiceConfig}, validation::NftLaunchRequest};
 
 use super::Config;
 
@@ -37,7 +37,7 @@ pub struct NftPublishedMessage {
     pub minting_price: u64,
 }
 
-pub async fn nft_launched_consume(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
+pub async fn nft_launched_consume<S: KVStore>(cfg: Arc<Config>, db: S) -> anyhow::Result<()> {
     loop {
         let result = process(cfg.clone(), &db).await;
         match result {
@@ -55,7 +55,7 @@ pub async fn nft_launched_consume(cfg: Arc<Config>, db:RocksDB) -> anyhow::Resul
     }
 }
 
-async fn process(cfg: Arc<Config>, db:&RocksDB) -> anyhow::Result<()> {
+async fn process<S: KVStore>(cfg: Arc<Config>, db: &S) -> anyhow::Result<()> {
     debug!("starting nft_launched task");
 
     let connection = Connection::open(
@@ -110,12 +110,19 @@ async fn process(cfg: Arc<Config>, db:&RocksDB) -> anyhow::Result<()> {
 
     let (ctag, mut rx) = channel.basic_consume_rx(consume_args).await.unwrap();
     let new_channel = channel.clone();
-    let rocksdb = db.clone();
+    let creators = CreatorRepository::new(db.clone());
+    let coin_packages = CoinPackageRepository::new(db.clone());
+    let nft_packages = NftPackageRepository::new(db.clone());
+    let collections = CollectionRepository::new(db.clone());
+    let workflows = WorkflowRepository::new(db.clone());
+    let portfolios = PortfolioRepository::new(db.clone());
+    let build_cache = db.clone();
     let jh = tokio::spawn(async move {
         let dir_path = std::env::var("CONTRACTS_DIR_PATH").expect("CONTRACTS_DIR_PATH must be set");
         let provider = std::env::var("PROVIDER").expect("PROVIDER must be set");
         let host = std::env::var("HOST").expect("HOST must be set");
         let key_store_path = std::env::var("KEY_STORE_PATH").expect("KEY_STORE_PATH must be set");
+        let builder = cached_builder_from_env(build_cache);
         while let Some(msg) = rx.recv().await {
             let content = msg.content.unwrap();
             let deliver = msg.deliver.unwrap();
@@ -125,36 +132,120 @@ async fn process(cfg: Arc<Config>, db:&RocksDB) -> anyhow::Result<()> {
                 deliver,
                 json
             );
-            // 处理消息(public_key,address,collection_id,limit,rewards_quantity,minting_price)
-            let value : Value = serde_json::from_str(json).unwrap();
-            let public_key = value.get("public_key").unwrap().as_str().unwrap();
-            let address = value.get("address").unwrap().as_str().unwrap();
-            let collection_id = value.get("collection_id").unwrap().as_str().unwrap();
-            let limit_str = value.get("limit").unwrap().as_str().unwrap();
-            let limit = limit_str.parse::<u64>().unwrap();
-            let rewards_quantity_str = value.get("rewards_quantity").unwrap().as_str().unwrap();
-            let rewards_quantity = rewards_quantity_str.parse::<u64>().unwrap();
-            let minting_price_str = value.get("minting_price").unwrap().as_str().unwrap();
-            let minting_price = minting_price_str.parse::<u64>().unwrap();
+            // 处理消息(public_key,address,collection_id,limit,rewards_quantity,minting_price,variant,params), 校验失败的消息确认后不再处理
+            let value : Value = event_payload(json).unwrap_or(Value::Null);
+            let request = match NftLaunchRequest::from_payload(&value) {
+                Ok(request) => request,
+                Err(err) => {
+                    tracing::error!("message:{}, rejected:{}", json, err);
+                    let collection_id = value.get("collection_id").and_then(|collection_id| collection_id.as_str()).unwrap_or("unknown");
+                    let record = WorkflowRecord::new("nft_launch", collection_id, WorkflowStatus::Failed, Some(err.to_string()));
+                    if let Err(err) = workflows.save(&record) {
+                        tracing::error!("message:{}, save workflow failed:{:?}", json, err);
+                    }
+                    let args = BasicAckArguments::new(deliver.delivery_tag(), false);
+                    new_channel.basic_ack(args).await.unwrap();
+                    continue;
+                }
+            };
+            let public_key = request.public_key.as_str();
+            let address = request.address.as_str();
+            let collection_id = request.collection_id.as_str();
+            let launch = request.launch;
+            let template = request.template;
+            // 创作者名下已有的collection记录; 已完成或属于其他创作者时不再发布
+            let existing = match portfolios.find_collection(collection_id) {
+                Ok(existing) => existing,
+                Err(err) => {
+                    tracing::error!("message:{}, error:{:?}", json, err);
+                    let args = BasicNackArguments::new(deliver.delivery_tag(), false, true);
+                    new_channel.basic_nack(args).await.unwrap();
+                    sleep(Duration::from_secs(10)).await;
+                    continue;
+                }
+            };
+            let mut entry = match existing {
+                Some(existing) if existing.wallet_address != address => {
+                    tracing::error!("message:{}, collection already belongs to {}", json, existing.wallet_address);
+                    let record = WorkflowRecord::new("nft_launch", collection_id, WorkflowStatus::Failed, Some(format!("collection belongs to {}", existing.wallet_address)));
+                    if let Err(err) = workflows.save(&record) {
+                        tracing::error!("message:{}, save workflow failed:{:?}", json, err);
+                    }
+                    let args = BasicAckArguments::new(deliver.delivery_tag(), false);
+                    new_channel.basic_ack(args).await.unwrap();
+                    continue;
+                }
+                Some(existing) if existing.status == WorkflowStatus::Completed => {
+                    tracing::warn!("message:{}, collection already launched as {:?}, skipped", json, existing.package_id);
+                    let args = BasicAckArguments::new(deliver.delivery_tag(), false);
+                    new_channel.basic_ack(args).await.unwrap();
+                    continue;
+                }
+                Some(mut existing) => {
+                    existing.launch = Some(launch);
+                    existing.template_variant = Some(template.variant.label());
+                    existing
+                }
+                None => CreatorCollection::new(address, collection_id, Some(launch), Some(template.variant.label())),
+            };
             let dir = PathBuf::from_str(&dir_path).unwrap();
             let creator = address;
             let package_id = "0x0";
             // 从RocksDB中获取
-            let coin_package_id = rocksdb.find(&(address.to_owned() + "_bassinet_coin"));
+            let coin_package_id = creators.coin_package_id(address);
+            if coin_package_id.is_err() {
+                // 存储读取失败, 不确认消息, 由RabbitMQ重新投递
+                tracing::error!("message:{}, error:{:?}", json, coin_package_id.err());
+                let args = BasicNackArguments::new(deliver.delivery_tag(), false, true);
+                new_channel.basic_nack(args).await.unwrap();
+                sleep(Duration::from_secs(10)).await;
+                continue;
+            }
+            let coin_package_id = coin_package_id.unwrap();
             if coin_package_id.is_none() {
-                tracing::error!("message:{}, error:Bassinet Coin package id not exist", json);
-                // TODO 重大事件，其他通知方式
+                // 创作者的DigitalServiceOpened尚未处理完成, 延后到代币合约发布后重新投递
+                tracing::warn!("message:{}, Bassinet Coin package id not exist, deferred", json);
+                // 延后消息、处理状态和创作者的collection记录一起写入
+                let record = WorkflowRecord::new("nft_launch", collection_id, WorkflowStatus::Deferred, None);
+                entry.update(WorkflowStatus::Deferred, None);
+                let mut batch = WriteBatch::new();
+                let staged = workflows.stage_save(&mut batch, &record)
+                    .and_then(|_| portfolios.stage_collection(&mut batch, &entry))
+                    .and_then(|_| defer_nft_launched(&workflows, address, json, batch));
+                if let Err(err) = staged {
+                    // 延后消息未保存, 不确认消息, 由RabbitMQ重新投递
+                    tracing::error!("message:{}, defer failed:{:?}", json, err);
+                    let args = BasicNackArguments::new(deliver.delivery_tag(), false, true);
+                    new_channel.basic_nack(args).await.unwrap();
+                    sleep(Duration::from_secs(10)).await;
+                    continue;
+                }
+                // 再次检查, 避免与代币合约发布完成产生竞争
+                if matches!(creators.coin_package_id(address), Ok(Some(_))) {
+                    if let Err(err) = republish_deferred_nft_launched(cfg.clone(), &workflows, address).await {
+                        tracing::error!("message:{}, republish deferred failed:{:?}", json, err);
+                    }
+                }
             }else {
+                // 之前重新投递失败而保留的延后消息
+                if let Err(err) = republish_deferred_nft_launched(cfg.clone(), &workflows, address).await {
+                    tracing::error!("message:{}, republish deferred failed:{:?}", json, err);
+                }
                 let coin_package_id = coin_package_id.unwrap();
-                let coin_info = rocksdb.find(&coin_package_id).unwrap();
-
-                let bassinet_coin: BassinetCoinPublishedResult = serde_json::from_str(&coin_info).unwrap();
+                let bassinet_coin = coin_packages.find(&coin_package_id);
                 // 获取collection_id信息
-                let collection_info = get_collection(collection_id, host.as_str()).await;
+                let collection_info = if matches!(bassinet_coin, Ok(Some(_))) {
+                    get_collection(collection_id, host.as_str()).await
+                } else {
+                    Err(anyhow!("Bassinet Coin package:{} record invalid, {:?}", coin_package_id, bassinet_coin))
+                };
                 if collection_info.is_err() {
-                    tracing::error!("message:{}, error:{:?}", json, collection_info.err());
+                    let err = collection_info.err().unwrap();
+                    tracing::error!("message:{}, error:{:?}", json, err);
+                    record_launch(&workflows, &portfolios, &mut entry, WorkflowStatus::Failed, Some(err.to_string()), json);
                     // TODO 重大事件，其他通知方式
                 }else {
+                    let bassinet_coin = bassinet_coin.unwrap().unwrap();
                     let (collection_url, description) = collection_info.unwrap();
                     let mut config = NftServiceConfig {
                         account: public_key.to_owned(),
@@ -165,36 +256,52 @@ async fn process(cfg: Arc<Config>, db:&RocksDB) -> anyhow::Result<()> {
                         provider: provider.to_owned(),
                         coin_package_id: coin_package_id.to_owned(),
                         package_id: package_id.to_owned(),
+                        template: template,
                     };
                     // 发布NFT
-                    let published_result = config.launch(&key_store_path).await;
+                    let published_result = config.launch(builder.as_ref(), &bassinet_coin, &key_store_path).await;
                     if published_result.is_err() {
-                        tracing::error!("message:{}, error:{:?}", json, published_result.err());
+                        let err = published_result.err().unwrap();
+                        tracing::error!("message:{}, error:{:?}", json, err);
+                        record_launch(&workflows, &portfolios, &mut entry, WorkflowStatus::Failed, Some(err.to_string()), json);
                         // TODO 重大事件，其他通知方式
                     }else {
-                        // json序列化保存到rocksdb
+                        // 保存发布结果
                         let publishing_reslut = published_result.unwrap();
                         let package_id = publishing_reslut.package_id.clone();
-                        let json = serde_json::to_string(&publishing_reslut).unwrap();
-                        rocksdb.save(package_id.as_str(), json.as_str());
-                        // collection_id对应的NFT package_id
-                        rocksdb.save(collection_id, package_id.as_str());
+                        // 发布结果、collection_id对应的NFT package_id、创作者的collection和处理状态一起写入
+                        let record = WorkflowRecord::new("nft_launch", collection_id, WorkflowStatus::Published, None);
+                        entry.published(&publishing_reslut);
+                        entry.update(WorkflowStatus::Published, None);
+                        let mut batch = WriteBatch::new();
+                        collections.stage_package_id(&mut batch, collection_id, package_id.as_str());
+                        let staged = nft_packages.stage_save(&mut batch, &publishing_reslut)
+                            .and_then(|_| portfolios.stage_collection(&mut batch, &entry))
+                            .and_then(|_| workflows.stage_save(&mut batch, &record))
+                            .and_then(|_| workflows.write(batch));
+                        if let Err(err) = staged {
+                            tracing::error!("message:{}, save package:{} failed:{:?}", json, package_id, err);
+                        }
         
                         let config_info = NftConfigInfo{
                             description: description.to_owned(),
                             collection_id: collection_id.to_owned(),
                             collection_url: collection_url.to_owned(),
-                            limit: limit,
-                            rewards_quantity: rewards_quantity,
-                            minting_price: minting_price,
+                            limit: launch.limit,
+                            rewards_quantity: launch.rewards_quantity,
+                            minting_price: launch.minting_price,
                         };
                         let policy_id = ObjectID::from_hex_literal(&publishing_reslut.policy_id).unwrap();
                         let mint_id = ObjectID::from_hex_literal(&publishing_reslut.mint_id).unwrap();
                         // 初始配置NFT
                         let init_result = config.init_config(&config_info, policy_id, mint_id, &key_store_path).await;
                         if init_result.is_err() {
-                            tracing::error!("初始化配置:message:{}, package_id:{}, error:{:?}", json, package_id, init_result.err());
+                            let err = init_result.err().unwrap();
+                            tracing::error!("初始化配置:message:{}, package_id:{}, error:{:?}", json, package_id, err);
                             // TODO 重大事件，其他通知方式
+                            record_launch(&workflows, &portfolios, &mut entry, WorkflowStatus::Failed, Some(err.to_string()), json);
+                        }else {
+                            record_launch(&workflows, &portfolios, &mut entry, WorkflowStatus::Completed, None, json);
                         }
     
                         let message = NftPublishedMessage {
@@ -208,9 +315,9 @@ async fn process(cfg: Arc<Config>, db:&RocksDB) -> anyhow::Result<()> {
                             admin_cap_id: bassinet_coin.admin_cap_id,
                             description: description,
                             collection_url: collection_url,
-                            limit: limit,
-                            rewards_quantity: rewards_quantity,
-                            minting_price: minting_price,
+                            limit: launch.limit,
+                            rewards_quantity: launch.rewards_quantity,
+                            minting_price: launch.minting_price,
                         };
     
                         // 发送mq消息
@@ -234,7 +341,60 @@ async fn process(cfg: Arc<Config>, db:&RocksDB) -> anyhow::Result<()> {
     // }
 }
 
-async fn get_collection(collection_id: &str, host: &str) -> Result<(String, String), anyhow::Error> {
+/// 一起写入NFT发布的处理状态和创作者的collection记录
+fn record_launch<S: KVStore>(workflows: &WorkflowRepository<S>, portfolios: &PortfolioRepository<S>, entry: &mut CreatorCollection, status: WorkflowStatus, error: Option<String>, json: &str) {
+    let record = WorkflowRecord::new("nft_launch", &entry.collection_id, status, error.clone());
+    entry.update(status, error);
+    let mut batch = WriteBatch::new();
+    let staged = portfolios.stage_collection(&mut batch, entry)
+        .and_then(|_| workflows.stage_save(&mut batch, &record))
+        .and_then(|_| workflows.write(batch));
+    if let Err(err) = staged {
+        tracing::error!("message:{}, save workflow failed:{:?}", json, err);
+    }
+}
+
+/// 延后消息的互斥锁(两个消费者任务共享)
+static DEFERRED_LOCK: Mutex<()> = Mutex::new(());
+
+/// 延后处理NftLaunched消息, 等待创作者代币合约发布; 与batch中的其他记录一起提交
+pub fn defer_nft_launched<S: KVStore>(workflows: &WorkflowRepository<S>, address: &str, json: &str, mut batch: WriteBatch) -> Result<(), StoreError> {
+    let _guard = DEFERRED_LOCK.lock().unwrap();
+    let mut deferred = workflows.deferred_nft_launched(address)?;
+    if !deferred.iter().any(|item| item.content == json) {
+        deferred.push(DeferredMessage::new(json));
+    }
+    workflows.stage_deferred_nft_launched(&mut batch, address, &deferred)?;
+    workflows.write(batch)
+}
+
+/// 删除已重新投递的延后消息, 保留重新投递期间新延后的消息
+pub fn remove_deferred_nft_launched<S: KVStore>(workflows: &WorkflowRepository<S>, address: &str, published: &[DeferredMessage]) -> Result<(), StoreError> {
+    let _guard = DEFERRED_LOCK.lock().unwrap();
+    let remaining: Vec<DeferredMessage> = workflows.deferred_nft_launched(address)?
+        .into_iter()
+        .filter(|message| !published.iter().any(|item| item.content == message.content))
+        .collect();
+    let mut batch = WriteBatch::new();
+    workflows.stage_deferred_nft_launched(&mut batch, address, &remaining)?;
+    workflows.write(batch)
+}
+
+/// 重新投递创作者被延后的NftLaunched消息, 投递成功后才删除; 投递失败时保留, 下次处理该创作者的消息时重新投递
+pub async fn republish_deferred_nft_launched<S: KVStore>(cfg: Arc<Config>, workflows: &WorkflowRepository<S>, address: &str) -> anyhow::Result<usize> {
+    let deferred = {
+        let _guard = DEFERRED_LOCK.lock().unwrap();
+        workflows.deferred_nft_launched(address)?
+    };
+    if deferred.is_empty() {
+        return Ok(0)
+    }
+    nft_launched_producer::produce_nft_launched(cfg, &deferred).await?;
+    remove_deferred_nft_launched(workflows, address, &deferred)?;
+    Ok(deferred.len())
+}
+
+pub async fn get_collection(collection_id: &str, host: &str) -> Result<(String, String), anyhow::Error> {
     let mut count = 0;
     while count < 60 {
         count += 1;
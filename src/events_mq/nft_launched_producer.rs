@@ -0,0 +1,109 @@
+use std::{sync::Arc};
+
+use amqprs::{
+    callbacks::{DefaultChannelCallback, DefaultConnectionCallback},
+    channel::{BasicPublishArguments},
+    connection::{Connection, OpenConnectionArguments},
+    BasicProperties
+};
+use anyhow::{Context};
+use tokio::time::{self, sleep, Duration};
+use tracing::{debug, error, info};
+
+use crate::repository::DeferredMessage;
+
+use super::Config;
+
+
+/// 重新投递的尝试次数, 失败后由调用方保留延后消息
+const PUBLISH_ATTEMPTS: u32 = 5;
+
+/// 重新投递被延后的NftLaunched消息
+pub async fn produce_nft_launched(cfg: Arc<Config>, contents: &[DeferredMessage]) -> anyhow::Result<()> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = process(cfg.clone(), contents).await;
+        match result {
+            Ok(value) => {
+                // Not actually implemented right now.
+                // warn!("exiting in response to a shutdown command");
+                return Ok(value);
+            }
+            Err(err) if attempt >= PUBLISH_ATTEMPTS => return Err(err),
+            Err(err) => {
+                error!("RabbitMQ connection returned error: {err:?}");
+                sleep(Duration::from_millis(1000)).await;
+                info!("ready to restart RabbitMQ task");
+            }
+        }
+    }
+}
+
+pub async fn process(cfg: Arc<Config>, contents: &[DeferredMessage]) -> anyhow::Result<()> {
+    debug!("starting nft_launched_producer task");
+
+    let connection = Connection::open(
+        &OpenConnectionArguments::new(&cfg.host, cfg.port, &cfg.username, &cfg.password)
+            .virtual_host(&cfg.virtual_host),
+    )
+    .await
+    .with_context(|| {
+        format!(
+            "can't connect to RabbitMQ server at {}:{}",
+            cfg.host, cfg.port
+        )
+    })?;
+
+    // Add simple connection callback, it just logs diagnostics.
+    connection
+        .register_callback(DefaultConnectionCallback)
+        .await
+        .context("registering connection callback failed")?;
+
+    let channel = connection
+        .open_channel(None)
+        .await
+        .context("opening channel failed")?;
+    channel
+        .register_callback(DefaultChannelCallback)
+        .await
+        .context("registering channel callback failed")?;
+    let exchange_name = "bassinet.topic";
+    
+    // 发送事件
+    let routing_key = "bassinet.NftLaunched";
+    for message in contents {
+        // create arguments for basic_publish
+        let args = BasicPublishArguments::new(exchange_name, routing_key);
+        // 沿用原消息信封的属性(message_id、type、timestamp), 旧消息没有信封
+        let properties = match &message.envelope {
+            Some(envelope) => envelope.properties(),
+            None => BasicProperties::default().with_persistence(true).with_content_type("application/json").finish(),
+        };
+        channel
+        .basic_publish(
+            properties,
+            message.content.as_bytes().to_vec(),
+            args,
+        )
+        .await
+        .context("publish deferred NftLaunched failed")?;
+        tracing::info!("重新投递消息:{}, routing_key:{}", message.content, routing_key);
+    }
+
+    // keep the `channel` and `connection` object from dropping before pub/sub is done.
+    // channel/connection will be closed when drop.
+    time::sleep(time::Duration::from_secs(10)).await;
+    // explicitly close
+    channel.close().await.context("closing channel failed")?;
+    connection.close().await.context("closing connection failed")?;
+
+    Ok(())
+
+    // if connection.listen_network_io_failure().await {
+    //     Err(RabbitError::ConnectionLost("connection failure".to_owned()).into())
+    // } else {
+    //     Err(RabbitError::ConnectionLost("connection shut down normally. Since we don't close it ourselves, this shouldn't happen in this program".to_owned()).into())
+    // }
+}
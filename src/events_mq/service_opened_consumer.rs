@@ -14,7 +14,7 @@ use tracing::{debug, error, info };
 
 // use super::RabbitError;
 
-use crate::{events_mq::coin_published_producer, kv_store::{KVStore, RocksDB}, sui_service::digital_service::OpenDigitalServiceConfig};
+use crate::{events_mq::{event_payload, coin_published_producer, nft_launched_consumer::republish_deferred_nft_launched}, kv_store::{KVStore, StoreError, WriteBatch}, move_build::cached_builder_from_env, repository::{CoinPackageRepository, CreatorRepository, WorkflowRecord, WorkflowRepository, WorkflowStatus}, sui_service::{digital_service::OpenDigitalServiceConfig, BassinetCoinPublishedResult}, validation::{validate_address, CoinPublishRequest}};
 
 use super::Config;
 
@@ -29,9 +29,15 @@ pub struct CoinPublishedMessage {
     pub icon_url: String,
     pub account: String,
     pub wallet_address: String,
+    pub decimals: u8,
+    /// 最大发行量(整币数量)
+    pub max_supply: u64,
+    pub minter_percent: u8,
+    pub creator_percent: u8,
+    pub provider_percent: u8,
 }
 
-pub async fn service_opened_consume(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
+pub async fn service_opened_consume<S: KVStore>(cfg: Arc<Config>, db: S) -> anyhow::Result<()> {
     loop {
         let result = process(cfg.clone(), db.clone()).await;
         match result {
@@ -49,7 +55,7 @@ pub async fn service_opened_consume(cfg: Arc<Config>, db:RocksDB) -> anyhow::Res
     }
 }
 
-async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
+async fn process<S: KVStore>(cfg: Arc<Config>, db: S) -> anyhow::Result<()> {
     debug!("starting service_opened task");
 
     let connection = Connection::open(
@@ -105,11 +111,16 @@ async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
 
     let (ctag, mut rx) = channel.basic_consume_rx(consume_args).await.unwrap();
     let new_channel = channel.clone();
-    let rocksdb = db.clone();
+    let coin_packages = CoinPackageRepository::new(db.clone());
+    let creators = CreatorRepository::new(db.clone());
+    let workflows = WorkflowRepository::new(db.clone());
+    let build_cache = db.clone();
     let jh = tokio::spawn(async move {
         let dir_path = std::env::var("CONTRACTS_DIR_PATH").expect("CONTRACTS_DIR_PATH must be set");
         let provider = std::env::var("PROVIDER").expect("PROVIDER must be set");
+        validate_address("PROVIDER", &provider).expect("PROVIDER must be a valid Sui address");
         let key_store_path = std::env::var("KEY_STORE_PATH").expect("KEY_STORE_PATH must be set");
+        let builder = cached_builder_from_env(build_cache);
         while let Some(msg) = rx.recv().await {
             let content = msg.content.unwrap();
             let deliver = msg.deliver.unwrap();
@@ -120,15 +131,18 @@ async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
                 json
             );
 
-            // 处理消息(public_key,address,symbol,name,description,icon_url)
-            let value : Value = serde_json::from_str(json).unwrap();
-            let address = value.get("address");
-            let public_key = value.get("public_key");
-            let symbol = value.get("symbol");
-            let name = value.get("name");
-            let description = value.get("description");
-            let icon_url = value.get("icon_url");
-            if address.is_some() && public_key.is_some() && symbol.is_some(){
+            // 处理消息(public_key,address,symbol,name,description,icon_url,variant,params), 发布前校验
+            let value : Value = event_payload(json).unwrap_or(Value::Null);
+            let request = CoinPublishRequest::from_payload(&value);
+            if let Err(err) = &request {
+                tracing::error!("message:{}, rejected:{}", json, err);
+                let address = value.get("address").and_then(|address| address.as_str()).unwrap_or("unknown");
+                let record = WorkflowRecord::new("coin_publish", address, WorkflowStatus::Failed, Some(err.to_string()));
+                if let Err(err) = workflows.save(&record) {
+                    tracing::error!("message:{}, save workflow failed:{:?}", json, err);
+                }
+            }
+            if let Ok(request) = request {
                 // pub account: String,
                 // pub wallet_address: String,
                 // pub dir: PathBuf,
@@ -139,14 +153,14 @@ async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
                 // pub creator: String,
                 // pub provider: String,
                 // pub package_id: String
-                let account = public_key.unwrap().as_str().unwrap().to_owned();
-                let address = address.unwrap().as_str().unwrap();
+                let account = request.public_key.clone();
+                let address = request.address.as_str();
                 // let wallet_address = address.strip_prefix("0x").unwrap_or(address);
                 let dir = PathBuf::from_str(&dir_path).unwrap();
-                let symbol = symbol.unwrap().as_str().unwrap();
-                let name = name.unwrap().as_str().unwrap();
-                let description = description.unwrap().as_str().unwrap();
-                let icon_url = icon_url.unwrap().as_str().unwrap();
+                let symbol = request.symbol.as_str();
+                let name = request.name.as_str();
+                let description = request.description.as_str();
+                let icon_url = request.icon_url.as_str();
                 let creator = address;
                 let package_id = "0x0";
                 let mut config = OpenDigitalServiceConfig::new(
@@ -159,20 +173,31 @@ async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
                     icon_url.to_owned(),
                     creator.to_owned(),
                     provider.to_owned(),
-                    package_id.to_owned()
+                    package_id.to_owned(),
+                    request.template.clone(),
+                    request.economics
                 );
-                let result = config.open(&key_store_path).await;
+                let result = config.open(builder.as_ref(), &key_store_path).await;
                 if result.is_err() {
-                    tracing::error!("message:{}, error:{:?}", json, result.err());
+                    let err = result.err().unwrap();
+                    tracing::error!("message:{}, error:{:?}", json, err);
+                    let record = WorkflowRecord::new("coin_publish", address, WorkflowStatus::Failed, Some(err.to_string()));
+                    if let Err(err) = workflows.save(&record) {
+                        tracing::error!("message:{}, save workflow failed:{:?}", json, err);
+                    }
                     // TODO 重大事件，其他通知方式
                 }else {
-                    // json序列化保存到rocksdb
+                    // 保存发布结果
                     let publishing_reslut = result.unwrap();
                     let package_id = publishing_reslut.package_id.clone();
-                    let json = serde_json::to_string(&publishing_reslut).unwrap();
-                    rocksdb.save(package_id.as_str(), json.as_str());
-                    // 存储钱包地址对应的BassinetCoin的package_id
-                    rocksdb.save(&(address.to_owned() + "_bassinet_coin"), package_id.as_str());
+                    if let Err(err) = save_coin_published(&creators, &coin_packages, &workflows, address, &publishing_reslut) {
+                        tracing::error!("message:{}, save package:{} failed:{:?}", json, package_id, err);
+                    }
+
+                    // 重新投递等待该代币合约的NftLaunched消息, 投递成功后才删除
+                    if let Err(err) = republish_deferred_nft_launched(cfg.clone(), &workflows, address).await {
+                        tracing::error!("message:{}, republish deferred failed:{:?}", json, err);
+                    }
 
                     let message = CoinPublishedMessage{
                         package_id : package_id,
@@ -183,17 +208,22 @@ async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
                         description: description.to_owned(),
                         icon_url: icon_url.to_owned(),
                         account: account,
-                        wallet_address: address.to_owned()
+                        wallet_address: address.to_owned(),
+                        decimals: request.economics.decimals,
+                        max_supply: request.economics.max_supply,
+                        minter_percent: request.economics.minter_percent,
+                        creator_percent: request.economics.creator_percent,
+                        provider_percent: request.economics.provider_percent,
                     };
 
                     // 发送mq消息
                     let _ = coin_published_producer::produce_coin_published(cfg.clone(), &message).await;
                 }
-
-                // Ack explicitly
-                let args = BasicAckArguments::new(deliver.delivery_tag(), false);
-                new_channel.basic_ack(args).await.unwrap();
             }
+
+            // Ack explicitly(校验失败的消息同样确认, 不再重新投递)
+            let args = BasicAckArguments::new(deliver.delivery_tag(), false);
+            new_channel.basic_ack(args).await.unwrap();
         }
     });
     assert!(jh.await.is_err());
@@ -206,6 +236,16 @@ async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
     // }
 }
 
+/// 发布结果、钱包地址对应的BassinetCoin的package_id和处理状态一起写入; 延后消息在重新投递成功后删除
+pub fn save_coin_published<S: KVStore>(creators: &CreatorRepository<S>, coin_packages: &CoinPackageRepository<S>, workflows: &WorkflowRepository<S>, address: &str, result: &BassinetCoinPublishedResult) -> Result<(), StoreError> {
+    let record = WorkflowRecord::new("coin_publish", address, WorkflowStatus::Completed, None);
+    let mut batch = WriteBatch::new();
+    creators.stage_coin_package_id(&mut batch, address, &result.package_id);
+    coin_packages.stage_save(&mut batch, result)?;
+    workflows.stage_save(&mut batch, &record)?;
+    workflows.write(batch)
+}
+
 // pub struct ServiceOpenedConsumer {
 //     no_ack: bool,
 //     panic_countdown: u32,
@@ -263,4 +303,60 @@ async fn process(cfg: Arc<Config>, db:RocksDB) -> anyhow::Result<()> {
 //             channel.basic_ack(args).await.unwrap();
 //         }
 //     }
-// }
\ No newline at end of file
+// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events_mq::nft_launched_consumer::{defer_nft_launched, remove_deferred_nft_launched}, kv_store::MemoryStore};
+
+    const ADDRESS: &str = "0xc0ffee";
+
+    fn published() -> BassinetCoinPublishedResult {
+        BassinetCoinPublishedResult {
+            package_id: "0xa1".to_owned(),
+            admin_cap_id: "0xa2".to_owned(),
+            treasury_lock_id: "0xa3".to_owned(),
+            upgrade_cap_id: None,
+            wallet_address: ADDRESS.to_owned(),
+            account: "account".to_owned(),
+            network: "testnet".to_owned(),
+            template_version: None,
+            template_variant: None,
+            template_params: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn deferred_launches_survive_until_republished() {
+        let db = MemoryStore::new();
+        let creators = CreatorRepository::new(db.clone());
+        let coin_packages = CoinPackageRepository::new(db.clone());
+        let workflows = WorkflowRepository::new(db.clone());
+        defer_nft_launched(&workflows, ADDRESS, "launch-1", WriteBatch::new()).unwrap();
+        defer_nft_launched(&workflows, ADDRESS, "launch-2", WriteBatch::new()).unwrap();
+        defer_nft_launched(&workflows, ADDRESS, "launch-1", WriteBatch::new()).unwrap();
+        defer_nft_launched(&workflows, "0xother", "launch-3", WriteBatch::new()).unwrap();
+
+        save_coin_published(&creators, &coin_packages, &workflows, ADDRESS, &published()).unwrap();
+        assert_eq!(creators.coin_package_id(ADDRESS).unwrap().as_deref(), Some("0xa1"));
+        assert_eq!(coin_packages.find("0xa1").unwrap().unwrap().treasury_lock_id, "0xa3");
+        assert_eq!(workflows.find("coin_publish", ADDRESS).unwrap().unwrap().status, WorkflowStatus::Completed);
+        // 发布结果写入不删除延后消息
+        let deferred = workflows.deferred_nft_launched(ADDRESS).unwrap();
+        let contents: Vec<&str> = deferred.iter().map(|message| message.content.as_str()).collect();
+        assert_eq!(contents, vec!["launch-1", "launch-2"]);
+        assert!(deferred.iter().all(|message| message.envelope.is_none()));
+
+        // 重新投递期间新延后的消息保留
+        defer_nft_launched(&workflows, ADDRESS, "launch-4", WriteBatch::new()).unwrap();
+        remove_deferred_nft_launched(&workflows, ADDRESS, &deferred).unwrap();
+        let remaining = workflows.deferred_nft_launched(ADDRESS).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].content, "launch-4");
+        assert_eq!(workflows.deferred_nft_launched("0xother").unwrap()[0].content, "launch-3");
+
+        remove_deferred_nft_launched(&workflows, ADDRESS, &remaining).unwrap();
+        assert!(workflows.deferred_nft_launched(ADDRESS).unwrap().is_empty());
+    }
+}
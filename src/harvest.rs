@@ -0,0 +1,346 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sui_sdk::{rpc_types::{BalanceChange, SuiObjectDataOptions, SuiParsedData}, types::{base_types::{ObjectID, SuiAddress}, object::Owner, programmable_transaction_builder::ProgrammableTransactionBuilder, transaction::{Argument, CallArg, Command, ObjectArg, TransactionKind}, Identifier, TypeTag}, SuiClient};
+use tokio::time::{sleep, Duration};
+use tracing::{error, info};
+
+use crate::{event_listening::get_client, events_mq::{load_config, rewards_harvested_producer, Config}, kv_store::{now_millis, KVStore, WriteBatch}, repository::{CoinPackageRepository, HarvestRecord, HarvestRepository}, sui_service::{execute_transaction_block, BassinetCoinPublishedResult, NETWORK}, treasury_stats::u64_value};
+
+const USAGE: &str = "usage: bassinet-sui harvest [--dry-run]";
+
+/// 默认每天领取一次
+const DEFAULT_HARVEST_INTERVAL_SECS: u64 = 24 * 60 * 60;
+/// 每个交易领取的TreasuryLock数量
+const DEFAULT_HARVEST_BATCH_SIZE: usize = 20;
+/// 领取交易的gas预算
+const HARVEST_GAS_BUDGET: u64 = 500_000_000;
+
+/// 平台激励领取配置
+#[derive(Debug, Clone)]
+pub struct HarvestConfig {
+    pub provider: String,
+    /// 领取的激励发送到的地址
+    pub treasury: String,
+    /// 平台激励余额(含精度)达到该值才领取
+    pub threshold: u64,
+    pub batch_size: usize,
+    pub key_store_path: String,
+}
+
+impl HarvestConfig {
+
+    pub fn from_env() -> Self {
+        Self {
+            provider: std::env::var("PROVIDER").expect("PROVIDER must be set"),
+            treasury: std::env::var("HARVEST_TREASURY_ADDRESS").expect("HARVEST_TREASURY_ADDRESS must be set"),
+            threshold: std::env::var("HARVEST_THRESHOLD")
+                .map(|s| s.parse::<u64>().expect("can't parse HARVEST_THRESHOLD"))
+                .unwrap_or(1)
+                .max(1),
+            batch_size: std::env::var("HARVEST_BATCH_SIZE")
+                .map(|s| s.parse::<usize>().expect("can't parse HARVEST_BATCH_SIZE"))
+                .unwrap_or(DEFAULT_HARVEST_BATCH_SIZE)
+                .max(1),
+            key_store_path: std::env::var("KEY_STORE_PATH").expect("KEY_STORE_PATH must be set"),
+        }
+    }
+}
+
+/// 单个代币合约的领取数量
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarvestedAmount {
+    pub package_id: String,
+    pub treasury_lock_id: String,
+    pub wallet_address: String,
+    pub amount: u64,
+}
+
+/// 一个领取交易的结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RewardsHarvestedMessage {
+    pub tx_digest: String,
+    pub recipient: String,
+    pub harvests: Vec<HarvestedAmount>,
+    pub harvested_at: u64,
+}
+
+/// 领取报告
+#[derive(Debug, Default)]
+pub struct HarvestReport {
+    pub dry_run: bool,
+    pub checked: usize,
+    pub harvested: Vec<String>,
+    pub skipped: Vec<String>,
+    pub failed: Vec<String>,
+}
+
+impl fmt::Display for HarvestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for harvested in &self.harvested {
+            writeln!(f, "  harvested {}", harvested)?;
+        }
+        for skipped in &self.skipped {
+            writeln!(f, "  skipped {}", skipped)?;
+        }
+        for failed in &self.failed {
+            writeln!(f, "  failed {}", failed)?;
+        }
+        let mode = if self.dry_run { " (dry run)" } else { "" };
+        write!(f, "{} coin packages checked, {} harvested, {} skipped, {} failed{}",
+            self.checked, self.harvested.len(), self.skipped.len(), self.failed.len(), mode)
+    }
+}
+
+/// 待领取的TreasuryLock
+struct Candidate {
+    coin: BassinetCoinPublishedResult,
+    lock: ObjectArg,
+    amount: u64,
+}
+
+/// 领取子命令, 执行一次
+pub async fn harvest_command<S: KVStore>(args: &[String], db: S) -> Result<(), anyhow::Error> {
+    let mut dry_run = false;
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            _ => return Err(anyhow!("Unknown argument:{}\n{}", arg, USAGE)),
+        }
+    }
+    let config = HarvestConfig::from_env();
+    let client = get_client().await?;
+    let (report, messages) = harvest(&client, &db, &config, dry_run).await?;
+    if !messages.is_empty() {
+        let cfg = Arc::new(load_config().await);
+        for message in &messages {
+            let _ = rewards_harvested_producer::produce_rewards_harvested(cfg.clone(), message).await;
+        }
+    }
+    println!("{}", report);
+    Ok(())
+}
+
+/// 定期领取全部代币合约的平台激励
+pub async fn harvest_periodically<S: KVStore>(db: S, cfg: Arc<Config>) {
+    let interval = std::env::var("HARVEST_INTERVAL_SECS")
+        .map(|s| s.parse::<u64>().expect("can't parse HARVEST_INTERVAL_SECS"))
+        .unwrap_or(DEFAULT_HARVEST_INTERVAL_SECS);
+    let config = HarvestConfig::from_env();
+    loop {
+        sleep(Duration::from_secs(interval)).await;
+        let result = match get_client().await {
+            Ok(client) => harvest(&client, &db, &config, false).await,
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok((report, messages)) => {
+                info!("平台激励领取完成:\n{}", report);
+                for message in &messages {
+                    let _ = rewards_harvested_producer::produce_rewards_harvested(cfg.clone(), message).await;
+                }
+            }
+            Err(err) => error!("平台激励领取失败:{err:?}"),
+        }
+    }
+}
+
+/// 读取每个TreasuryLock的平台激励余额, 达到阈值的按批次在一个交易中领取
+pub async fn harvest<S: KVStore>(client: &SuiClient, db: &S, config: &HarvestConfig, dry_run: bool) -> Result<(HarvestReport, Vec<RewardsHarvestedMessage>), anyhow::Error> {
+    let provider = parse_address(&config.provider)?;
+    let treasury = parse_address(&config.treasury)?;
+    let harvests = HarvestRepository::new(db.clone());
+
+    let mut report = HarvestReport { dry_run, ..Default::default() };
+    let mut candidates = Vec::new();
+    for coin in CoinPackageRepository::new(db.clone()).list()? {
+        if coin.network != NETWORK {
+            continue;
+        }
+        report.checked += 1;
+        match check_candidate(client, &coin, provider, config.threshold).await {
+            Ok(Some(candidate)) => candidates.push(candidate),
+            Ok(None) => {}
+            Err(err) => report.skipped.push(format!("{}: {}", coin.package_id, err)),
+        }
+    }
+
+    let mut messages = Vec::new();
+    for batch in candidates.chunks(config.batch_size) {
+        if dry_run {
+            report.harvested.extend(batch.iter().map(|candidate| format!("{}: {}", candidate.coin.package_id, candidate.amount)));
+            continue;
+        }
+        let mut ptb = ProgrammableTransactionBuilder::new();
+        let recipient = ptb.pure(treasury)?;
+        for candidate in batch {
+            let rewards = take_rewards_call(&mut ptb, candidate)?;
+            ptb.command(Command::TransferObjects(vec![rewards], recipient));
+        }
+        let package_ids: Vec<&str> = batch.iter().map(|candidate| candidate.coin.package_id.as_str()).collect();
+        let response = match execute_transaction_block(&config.provider, ptb, HARVEST_GAS_BUDGET, &config.key_store_path).await {
+            Ok(response) => response,
+            Err(err) => {
+                report.failed.push(format!("{}: {}", package_ids.join(","), err));
+                continue;
+            }
+        };
+
+        // 领取数量以交易中国库地址实际收到的代币为准, 而不是检查时的余额
+        let tx_digest = response.digest.to_string();
+        let balance_changes = response.balance_changes.unwrap_or_default();
+        let harvested_at = now_millis();
+        let mut write_batch = WriteBatch::new();
+        let mut amounts = Vec::new();
+        for candidate in batch {
+            let Some(amount) = harvested_amount(&balance_changes, treasury, &candidate.coin.package_id) else {
+                report.failed.push(format!("{}: no balance change to treasury in tx {}", candidate.coin.package_id, tx_digest));
+                continue;
+            };
+            if amount != candidate.amount {
+                info!("{}: harvested {}, checked {}", candidate.coin.package_id, amount, candidate.amount);
+            }
+            let record = HarvestRecord {
+                package_id: candidate.coin.package_id.clone(),
+                treasury_lock_id: candidate.coin.treasury_lock_id.clone(),
+                wallet_address: candidate.coin.wallet_address.clone(),
+                amount,
+                recipient: config.treasury.clone(),
+                tx_digest: tx_digest.clone(),
+                harvested_at,
+            };
+            harvests.stage_save(&mut write_batch, &record)?;
+            report.harvested.push(format!("{}: {} ({})", record.package_id, record.amount, tx_digest));
+            amounts.push(HarvestedAmount {
+                package_id: record.package_id,
+                treasury_lock_id: record.treasury_lock_id,
+                wallet_address: record.wallet_address,
+                amount: record.amount,
+            });
+        }
+        if amounts.is_empty() {
+            continue;
+        }
+        if let Err(err) = db.write(write_batch) {
+            error!("tx:{}, save harvest records failed:{:?}", tx_digest, err);
+        }
+        messages.push(RewardsHarvestedMessage {
+            tx_digest,
+            recipient: config.treasury.clone(),
+            harvests: amounts,
+            harvested_at,
+        });
+    }
+    Ok((report, messages))
+}
+
+/// 读取TreasuryLock, 平台激励达到阈值且领取结果与余额一致时返回待领取项
+async fn check_candidate(client: &SuiClient, coin: &BassinetCoinPublishedResult, provider: SuiAddress, threshold: u64) -> Result<Option<Candidate>, anyhow::Error> {
+    let lock_id = ObjectID::from_hex_literal(&coin.treasury_lock_id)?;
+    let response = client.read_api()
+        .get_object_with_options(lock_id, SuiObjectDataOptions::new().with_content().with_owner())
+        .await?;
+    let data = response.data.ok_or(anyhow!("TreasuryLock {} not found", coin.treasury_lock_id))?;
+    let fields = match &data.content {
+        Some(SuiParsedData::MoveObject(object)) => object.fields.to_json_value(),
+        _ => return Err(anyhow!("TreasuryLock {} has no content", coin.treasury_lock_id)),
+    };
+    let platform_provider = fields.get("platform_provider").and_then(|value| value.as_str()).map(parse_address).transpose()?;
+    if platform_provider != Some(provider) {
+        return Err(anyhow!("TreasuryLock platform provider is {:?}", platform_provider))
+    }
+    let amount = fields.get("platform_provider_rewards").and_then(u64_value)
+        .ok_or(anyhow!("TreasuryLock {} has no platform_provider_rewards", coin.treasury_lock_id))?;
+    if amount < threshold {
+        return Ok(None)
+    }
+    let lock = match data.owner {
+        Some(Owner::Shared { initial_shared_version }) => ObjectArg::SharedObject { id: lock_id, initial_shared_version, mutable: true },
+        _ => return Err(anyhow!("TreasuryLock {} is not shared", coin.treasury_lock_id)),
+    };
+    let candidate = Candidate { coin: coin.clone(), lock, amount };
+
+    // 旧模板的 take_provider_profits 领取的是创作者激励, 先模拟执行确认领取数量等于平台激励余额
+    let mut ptb = ProgrammableTransactionBuilder::new();
+    take_rewards_call(&mut ptb, &candidate)?;
+    let inspected = client.read_api()
+        .dev_inspect_transaction_block(provider, TransactionKind::programmable(ptb.finish()), None, None, None)
+        .await?;
+    if let Some(err) = inspected.error {
+        return Err(anyhow!("take_rewards_ inspect failed: {}", err))
+    }
+    let returned = inspected.results.as_ref()
+        .and_then(|results| results.first())
+        .and_then(|result| result.return_values.first())
+        .and_then(|(bytes, _)| coin_value(bytes))
+        .ok_or(anyhow!("take_rewards_ returned no coin"))?;
+    if returned != amount {
+        return Err(anyhow!("take_rewards_ returns {}, platform rewards {}, template takes creator rewards", returned, amount))
+    }
+    Ok(Some(candidate))
+}
+
+/// <package>::bassinet_coin::take_rewards_(lock), 返回领取的Coin
+fn take_rewards_call(ptb: &mut ProgrammableTransactionBuilder, candidate: &Candidate) -> Result<Argument, anyhow::Error> {
+    let package = ObjectID::from_hex_literal(&candidate.coin.package_id)?;
+    let lock = ptb.input(CallArg::Object(candidate.lock))?;
+    let module = Identifier::new("bassinet_coin").map_err(|e| anyhow!(e))?;
+    let function = Identifier::new("take_rewards_").map_err(|e| anyhow!(e))?;
+    Ok(ptb.command(Command::move_call(package, module, function, vec![], vec![lock])))
+}
+
+fn parse_address(address: &str) -> Result<SuiAddress, anyhow::Error> {
+    SuiAddress::from_bytes(hex::decode(address.strip_prefix("0x").unwrap_or(address))?).map_err(|e| anyhow!("Invalid address:{}, {}", address, e))
+}
+
+/// 交易中treasury收到的该代币合约代币(<package>::bassinet_coin::*)数量
+fn harvested_amount(balance_changes: &[BalanceChange], treasury: SuiAddress, package_id: &str) -> Option<u64> {
+    let package = ObjectID::from_hex_literal(package_id).ok()?;
+    let change = balance_changes.iter().find(|change| {
+        change.owner == Owner::AddressOwner(treasury) && match &change.coin_type {
+            TypeTag::Struct(tag) => ObjectID::from(tag.address) == package && tag.module.as_str() == "bassinet_coin",
+            _ => false,
+        }
+    })?;
+    u64::try_from(change.amount).ok().filter(|amount| *amount > 0)
+}
+
+/// Coin<T> 的BCS编码: UID(32字节) + u64
+fn coin_value(bytes: &[u8]) -> Option<u64> {
+    let value: [u8; 8] = bytes.get(32..40)?.try_into().ok()?;
+    Some(u64::from_le_bytes(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use sui_sdk::types::parse_sui_type_tag;
+
+    use super::*;
+
+    const PACKAGE: &str = "0x00000000000000000000000000000000000000000000000000000000000000c1";
+    const OTHER: &str = "0x00000000000000000000000000000000000000000000000000000000000000c2";
+
+    fn change(owner: SuiAddress, package_id: &str, amount: i128) -> BalanceChange {
+        BalanceChange {
+            owner: Owner::AddressOwner(owner),
+            coin_type: parse_sui_type_tag(&format!("{}::bassinet_coin::BASSINET_COIN", package_id)).unwrap(),
+            amount,
+        }
+    }
+
+    #[test]
+    fn harvested_amount_comes_from_treasury_balance_change() {
+        let treasury = parse_address("0x00000000000000000000000000000000000000000000000000000000000000aa").unwrap();
+        let provider = parse_address("0x00000000000000000000000000000000000000000000000000000000000000bb").unwrap();
+        let changes = vec![
+            change(provider, PACKAGE, -700),
+            change(treasury, PACKAGE, 700),
+            change(treasury, OTHER, 30),
+        ];
+        assert_eq!(harvested_amount(&changes, treasury, PACKAGE), Some(700));
+        assert_eq!(harvested_amount(&changes, treasury, OTHER), Some(30));
+        assert_eq!(harvested_amount(&changes, provider, OTHER), None);
+        assert_eq!(harvested_amount(&[change(treasury, PACKAGE, -5)], treasury, PACKAGE), None);
+    }
+}
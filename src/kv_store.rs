@@ -1,15 +1,189 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
 
-use rocksdb::DB;
+use rocksdb::{checkpoint::Checkpoint, compaction_filter::Decision, ColumnFamilyDescriptor, Direction, IteratorMode, Options, DB};
+use thiserror::Error;
 
-pub trait KVStore {
-    fn init(file_path: &str) -> Self;
-    
-    fn save(&self, key: &str, value: &str) -> bool;
-    
-    fn find(&self, key: &str) -> Option<String>;
+pub use memory::MemoryStore;
 
-    fn delete(&self, key: &str) -> bool;
+pub mod memory;
+
+/// 去重标记默认保留30天
+const DEFAULT_DEDUP_RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// 列族
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Column {
+    /// 默认列族(旧版本平铺的键空间)
+    Default,
+    /// 创作者钱包地址 -> 代币合约package_id
+    Creators,
+    /// 代币合约package_id -> BassinetCoinPublishedResult
+    CoinPackages,
+    /// NFT合约package_id -> NftPublishedResult
+    NftPackages,
+    /// collection_id -> NFT合约package_id
+    Collections,
+    /// 事件游标
+    Cursors,
+    /// 事件去重标记
+    Dedup,
+    /// 消息处理状态
+    Workflows,
+    /// 合约包源码哈希 -> 编译结果
+    BuildCache,
+    /// 创作者钱包地址:collection_id -> CreatorCollection
+    CreatorCollections,
+    /// collection_id -> 创作者钱包地址
+    CollectionCreators,
+    /// 创作者钱包地址:时间:操作ID -> 授权操作记录
+    Operations,
+    /// 代币合约package_id:时间 -> 平台激励领取记录
+    Harvests,
+}
+
+impl Column {
+
+    pub const ALL: [Column; 13] = [
+        Column::Default,
+        Column::Creators,
+        Column::CoinPackages,
+        Column::NftPackages,
+        Column::Collections,
+        Column::Cursors,
+        Column::Dedup,
+        Column::Workflows,
+        Column::BuildCache,
+        Column::CreatorCollections,
+        Column::CollectionCreators,
+        Column::Operations,
+        Column::Harvests,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Default => "default",
+            Column::Creators => "creators",
+            Column::CoinPackages => "coin_packages",
+            Column::NftPackages => "nft_packages",
+            Column::Collections => "collections",
+            Column::Cursors => "cursors",
+            Column::Dedup => "dedup",
+            Column::Workflows => "workflows",
+            Column::BuildCache => "build_cache",
+            Column::CreatorCollections => "creator_collections",
+            Column::CollectionCreators => "collection_creators",
+            Column::Operations => "operations",
+            Column::Harvests => "harvests",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Column> {
+        Column::ALL.iter().find(|column| column.name() == name).copied()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StoreError {
+    #[error("store open failed: {0}")]
+    Open(String),
+    #[error("store read failed: {0}")]
+    Read(String),
+    #[error("store write failed: {0}")]
+    Write(String),
+    #[error("invalid utf-8 value for key: {0}")]
+    InvalidValue(String),
+    #[error("invalid record for key {0}: {1}")]
+    InvalidRecord(String, String),
+    #[error("store schema version {0} is newer than supported version {1}")]
+    SchemaVersion(u32, u32),
+    #[error("store schema version {0} is older than current version {1}, run `bassinet-sui migrate`")]
+    SchemaOutdated(u32, u32),
+}
+
+/// 批量写入操作
+#[derive(Debug, Clone)]
+pub enum BatchOp {
+    Put(Column, String, String),
+    Delete(Column, String),
+}
+
+/// 原子批量写入, 全部成功或全部失败
+#[derive(Debug, Clone, Default)]
+pub struct WriteBatch {
+    ops: Vec<BatchOp>,
+}
+
+impl WriteBatch {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put(&mut self, column: Column, key: &str, value: &str) {
+        self.ops.push(BatchOp::Put(column, key.to_owned(), value.to_owned()));
+    }
+
+    pub fn delete(&mut self, column: Column, key: &str) {
+        self.ops.push(BatchOp::Delete(column, key.to_owned()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn ops(&self) -> &[BatchOp] {
+        &self.ops
+    }
+}
+
+pub trait KVStore: Clone + Send + Sync + 'static {
+    fn save(&self, column: Column, key: &str, value: &str) -> Result<(), StoreError>;
+
+    fn find(&self, column: Column, key: &str) -> Result<Option<String>, StoreError>;
+
+    fn delete(&self, column: Column, key: &str) -> Result<(), StoreError>;
+
+    /// 原子写入一批记录
+    fn write(&self, batch: WriteBatch) -> Result<(), StoreError>;
+
+    /// 列族内全部记录(按键排序)
+    fn list(&self, column: Column) -> Result<Vec<(String, String)>, StoreError>;
+
+    /// 列族内键以prefix开头的记录(按键排序)
+    fn list_prefix(&self, column: Column, prefix: &str) -> Result<Vec<(String, String)>, StoreError>;
+
+    /// 同一时刻的全部记录(用于导出)
+    fn dump(&self) -> Result<Vec<(Column, String, String)>, StoreError>;
+
+    /// 清理超过保留时长的去重标记
+    fn compact_event_marks(&self) -> Result<(), StoreError>;
+
+    /// 写入事件去重标记(值为标记时间, 毫秒)
+    fn mark_event(&self, event_id: &str) -> Result<(), StoreError> {
+        self.save(Column::Dedup, event_id, now_millis().to_string().as_str())
+    }
+
+    /// 事件是否已标记
+    fn event_marked(&self, event_id: &str) -> Result<bool, StoreError> {
+        Ok(self.find(Column::Dedup, event_id)?.is_some())
+    }
+}
+
+/// 去重标记保留时长(秒)
+pub fn dedup_retention_secs() -> u64 {
+    std::env::var("DEDUP_RETENTION_SECS")
+        .map(|s| s.parse::<u64>().expect("can't parse DEDUP_RETENTION_SECS"))
+        .unwrap_or(DEFAULT_DEDUP_RETENTION_SECS)
+}
+
+/// 去重标记是否已过期
+pub fn event_mark_expired(value: &[u8], retention_millis: u64) -> bool {
+    let marked_at = std::str::from_utf8(value).ok().and_then(|value| value.parse::<u64>().ok());
+    match marked_at {
+        Some(marked_at) => now_millis().saturating_sub(marked_at) > retention_millis,
+        // 无法解析的旧标记直接清理
+        None => true,
+    }
 }
 
 #[derive(Clone)]
@@ -17,35 +191,278 @@ pub struct RocksDB {
     db: Arc<DB>,
 }
 
-impl KVStore for RocksDB {
+impl RocksDB {
+
+    pub fn open(file_path: &str) -> Result<Self, StoreError> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let db = DB::open_cf_descriptors(&opts, file_path, column_descriptors()).map_err(|e| StoreError::Open(e.to_string()))?;
+        Ok(RocksDB { db: Arc::new(db) })
+    }
 
-    fn init(file_path: &str) -> Self {
-        RocksDB { db: Arc::new(DB::open_default(file_path).unwrap()) }
+    /// 以secondary方式只读打开正在运行的存储, secondary_path保存secondary实例自身的日志
+    pub fn open_secondary(file_path: &str, secondary_path: &str) -> Result<Self, StoreError> {
+        let mut opts = Options::default();
+        opts.set_max_open_files(-1);
+        let db = DB::open_cf_descriptors_as_secondary(&opts, file_path, secondary_path, column_descriptors()).map_err(|e| StoreError::Open(e.to_string()))?;
+        db.try_catch_up_with_primary().map_err(|e| StoreError::Read(e.to_string()))?;
+        Ok(RocksDB { db: Arc::new(db) })
     }
 
-    fn save(&self, key: &str, value: &str) -> bool {
-        self.db.put(key.as_bytes(), value.as_bytes()).is_ok()
+    /// 在目录dir创建一致的检查点(目录不能已存在), 可直接作为存储目录打开
+    pub fn checkpoint(&self, dir: &Path) -> Result<(), StoreError> {
+        let checkpoint = Checkpoint::new(&self.db).map_err(|e| StoreError::Write(e.to_string()))?;
+        checkpoint.create_checkpoint(dir).map_err(|e| StoreError::Write(e.to_string()))
     }
+}
+
+fn column_descriptors() -> Vec<ColumnFamilyDescriptor> {
+    Column::ALL.iter().map(|column| {
+        let cf_opts = if *column == Column::Dedup { dedup_options(dedup_retention_secs()) } else { Options::default() };
+        ColumnFamilyDescriptor::new(column.name(), cf_opts)
+    }).collect()
+}
+
+fn decode_entry(key: &[u8], value: &[u8]) -> Result<(String, String), StoreError> {
+    let key = String::from_utf8(key.to_vec()).map_err(|_| StoreError::InvalidValue(hex::encode(key)))?;
+    let value = String::from_utf8(value.to_vec()).map_err(|_| StoreError::InvalidValue(key.clone()))?;
+    Ok((key, value))
+}
 
-    fn find(&self, key: &str) -> Option<String> {
-        match self.db.get(key.as_bytes()) {
+impl KVStore for RocksDB {
+
+    fn save(&self, column: Column, key: &str, value: &str) -> Result<(), StoreError> {
+        let cf = self.db.cf_handle(column.name()).unwrap();
+        self.db.put_cf(cf, key.as_bytes(), value.as_bytes()).map_err(|e| StoreError::Write(e.to_string()))
+    }
+
+    fn find(&self, column: Column, key: &str) -> Result<Option<String>, StoreError> {
+        let cf = self.db.cf_handle(column.name()).unwrap();
+        match self.db.get_cf(cf, key.as_bytes()) {
             Ok(Some(value)) => {
-                let result = String::from_utf8(value).unwrap();
-                // println!("Finding '{}' returns '{}'", key, result);
-                Some(result)
+                let result = String::from_utf8(value).map_err(|_| StoreError::InvalidValue(key.to_owned()))?;
+                Ok(Some(result))
             },
-            Ok(None) => {
-                // println!("Finding '{}' returns None", key);
-                None
-            },
-            Err(e) => {
-                // println!("Error retrieving value for {}: {}", key, e);
-                None
+            Ok(None) => Ok(None),
+            Err(e) => Err(StoreError::Read(e.to_string())),
+        }
+    }
+
+    fn delete(&self, column: Column, key: &str) -> Result<(), StoreError> {
+        let cf = self.db.cf_handle(column.name()).unwrap();
+        self.db.delete_cf(cf, key.as_bytes()).map_err(|e| StoreError::Write(e.to_string()))
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<(), StoreError> {
+        let mut rocksdb_batch = rocksdb::WriteBatch::default();
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put(column, key, value) => {
+                    let cf = self.db.cf_handle(column.name()).unwrap();
+                    rocksdb_batch.put_cf(cf, key.as_bytes(), value.as_bytes());
+                }
+                BatchOp::Delete(column, key) => {
+                    let cf = self.db.cf_handle(column.name()).unwrap();
+                    rocksdb_batch.delete_cf(cf, key.as_bytes());
+                }
             }
         }
+        self.db.write(rocksdb_batch).map_err(|e| StoreError::Write(e.to_string()))
+    }
+
+    fn list(&self, column: Column) -> Result<Vec<(String, String)>, StoreError> {
+        let cf = self.db.cf_handle(column.name()).unwrap();
+        let mut records = Vec::new();
+        for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+            let (key, value) = item.map_err(|e| StoreError::Read(e.to_string()))?;
+            records.push(decode_entry(&key, &value)?);
+        }
+        Ok(records)
+    }
+
+    fn list_prefix(&self, column: Column, prefix: &str) -> Result<Vec<(String, String)>, StoreError> {
+        let cf = self.db.cf_handle(column.name()).unwrap();
+        let mut records = Vec::new();
+        for item in self.db.iterator_cf(cf, IteratorMode::From(prefix.as_bytes(), Direction::Forward)) {
+            let (key, value) = item.map_err(|e| StoreError::Read(e.to_string()))?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            records.push(decode_entry(&key, &value)?);
+        }
+        Ok(records)
+    }
+
+    fn dump(&self) -> Result<Vec<(Column, String, String)>, StoreError> {
+        let snapshot = self.db.snapshot();
+        let mut records = Vec::new();
+        for column in Column::ALL {
+            let cf = self.db.cf_handle(column.name()).unwrap();
+            for item in snapshot.iterator_cf(cf, IteratorMode::Start) {
+                let (key, value) = item.map_err(|e| StoreError::Read(e.to_string()))?;
+                let (key, value) = decode_entry(&key, &value)?;
+                records.push((column, key, value));
+            }
+        }
+        Ok(records)
+    }
+
+    fn compact_event_marks(&self) -> Result<(), StoreError> {
+        let cf = self.db.cf_handle(Column::Dedup.name()).unwrap();
+        self.db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        Ok(())
+    }
+}
+
+/// 去重标记列族选项: 压缩时删除超过保留时长的标记
+fn dedup_options(retention_secs: u64) -> Options {
+    let mut opts = Options::default();
+    let retention_millis = retention_secs.saturating_mul(1000);
+    opts.set_compaction_filter("dedup_retention", move |_level: u32, _key: &[u8], value: &[u8]| {
+        if event_mark_expired(value, retention_millis) {
+            Decision::Remove
+        } else {
+            Decision::Keep
+        }
+    });
+    opts
+}
+
+pub fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// 测试用的RocksDB目录, 结束时删除
+    struct TempStore {
+        dir: PathBuf,
+        db: Option<RocksDB>,
+    }
+
+    impl TempStore {
+
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("bassinet-kv-{}", uuid::Uuid::new_v4()));
+            let db = RocksDB::open(dir.to_str().unwrap()).unwrap();
+            Self { dir, db: Some(db) }
+        }
+
+        fn db(&self) -> RocksDB {
+            self.db.clone().unwrap()
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            self.db.take();
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn get_save<S: KVStore>(db: S) {
+        assert_eq!(db.find(Column::Creators, "a").unwrap(), None);
+        db.save(Column::Creators, "a", "1").unwrap();
+        db.save(Column::Creators, "a", "2").unwrap();
+        assert_eq!(db.find(Column::Creators, "a").unwrap().as_deref(), Some("2"));
+        // 列族之间互不影响
+        assert_eq!(db.find(Column::Collections, "a").unwrap(), None);
+        db.delete(Column::Creators, "a").unwrap();
+        assert_eq!(db.find(Column::Creators, "a").unwrap(), None);
+        db.delete(Column::Creators, "missing").unwrap();
+    }
+
+    fn batch_atomicity<S: KVStore>(db: S) {
+        db.save(Column::Creators, "old", "1").unwrap();
+        let mut batch = WriteBatch::new();
+        batch.put(Column::Creators, "a", "1");
+        batch.put(Column::CoinPackages, "p", "{}");
+        batch.delete(Column::Creators, "old");
+        batch.put(Column::Creators, "a", "2");
+        assert_eq!(db.find(Column::Creators, "a").unwrap(), None);
+        db.write(batch).unwrap();
+        // 批量操作按顺序生效
+        assert_eq!(db.find(Column::Creators, "a").unwrap().as_deref(), Some("2"));
+        assert_eq!(db.find(Column::CoinPackages, "p").unwrap().as_deref(), Some("{}"));
+        assert_eq!(db.find(Column::Creators, "old").unwrap(), None);
+        db.write(WriteBatch::new()).unwrap();
+    }
+
+    fn list_prefix<S: KVStore>(db: S) {
+        for key in ["0xa:2", "0xa:1", "0xab:1", "0xb:1", "0x"] {
+            db.save(Column::CreatorCollections, key, key).unwrap();
+        }
+        db.save(Column::Collections, "0xa:3", "other column").unwrap();
+        let keys = |records: Vec<(String, String)>| records.into_iter().map(|(key, _)| key).collect::<Vec<_>>();
+        assert_eq!(keys(db.list_prefix(Column::CreatorCollections, "0xa:").unwrap()), vec!["0xa:1", "0xa:2"]);
+        assert_eq!(keys(db.list_prefix(Column::CreatorCollections, "0xa").unwrap()), vec!["0xa:1", "0xa:2", "0xab:1"]);
+        assert!(db.list_prefix(Column::CreatorCollections, "0xc").unwrap().is_empty());
+        assert_eq!(keys(db.list(Column::CreatorCollections).unwrap()), vec!["0x", "0xa:1", "0xa:2", "0xab:1", "0xb:1"]);
+        assert_eq!(db.dump().unwrap().len(), 6);
+    }
+
+    fn dedup_marks<S: KVStore>(db: S) {
+        assert!(!db.event_marked("event-1").unwrap());
+        db.mark_event("event-1").unwrap();
+        assert!(db.event_marked("event-1").unwrap());
+        // 过期和无法解析的标记在压缩时清理
+        db.save(Column::Dedup, "event-expired", "0").unwrap();
+        db.save(Column::Dedup, "event-invalid", "not a timestamp").unwrap();
+        db.compact_event_marks().unwrap();
+        assert!(db.event_marked("event-1").unwrap());
+        assert!(!db.event_marked("event-expired").unwrap());
+        assert!(!db.event_marked("event-invalid").unwrap());
+    }
+
+    #[test]
+    fn memory_get_save() {
+        get_save(MemoryStore::new());
+    }
+
+    #[test]
+    fn rocksdb_get_save() {
+        get_save(TempStore::new().db());
+    }
+
+    #[test]
+    fn memory_batch_atomicity() {
+        batch_atomicity(MemoryStore::new());
+    }
+
+    #[test]
+    fn rocksdb_batch_atomicity() {
+        batch_atomicity(TempStore::new().db());
+    }
+
+    #[test]
+    fn memory_list_prefix() {
+        list_prefix(MemoryStore::new());
+    }
+
+    #[test]
+    fn rocksdb_list_prefix() {
+        list_prefix(TempStore::new().db());
+    }
+
+    #[test]
+    fn memory_dedup_marks() {
+        dedup_marks(MemoryStore::new());
+    }
+
+    #[test]
+    fn rocksdb_dedup_marks() {
+        dedup_marks(TempStore::new().db());
     }
 
-    fn delete(&self, key: &str) -> bool {
-        self.db.delete(key.as_bytes()).is_ok()
+    #[test]
+    fn event_mark_expiry() {
+        assert!(!event_mark_expired(now_millis().to_string().as_bytes(), 1000));
+        assert!(event_mark_expired(b"0", 1000));
+        assert!(event_mark_expired(b"x", 1000));
     }
 }
@@ -0,0 +1,89 @@
+use std::{collections::{BTreeMap, HashMap}, sync::{Arc, RwLock}};
+
+use super::{dedup_retention_secs, event_mark_expired, BatchOp, Column, KVStore, StoreError, WriteBatch};
+
+/// 内存存储, 用于测试和本地开发
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    columns: Arc<RwLock<HashMap<Column, BTreeMap<String, String>>>>,
+}
+
+impl MemoryStore {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl KVStore for MemoryStore {
+
+    fn save(&self, column: Column, key: &str, value: &str) -> Result<(), StoreError> {
+        let mut columns = self.columns.write().map_err(|e| StoreError::Write(e.to_string()))?;
+        columns.entry(column).or_default().insert(key.to_owned(), value.to_owned());
+        Ok(())
+    }
+
+    fn find(&self, column: Column, key: &str) -> Result<Option<String>, StoreError> {
+        let columns = self.columns.read().map_err(|e| StoreError::Read(e.to_string()))?;
+        Ok(columns.get(&column).and_then(|entries| entries.get(key).cloned()))
+    }
+
+    fn delete(&self, column: Column, key: &str) -> Result<(), StoreError> {
+        let mut columns = self.columns.write().map_err(|e| StoreError::Write(e.to_string()))?;
+        if let Some(entries) = columns.get_mut(&column) {
+            entries.remove(key);
+        }
+        Ok(())
+    }
+
+    fn write(&self, batch: WriteBatch) -> Result<(), StoreError> {
+        // 持有写锁期间应用全部操作, 其他读写看不到中间状态
+        let mut columns = self.columns.write().map_err(|e| StoreError::Write(e.to_string()))?;
+        for op in batch.ops() {
+            match op {
+                BatchOp::Put(column, key, value) => {
+                    columns.entry(*column).or_default().insert(key.clone(), value.clone());
+                }
+                BatchOp::Delete(column, key) => {
+                    if let Some(entries) = columns.get_mut(column) {
+                        entries.remove(key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn list(&self, column: Column) -> Result<Vec<(String, String)>, StoreError> {
+        let columns = self.columns.read().map_err(|e| StoreError::Read(e.to_string()))?;
+        Ok(columns.get(&column).map(|entries| entries.iter().map(|(key, value)| (key.clone(), value.clone())).collect()).unwrap_or_default())
+    }
+
+    fn list_prefix(&self, column: Column, prefix: &str) -> Result<Vec<(String, String)>, StoreError> {
+        let columns = self.columns.read().map_err(|e| StoreError::Read(e.to_string()))?;
+        Ok(columns.get(&column).map(|entries| entries.range(prefix.to_owned()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()).unwrap_or_default())
+    }
+
+    fn dump(&self) -> Result<Vec<(Column, String, String)>, StoreError> {
+        let columns = self.columns.read().map_err(|e| StoreError::Read(e.to_string()))?;
+        let mut records = Vec::new();
+        for column in Column::ALL {
+            if let Some(entries) = columns.get(&column) {
+                records.extend(entries.iter().map(|(key, value)| (column, key.clone(), value.clone())));
+            }
+        }
+        Ok(records)
+    }
+
+    fn compact_event_marks(&self) -> Result<(), StoreError> {
+        let retention_millis = dedup_retention_secs().saturating_mul(1000);
+        let mut columns = self.columns.write().map_err(|e| StoreError::Write(e.to_string()))?;
+        if let Some(entries) = columns.get_mut(&Column::Dedup) {
+            entries.retain(|_, marked_at| !event_mark_expired(marked_at.as_bytes(), retention_millis));
+        }
+        Ok(())
+    }
+}
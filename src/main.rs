@@ -1,8 +1,10 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use event_listening::listening;
-use kv_store::{KVStore, RocksDB};
-use events_mq::{load_config, nft_launched_consumer::nft_launched_consume, service_opened_consumer::service_opened_consume};
+use replay::replay;
+use kv_store::{KVStore, MemoryStore, RocksDB};
+use migration::{ensure_current, migrate, open_migrated};
+use events_mq::{authorization_consumer::authorization_consume, load_config, nft_launched_consumer::nft_launched_consume, service_opened_consumer::service_opened_consume};
 use reqwest::StatusCode;
 use tokio::time::sleep;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -18,6 +20,18 @@ mod archive;
 mod template;
 mod sui_api_integration;
 mod kv_store;
+mod replay;
+mod repository;
+mod migration;
+mod backup;
+mod reconcile;
+mod recovery;
+mod validation;
+mod workspace;
+mod move_build;
+mod portfolio;
+mod harvest;
+mod treasury_stats;
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
@@ -39,7 +53,34 @@ async fn main() -> Result<(), anyhow::Error> {
     // let _ = declare_exchange(config.clone()).await;
 
     let rocksdb_dir_path = std::env::var("ROCKSDB_STORE_DIR_PATH").expect("ROCKSDB_STORE_DIR_PATH must be set");
-    let db = RocksDB::init(rocksdb_dir_path.as_str());
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 1 {
+        return run_command(&args[1..], rocksdb_dir_path.as_str()).await;
+    }
+    // KV_STORE_BACKEND=memory 时使用内存存储(本地开发)
+    let backend = std::env::var("KV_STORE_BACKEND").unwrap_or("rocksdb".to_owned());
+    match backend.as_str() {
+        "memory" => serve(MemoryStore::new()).await,
+        _ => {
+            let db = RocksDB::open(rocksdb_dir_path.as_str())?;
+            // 设置 STORE_CHECKPOINT_DIR 时定期创建检查点
+            if let Ok(checkpoint_dir) = std::env::var("STORE_CHECKPOINT_DIR") {
+                tokio::spawn(backup::checkpoint_periodically(db.clone(), checkpoint_dir));
+            }
+            // 设置 STORE_CONTROL_ADDR 时可通过 `store checkpoint` 为运行中的存储创建检查点
+            if let Some(addr) = backup::control_addr()? {
+                tokio::spawn(backup::serve_control(db.clone(), addr));
+            }
+            serve(db).await
+        }
+    }
+}
+
+/// 启动消费者与事件监听
+async fn serve<S: KVStore>(db: S) -> Result<(), anyhow::Error> {
+    // 启动前将存储迁移到当前结构版本
+    let report = migrate(&db, false)?;
+    info!("存储迁移完成:\n{}", report);
 
     let config = Arc::new(load_config().await);
     println!("config:{:?}", config.clone());
@@ -50,6 +91,21 @@ async fn main() -> Result<(), anyhow::Error> {
     let nft_launched_cfg = config.clone();
     tokio::spawn(nft_launched_consume(nft_launched_cfg, db.clone()));
 
+    let authorization_cfg = config.clone();
+    tokio::spawn(authorization_consume(authorization_cfg, db.clone()));
+
+    tokio::spawn(compact_event_marks(db.clone()));
+
+    // 设置 HARVEST_TREASURY_ADDRESS 时定期领取平台激励
+    if std::env::var("HARVEST_TREASURY_ADDRESS").is_ok() {
+        tokio::spawn(harvest::harvest_periodically(db.clone(), config.clone()));
+    }
+
+    // 设置 STATS_SNAPSHOT_INTERVAL_SECS 时定期发送统计快照
+    if std::env::var("STATS_SNAPSHOT_INTERVAL_SECS").is_ok() {
+        tokio::spawn(treasury_stats::publish_snapshots_periodically(db.clone(), config.clone()));
+    }
+
     // let package_id = "0x4b02907c0d7f471048c98e318343a0ed29b6e5e3a505bcf894106a9b2a915ac5";
     let package_id = std::env::var("LISTENING_PACKAGE_ID").expect("LISTENING_PACKAGE_ID must be set");
     let _= listening(package_id.as_str(), db.clone(), config.clone()).await;
@@ -62,6 +118,76 @@ async fn main() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// 定期压缩事件去重标记, 清理超过保留时长的标记
+async fn compact_event_marks<S: KVStore>(db: S) {
+    loop {
+        sleep(Duration::from_secs(60 * 60)).await;
+        let db = db.clone();
+        match tokio::task::spawn_blocking(move || db.compact_event_marks()).await {
+            Ok(Ok(())) => info!("事件去重标记压缩完成"),
+            Ok(Err(err)) => error!("事件去重标记压缩失败:{err:?}"),
+            Err(err) => error!("事件去重标记压缩失败:{err:?}"),
+        }
+    }
+}
+
+/// 命令行子命令
+async fn run_command(args: &[String], rocksdb_dir_path: &str) -> Result<(), anyhow::Error> {
+    match args[0].as_str() {
+        "replay" => {
+            let config = Arc::new(load_config().await);
+            let package_id = std::env::var("LISTENING_PACKAGE_ID").expect("LISTENING_PACKAGE_ID must be set");
+            // 忽略去重标记时无需打开存储(服务运行中时存储被占用)
+            let db = if args.iter().any(|arg| arg == "--ignore-dedup") {
+                None
+            } else {
+                Some(open_migrated(rocksdb_dir_path)?)
+            };
+            replay(&args[1..], package_id.as_str(), db, config).await
+        }
+        "migrate" => {
+            let dry_run = args.iter().any(|arg| arg == "--dry-run");
+            let db = RocksDB::open(rocksdb_dir_path)?;
+            let report = migrate(&db, dry_run)?;
+            println!("{}", report);
+            Ok(())
+        }
+        "store" => backup::store(&args[1..], rocksdb_dir_path),
+        "recover" => recovery::recover_command(&args[1..], open_migrated(rocksdb_dir_path)?).await,
+        "reconcile" => {
+            if args.iter().any(|arg| arg == "--repair") {
+                reconcile::reconcile_command(&args[1..], open_migrated(rocksdb_dir_path)?).await
+            } else {
+                // 只读对账时以secondary方式打开, 服务运行中也可执行
+                with_secondary_store(rocksdb_dir_path, |db| reconcile::reconcile_command(&args[1..], db)).await
+            }
+        }
+        // 只读查询, 以secondary方式打开
+        "portfolio" => with_secondary_store(rocksdb_dir_path, |db| async move { portfolio::portfolio_command(&args[1..], db) }).await,
+        "stats" => with_secondary_store(rocksdb_dir_path, |db| treasury_stats::stats_command(&args[1..], db)).await,
+        "harvest" => harvest::harvest_command(&args[1..], open_migrated(rocksdb_dir_path)?).await,
+        "workspace" => workspace::manager::workspace_command(&args[1..], open_migrated(rocksdb_dir_path)?),
+        command => Err(anyhow!("Unknown command:{}", command)),
+    }
+}
+
+/// 以secondary方式打开存储执行只读命令, 结束后删除secondary目录
+/// 只读打开无法迁移, 结构版本落后时先运行 migrate
+async fn with_secondary_store<F, Fut>(rocksdb_dir_path: &str, command: F) -> Result<(), anyhow::Error>
+where
+    F: FnOnce(RocksDB) -> Fut,
+    Fut: Future<Output = Result<(), anyhow::Error>>,
+{
+    let secondary_path = std::env::temp_dir().join(format!("bassinet-store-secondary-{}", std::process::id()));
+    let db = RocksDB::open_secondary(rocksdb_dir_path, secondary_path.to_str().unwrap())?;
+    let result = match ensure_current(&db) {
+        Ok(()) => command(db).await,
+        Err(err) => Err(err.into()),
+    };
+    let _ = std::fs::remove_dir_all(&secondary_path);
+    result
+}
+
 async fn get_collection(collection_id: &str, host: &str) -> Result<(String, String), anyhow::Error> {
     let mut count = 0;
     while count < 60 {
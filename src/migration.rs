@@ -0,0 +1,284 @@
+use std::fmt;
+
+use serde_json::Value;
+use tracing::info;
+
+use crate::{kv_store::{now_millis, Column, KVStore, MemoryStore, RocksDB, StoreError}, repository::DeferredMessage, sui_service::{BassinetCoinPublishedResult, NftPublishedResult, NETWORK}};
+
+/// 存储结构版本号, 保存在默认列族
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+/// 按版本顺序执行的迁移
+const MIGRATIONS: [(u32, &str); 3] = [
+    (1, "move legacy flat keys into column families"),
+    (2, "backfill network and upgrade_cap_id in published results"),
+    (3, "store envelopes with deferred NftLaunched messages"),
+];
+
+/// 当前存储结构版本
+pub fn current_schema_version() -> u32 {
+    MIGRATIONS.last().map(|(version, _)| *version).unwrap_or(0)
+}
+
+/// 迁移报告
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub dry_run: bool,
+    /// 已执行的迁移
+    pub applied: Vec<(u32, &'static str)>,
+    /// 改写的记录
+    pub changes: Vec<String>,
+    /// 无法识别、保留原样的记录
+    pub skipped: Vec<String>,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = if self.dry_run { " (dry run)" } else { "" };
+        writeln!(f, "schema version {} -> {}{}", self.from_version, self.to_version, mode)?;
+        for (version, description) in &self.applied {
+            writeln!(f, "  migration {}: {}", version, description)?;
+        }
+        for change in &self.changes {
+            writeln!(f, "  changed {}", change)?;
+        }
+        for skipped in &self.skipped {
+            writeln!(f, "  skipped {}", skipped)?;
+        }
+        write!(f, "{} changed, {} skipped", self.changes.len(), self.skipped.len())
+    }
+}
+
+/// 存储中记录的结构版本, 未记录时为0
+pub fn schema_version<S: KVStore>(store: &S) -> Result<u32, StoreError> {
+    match store.find(Column::Default, SCHEMA_VERSION_KEY)? {
+        Some(value) => value.parse::<u32>().map_err(|e| StoreError::InvalidRecord(SCHEMA_VERSION_KEY.to_owned(), e.to_string())),
+        None => Ok(0),
+    }
+}
+
+/// 执行未完成的迁移; dry_run 时在内存副本上执行, 只生成报告
+pub fn migrate<S: KVStore>(store: &S, dry_run: bool) -> Result<MigrationReport, StoreError> {
+    if dry_run {
+        let copy = MemoryStore::new();
+        for (column, key, value) in store.dump()? {
+            copy.save(column, &key, &value)?;
+        }
+        let mut report = run_migrations(&copy)?;
+        report.dry_run = true;
+        return Ok(report)
+    }
+    run_migrations(store)
+}
+
+/// 打开存储并执行未完成的迁移, 会写入存储的子命令都通过这里打开
+pub fn open_migrated(file_path: &str) -> Result<RocksDB, StoreError> {
+    let db = RocksDB::open(file_path)?;
+    let report = migrate(&db, false)?;
+    if !report.applied.is_empty() {
+        info!("存储迁移完成:\n{}", report);
+    }
+    Ok(db)
+}
+
+/// 只读打开的存储无法迁移, 结构版本不是当前版本时失败
+pub fn ensure_current<S: KVStore>(store: &S) -> Result<(), StoreError> {
+    let version = schema_version(store)?;
+    let current = current_schema_version();
+    if version > current {
+        return Err(StoreError::SchemaVersion(version, current))
+    }
+    if version < current {
+        return Err(StoreError::SchemaOutdated(version, current))
+    }
+    Ok(())
+}
+
+fn run_migrations<S: KVStore>(store: &S) -> Result<MigrationReport, StoreError> {
+    let from_version = schema_version(store)?;
+    let to_version = current_schema_version();
+    if from_version > to_version {
+        return Err(StoreError::SchemaVersion(from_version, to_version))
+    }
+    let mut report = MigrationReport { from_version, to_version, ..Default::default() };
+    for (version, description) in MIGRATIONS {
+        if version <= from_version {
+            continue;
+        }
+        match version {
+            1 => migrate_flat_keys(store, &mut report)?,
+            2 => backfill_published_results(store, &mut report)?,
+            3 => wrap_deferred_messages(store, &mut report)?,
+            _ => unreachable!("migration {} not implemented", version),
+        }
+        // 每个迁移完成后记录版本, 中断后从下一个迁移继续
+        store.save(Column::Default, SCHEMA_VERSION_KEY, version.to_string().as_str())?;
+        report.applied.push((version, description));
+    }
+    Ok(report)
+}
+
+/// v1: 旧版本所有记录平铺在默认列族, 按键名和值的格式归入各列族
+fn migrate_flat_keys<S: KVStore>(store: &S, report: &mut MigrationReport) -> Result<(), StoreError> {
+    for (key, value) in store.list(Column::Default)? {
+        if key == SCHEMA_VERSION_KEY {
+            continue;
+        }
+        let (column, new_key, new_value) = if let Some(address) = key.strip_suffix("_bassinet_coin") {
+            (Column::Creators, address.to_owned(), value)
+        } else if let Some(address) = key.strip_suffix("_deferred_nft_launched") {
+            (Column::Workflows, "deferred_nft_launched:".to_owned() + address, value)
+        } else if key.starts_with("bassinet_") {
+            (Column::Cursors, key.clone(), value)
+        } else if value == "1" {
+            // 旧去重标记没有标记时间, 从迁移时开始计算保留时长
+            (Column::Dedup, key.clone(), now_millis().to_string())
+        } else if let Ok(Value::Object(record)) = serde_json::from_str::<Value>(&value) {
+            if record.contains_key("treasury_lock_id") {
+                (Column::CoinPackages, key.clone(), value)
+            } else if record.contains_key("mint_id") {
+                (Column::NftPackages, key.clone(), value)
+            } else {
+                report.skipped.push(format!("{}: unrecognized record", key));
+                continue;
+            }
+        } else if value.starts_with("0x") {
+            (Column::Collections, key.clone(), value)
+        } else {
+            report.skipped.push(format!("{}: unrecognized value", key));
+            continue;
+        };
+        store.save(column, &new_key, &new_value)?;
+        store.delete(Column::Default, &key)?;
+        report.changes.push(format!("default/{} -> {}/{}", key, column.name(), new_key));
+    }
+    Ok(())
+}
+
+/// v2: 合约发布结果新增 network 和 upgrade_cap_id
+fn backfill_published_results<S: KVStore>(store: &S, report: &mut MigrationReport) -> Result<(), StoreError> {
+    for column in [Column::CoinPackages, Column::NftPackages] {
+        for (key, value) in store.list(column)? {
+            let mut record = match serde_json::from_str::<Value>(&value) {
+                Ok(Value::Object(record)) => record,
+                _ => {
+                    report.skipped.push(format!("{}/{}: invalid record", column.name(), key));
+                    continue;
+                }
+            };
+            if record.contains_key("network") && record.contains_key("upgrade_cap_id") {
+                continue;
+            }
+            record.entry("network").or_insert(Value::String(NETWORK.to_owned()));
+            record.entry("upgrade_cap_id").or_insert(Value::Null);
+            let record = Value::Object(record);
+            // 确认改写后的记录能被当前结构读取
+            let decoded = if column == Column::CoinPackages {
+                serde_json::from_value::<BassinetCoinPublishedResult>(record.clone()).map(|_| ())
+            } else {
+                serde_json::from_value::<NftPublishedResult>(record.clone()).map(|_| ())
+            };
+            if let Err(err) = decoded {
+                report.skipped.push(format!("{}/{}: {}", column.name(), key, err));
+                continue;
+            }
+            store.save(column, &key, &record.to_string())?;
+            report.changes.push(format!("{}/{}: backfilled network, upgrade_cap_id", column.name(), key));
+        }
+    }
+    Ok(())
+}
+
+/// v3: 延后的NftLaunched消息由字符串改为 DeferredMessage, 带信封的消息记录信封
+fn wrap_deferred_messages<S: KVStore>(store: &S, report: &mut MigrationReport) -> Result<(), StoreError> {
+    for (key, value) in store.list_prefix(Column::Workflows, "deferred_nft_launched:")? {
+        let Ok(messages) = serde_json::from_str::<Vec<String>>(&value) else {
+            if serde_json::from_str::<Vec<DeferredMessage>>(&value).is_err() {
+                report.skipped.push(format!("{}/{}: invalid deferred messages", Column::Workflows.name(), key));
+            }
+            continue;
+        };
+        let messages: Vec<DeferredMessage> = messages.iter().map(|content| DeferredMessage::new(content)).collect();
+        let value = serde_json::to_string(&messages).map_err(|e| StoreError::InvalidRecord(key.clone(), e.to_string()))?;
+        store.save(Column::Workflows, &key, &value)?;
+        report.changes.push(format!("{}/{}: {} deferred messages wrapped", Column::Workflows.name(), key, messages.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{CoinPackageRepository, CollectionRepository, CreatorRepository, CursorRepository, NftPackageRepository, WorkflowRepository};
+
+    /// 列族拆分前平铺在默认列族中的记录
+    fn legacy_store() -> MemoryStore {
+        let store = MemoryStore::new();
+        let coin = r#"{"package_id":"0xc01","admin_cap_id":"0xa01","treasury_lock_id":"0xb01","wallet_address":"0x1","account":"creator"}"#;
+        let nft = r#"{"collection_id":"c1","package_id":"0xn01","mint_id":"0xm01","policy_id":"0xp01","policy_cap_id":"0xq01"}"#;
+        for (key, value) in [
+            ("0x1_bassinet_coin", "0xc01"),
+            ("0xc01", coin),
+            ("0xn01", nft),
+            ("c1", "0xn01"),
+            ("0x2_deferred_nft_launched", r#"["{\"collection_id\":\"c2\"}"]"#),
+            ("bassinet_coin_cursor", "event-1"),
+            ("event-1", "1"),
+        ] {
+            store.save(Column::Default, key, value).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn v1_moves_every_legacy_key_layout() {
+        let store = legacy_store();
+        let report = migrate(&store, false).unwrap();
+        assert!(report.skipped.is_empty(), "{:?}", report.skipped);
+        assert_eq!(store.list(Column::Default).unwrap(), vec![(SCHEMA_VERSION_KEY.to_owned(), current_schema_version().to_string())]);
+
+        assert_eq!(CreatorRepository::new(store.clone()).coin_package_id("0x1").unwrap().as_deref(), Some("0xc01"));
+        let coin = CoinPackageRepository::new(store.clone()).find("0xc01").unwrap().unwrap();
+        assert_eq!(coin.network, NETWORK);
+        let nft = NftPackageRepository::new(store.clone()).find("0xn01").unwrap().unwrap();
+        assert_eq!(nft.collection_id, "c1");
+        assert_eq!(CollectionRepository::new(store.clone()).package_id("c1").unwrap().as_deref(), Some("0xn01"));
+        assert_eq!(WorkflowRepository::new(store.clone()).deferred_nft_launched("0x2").unwrap().len(), 1);
+        assert_eq!(CursorRepository::new(store.clone()).find("bassinet_coin_cursor").unwrap().as_deref(), Some("event-1"));
+        assert!(store.event_marked("event-1").unwrap());
+    }
+
+    #[test]
+    fn v3_keeps_envelopes_of_deferred_messages() {
+        let store = MemoryStore::new();
+        store.save(Column::Default, SCHEMA_VERSION_KEY, "2").unwrap();
+        let envelope = serde_json::json!({
+            "event_id": "event-1", "tx_digest": "tx", "event_seq": 0, "sender": "0x1", "timestamp_ms": 1000,
+            "package_id": "0x2", "module": "bassinet", "event_type": "NftLaunched", "struct_type": "0x2::bassinet::NftLaunched",
+            "payload": {"collection_id": "c1"},
+        }).to_string();
+        let legacy = r#"{"collection_id":"c2"}"#.to_owned();
+        let value = serde_json::to_string(&vec![envelope.clone(), legacy.clone()]).unwrap();
+        store.save(Column::Workflows, "deferred_nft_launched:0x1", &value).unwrap();
+
+        let report = migrate(&store, false).unwrap();
+        assert_eq!(report.applied, vec![(3, MIGRATIONS[2].1)]);
+        let deferred = WorkflowRepository::new(store.clone()).deferred_nft_launched("0x1").unwrap();
+        assert_eq!(deferred[0].content, envelope);
+        assert_eq!(deferred[0].envelope.as_ref().map(|envelope| envelope.event_id.as_str()), Some("event-1"));
+        assert_eq!(deferred[1].content, legacy);
+        assert!(deferred[1].envelope.is_none());
+    }
+
+    #[test]
+    fn ensure_current_rejects_unmigrated_store() {
+        let store = MemoryStore::new();
+        assert!(matches!(ensure_current(&store), Err(StoreError::SchemaOutdated(0, _))));
+        migrate(&store, false).unwrap();
+        assert!(ensure_current(&store).is_ok());
+        store.save(Column::Default, SCHEMA_VERSION_KEY, (current_schema_version() + 1).to_string().as_str()).unwrap();
+        assert!(matches!(ensure_current(&store), Err(StoreError::SchemaVersion(_, _))));
+    }
+}
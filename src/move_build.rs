@@ -0,0 +1,194 @@
+use std::{fmt, path::Path, sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use async_trait::async_trait;
+use sui_sdk::types::base_types::ObjectID;
+use thiserror::Error;
+
+use crate::kv_store::KVStore;
+
+pub mod cache;
+pub mod cli;
+pub mod in_process;
+pub mod patch;
+
+/// 默认编译超时(秒)
+const DEFAULT_BUILD_TIMEOUT_SECS: u64 = 300;
+
+/// patch模式失败后改为按创作者编译的次数
+static PATCH_FALLBACKS: AtomicU64 = AtomicU64::new(0);
+
+/// 编译结果: 待发布的模块字节码和依赖包ID
+#[derive(Debug, Clone)]
+pub struct BuiltPackage {
+    pub modules: Vec<Vec<u8>>,
+    pub dependencies: Vec<ObjectID>,
+}
+
+/// 编译器诊断信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// error 或 warning
+    pub severity: String,
+    pub code: Option<String>,
+    pub message: String,
+    /// 文件:行:列
+    pub location: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.severity)?;
+        if let Some(code) = &self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)?;
+        if let Some(location) = &self.location {
+            write!(f, " at {}", location)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BuildError {
+    #[error("failed to run move build: {0}")]
+    Spawn(#[from] std::io::Error),
+    #[error("move build timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("move build failed:\n{}", format_failure(.0, .1))]
+    Compile(Vec<Diagnostic>, String),
+    #[error("invalid move build output: {0}")]
+    InvalidOutput(String),
+    #[error("failed to read move package for build cache: {0}")]
+    Cache(std::io::Error),
+}
+
+/// 有诊断信息时只显示错误, 否则显示原始输出
+fn format_failure(diagnostics: &[Diagnostic], output: &str) -> String {
+    let errors: Vec<String> = diagnostics.iter()
+        .filter(|diagnostic| diagnostic.severity == "error")
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    if errors.is_empty() {
+        output.trim().to_owned()
+    } else {
+        errors.join("\n")
+    }
+}
+
+/// Move包编译
+#[async_trait]
+pub trait MoveBuilder: Send + Sync {
+    async fn build(&self, package_dir: &Path) -> Result<BuiltPackage, BuildError>;
+}
+
+/// 按 MOVE_BUILDER 选择编译方式: cli(默认, sui CLI) 或 in-process(进程内编译, 串行且超时后无法取消)
+pub fn builder_from_env() -> Box<dyn MoveBuilder> {
+    let timeout = std::env::var("MOVE_BUILD_TIMEOUT_SECS").ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_BUILD_TIMEOUT_SECS);
+    let timeout = Duration::from_secs(timeout);
+    match std::env::var("MOVE_BUILDER").unwrap_or("cli".to_owned()).as_str() {
+        "in-process" => Box::new(in_process::InProcessBuilder::new(timeout)),
+        _ => {
+            let binary = std::env::var("SUI_BINARY").unwrap_or("sui".to_owned());
+            let client_config = std::env::var("SUI_CLIENT_CONFIG").ok().filter(|path| !path.is_empty());
+            Box::new(cli::CliBuilder::new(binary.into(), timeout, client_config.map(Into::into)))
+        }
+    }
+}
+
+/// 带编译缓存的编译方式; MOVE_BUILD_CACHE=disabled 时不使用缓存
+pub fn cached_builder_from_env<S: KVStore>(store: S) -> Box<dyn MoveBuilder> {
+    let builder = builder_from_env();
+    match std::env::var("MOVE_BUILD_CACHE").unwrap_or_default().as_str() {
+        "disabled" => builder,
+        _ => Box::new(cache::CachedBuilder::new(builder, store)),
+    }
+}
+
+/// MOVE_PUBLISH_MODE=patch 时模板只编译一次, 发布前替换字节码中创作者相关的地址和常量
+pub fn patch_enabled() -> bool {
+    std::env::var("MOVE_PUBLISH_MODE").is_ok_and(|mode| mode == "patch")
+}
+
+/// 记录一次patch模式回退, 返回累计次数
+pub fn record_patch_fallback() -> u64 {
+    PATCH_FALLBACKS.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+/// 解析编译器输出中的诊断信息, 格式:
+/// error[E01002]: unexpected token
+///    ┌─ ./sources/bassinet_coin.move:18:25
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if let Some(location) = trimmed.strip_prefix("┌─") {
+            if let Some(last) = diagnostics.last_mut().filter(|last| last.location.is_none()) {
+                last.location = Some(location.trim().to_owned());
+            }
+            continue;
+        }
+        for severity in ["error", "warning"] {
+            let Some(rest) = trimmed.strip_prefix(severity) else {
+                continue;
+            };
+            let (code, rest) = match rest.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+                Some((code, rest)) => (Some(code.to_owned()), rest),
+                None => (None, rest),
+            };
+            if let Some(message) = rest.strip_prefix(':') {
+                diagnostics.push(Diagnostic {
+                    severity: severity.to_owned(),
+                    code,
+                    message: message.trim().to_owned(),
+                    location: None,
+                });
+            }
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_compiler_diagnostics() {
+        let diagnostics = parse_diagnostics(include_str!("move_build/fixtures/build_failed.txt"));
+        assert_eq!(diagnostics, vec![
+            Diagnostic {
+                severity: "warning".to_owned(),
+                code: Some("W09002".to_owned()),
+                message: "unused variable".to_owned(),
+                location: Some("./sources/bassinet_coin.move:120:9".to_owned()),
+            },
+            Diagnostic {
+                severity: "error".to_owned(),
+                code: Some("E01002".to_owned()),
+                message: "unexpected token".to_owned(),
+                location: Some("./sources/bassinet_coin.move:18:25".to_owned()),
+            },
+            Diagnostic {
+                severity: "error".to_owned(),
+                code: None,
+                message: "unbound module alias".to_owned(),
+                location: None,
+            },
+        ]);
+        assert_eq!(diagnostics[1].to_string(), "error[E01002]: unexpected token at ./sources/bassinet_coin.move:18:25");
+    }
+
+    #[test]
+    fn failure_shows_errors_or_raw_output() {
+        let output = include_str!("move_build/fixtures/build_failed.txt");
+        let message = BuildError::Compile(parse_diagnostics(output), output.to_owned()).to_string();
+        assert_eq!(message, "move build failed:\nerror[E01002]: unexpected token at ./sources/bassinet_coin.move:18:25\nerror: unbound module alias");
+
+        let message = BuildError::Compile(parse_diagnostics("Killed\n"), "Killed\n".to_owned()).to_string();
+        assert_eq!(message, "move build failed:\nKilled");
+    }
+}
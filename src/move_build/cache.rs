@@ -0,0 +1,183 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use async_trait::async_trait;
+use fastcrypto::encoding::{Base64, Encoding};
+use sha2::{Digest, Sha256};
+use sui_sdk::types::base_types::ObjectID;
+use tracing::{info, warn};
+
+use crate::{kv_store::{now_millis, KVStore}, repository::{BuildCacheEntry, BuildCacheRepository}, template::source::template_version};
+
+use super::{BuildError, BuiltPackage, MoveBuilder};
+
+/// 按渲染后的源码缓存编译结果, 命中时不再编译
+pub struct CachedBuilder<S: KVStore> {
+    inner: Box<dyn MoveBuilder>,
+    cache: BuildCacheRepository<S>,
+}
+
+impl<S: KVStore> CachedBuilder<S> {
+
+    pub fn new(inner: Box<dyn MoveBuilder>, store: S) -> Self {
+        Self { inner, cache: BuildCacheRepository::new(store) }
+    }
+}
+
+#[async_trait]
+impl<S: KVStore> MoveBuilder for CachedBuilder<S> {
+
+    async fn build(&self, package_dir: &Path) -> Result<BuiltPackage, BuildError> {
+        let key = cache_key(package_dir).map_err(BuildError::Cache)?;
+        // 缓存读写失败不影响编译
+        match self.cache.find(&key) {
+            Ok(Some(entry)) => match decode_entry(&entry) {
+                Ok(package) => {
+                    info!("move build cache hit {}: {}", package_dir.display(), key);
+                    return Ok(package)
+                }
+                Err(err) => warn!("move build cache entry {} invalid: {}", key, err),
+            },
+            Ok(None) => {}
+            Err(err) => warn!("move build cache read failed: {}", err),
+        }
+
+        let package = self.inner.build(package_dir).await?;
+        let entry = BuildCacheEntry {
+            modules: package.modules.iter().map(Base64::encode).collect(),
+            dependencies: package.dependencies.iter().map(|id| id.to_hex_literal()).collect(),
+            template_version: template_version().to_owned(),
+            created_at: now_millis(),
+        };
+        if let Err(err) = self.cache.save(&key, &entry) {
+            warn!("move build cache write failed: {}", err);
+        }
+        Ok(package)
+    }
+}
+
+/// 缓存键: 模板版本、Move.toml 和 sources 下全部文件(相对路径和内容)的 sha256,
+/// 包括 Move.toml 中的本地依赖(local = "..")及其本地依赖
+pub fn cache_key(package_dir: &Path) -> Result<String, io::Error> {
+    let mut packages = vec![PathBuf::new()];
+    let mut visited = vec![package_dir.canonicalize()?];
+    let mut files = Vec::new();
+    while let Some(package) = packages.pop() {
+        let manifest = package.join("Move.toml");
+        let content = fs::read_to_string(package_dir.join(&manifest))?;
+        for dependency in local_dependencies(&content) {
+            let relative = package.join(dependency);
+            let canonical = package_dir.join(&relative).canonicalize()?;
+            if !visited.contains(&canonical) {
+                visited.push(canonical);
+                packages.push(relative);
+            }
+        }
+        files.push(manifest);
+        collect_sources(package_dir, &package.join("sources"), &mut files)?;
+    }
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    hasher.update(template_version().as_bytes());
+    hasher.update([0u8]);
+    for file in files {
+        let content = fs::read(package_dir.join(&file))?;
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update([0u8]);
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Move.toml [dependencies] 中的本地依赖路径
+fn local_dependencies(manifest: &str) -> Vec<String> {
+    let mut dependencies = Vec::new();
+    let mut in_dependencies = false;
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.starts_with('[') {
+            in_dependencies = line == "[dependencies]";
+            continue;
+        }
+        if !in_dependencies {
+            continue;
+        }
+        for (start, key) in line.match_indices("local") {
+            // 只匹配键 local, 不匹配包名中的 local
+            if line[..start].ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                continue;
+            }
+            let Some(rest) = line[start + key.len()..].trim_start().strip_prefix('=') else {
+                continue;
+            };
+            let mut parts = rest.trim_start().split('"');
+            if let (Some(""), Some(path)) = (parts.next(), parts.next()) {
+                dependencies.push(path.to_owned());
+            }
+        }
+    }
+    dependencies
+}
+
+fn collect_sources(package_dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    for entry in fs::read_dir(package_dir.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            collect_sources(package_dir, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn decode_entry(entry: &BuildCacheEntry) -> Result<BuiltPackage, anyhow::Error> {
+    let modules = entry.modules.iter().map(|module| Base64::decode(module)).collect::<Result<Vec<_>, _>>()?;
+    let dependencies = entry.dependencies.iter().map(|id| ObjectID::from_hex_literal(id)).collect::<Result<Vec<_>, _>>()?;
+    Ok(BuiltPackage { modules, dependencies })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_local_dependencies() {
+        let manifest = r#"
+[package]
+name = "bassinet"
+
+[dependencies]
+# Local = { local = "../path/to" }
+bassinet_coin = {local = "../bassinet_coin"}
+my_local = { local = "../my_local", override = true }
+Remote = { git = "https://some.remote/host.git", subdir = "remote/path", rev = "main" }
+
+[dev-dependencies]
+Dev = { local = "../dev" }
+"#;
+        assert_eq!(local_dependencies(manifest), vec!["../bassinet_coin".to_owned(), "../my_local".to_owned()]);
+    }
+
+    #[test]
+    fn key_changes_with_dependency_sources() {
+        let root = std::env::temp_dir().join(format!("bassinet-cache-{}", uuid::Uuid::new_v4()));
+        let nft = root.join("bassinet");
+        let coin = root.join("bassinet_coin");
+        fs::create_dir_all(nft.join("sources")).unwrap();
+        fs::create_dir_all(coin.join("sources")).unwrap();
+        fs::write(nft.join("Move.toml"), "[dependencies]\nbassinet_coin = {local = \"../bassinet_coin\"}\n").unwrap();
+        fs::write(nft.join("sources/bassinet.move"), "module bassinet::bassinet;").unwrap();
+        fs::write(coin.join("Move.toml"), "[package]\nname = \"bassinet_coin\"\n").unwrap();
+        fs::write(coin.join("sources/bassinet_coin.move"), "const MAX_SUPPLY: u64 = 1;").unwrap();
+
+        let before = cache_key(&nft).unwrap();
+        assert_eq!(cache_key(&nft).unwrap(), before);
+        fs::write(coin.join("sources/bassinet_coin.move"), "const MAX_SUPPLY: u64 = 2;").unwrap();
+        let after = cache_key(&nft).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_ne!(before, after);
+    }
+}
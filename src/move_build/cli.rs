@@ -0,0 +1,161 @@
+use std::{fs, path::{Path, PathBuf}, process::Stdio, time::Duration};
+
+use async_trait::async_trait;
+use fastcrypto::encoding::{Base64, Encoding};
+use serde_json::Value;
+use sui_sdk::types::base_types::ObjectID;
+use tokio::process::Command;
+use tracing::warn;
+
+use crate::sui_service::{rpc_url, NETWORK};
+
+use super::{parse_diagnostics, BuildError, BuiltPackage, MoveBuilder};
+
+/// 通过 sui CLI 编译(sui move --client.config <config> build --dump-bytecode-as-base64)
+pub struct CliBuilder {
+    binary: PathBuf,
+    timeout: Duration,
+    /// 未设置时每次编译生成独立的客户端配置, 不读写全局 ~/.sui 配置
+    client_config: Option<PathBuf>,
+}
+
+impl CliBuilder {
+
+    pub fn new(binary: PathBuf, timeout: Duration, client_config: Option<PathBuf>) -> Self {
+        Self { binary, timeout, client_config }
+    }
+}
+
+#[async_trait]
+impl MoveBuilder for CliBuilder {
+
+    async fn build(&self, package_dir: &Path) -> Result<BuiltPackage, BuildError> {
+        let isolated;
+        let client_config = match &self.client_config {
+            Some(path) => path.clone(),
+            None => {
+                isolated = IsolatedClientConfig::create()?;
+                isolated.config_path()
+            }
+        };
+
+        let child = Command::new(&self.binary)
+            .current_dir(package_dir)
+            .arg("move")
+            .arg("--client.config").arg(&client_config)
+            .arg("build")
+            .arg("--dump-bytecode-as-base64")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output()).await
+            .map_err(|_| BuildError::Timeout(self.timeout))??;
+
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        let diagnostics = parse_diagnostics(&stderr);
+        if !output.status.success() {
+            return Err(BuildError::Compile(diagnostics, stderr))
+        }
+        for diagnostic in diagnostics.iter().filter(|diagnostic| diagnostic.severity == "warning") {
+            warn!("move build {}: {}", package_dir.display(), diagnostic);
+        }
+        parse_output(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// 解析 --dump-bytecode-as-base64 输出的JSON({"modules": [...], "dependencies": [...]})
+fn parse_output(stdout: &str) -> Result<BuiltPackage, BuildError> {
+    // JSON之前可能有编译进度输出
+    let json = stdout.find('{').map(|start| &stdout[start..]).ok_or(BuildError::InvalidOutput(stdout.to_owned()))?;
+    let value: Value = serde_json::from_str(json.trim()).map_err(|e| BuildError::InvalidOutput(e.to_string()))?;
+    let array = |name: &str| value[name].as_array().ok_or(BuildError::InvalidOutput(format!("{} not found", name)));
+    let mut modules = Vec::new();
+    for module in array("modules")? {
+        let module = module.as_str().ok_or(BuildError::InvalidOutput(module.to_string()))?;
+        modules.push(Base64::decode(module).map_err(|e| BuildError::InvalidOutput(e.to_string()))?);
+    }
+    let mut dependencies = Vec::new();
+    for dependency in array("dependencies")? {
+        let dependency = dependency.as_str().ok_or(BuildError::InvalidOutput(dependency.to_string()))?;
+        dependencies.push(ObjectID::from_hex_literal(dependency).map_err(|e| BuildError::InvalidOutput(e.to_string()))?);
+    }
+    Ok(BuiltPackage { modules, dependencies })
+}
+
+/// 单次编译使用的临时客户端配置(空密钥库, 只包含发布网络), 结束后删除
+struct IsolatedClientConfig {
+    dir: PathBuf,
+}
+
+impl IsolatedClientConfig {
+
+    fn create() -> Result<Self, std::io::Error> {
+        let dir = std::env::temp_dir().join(format!("bassinet-sui-client-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        let config = Self { dir };
+        let keystore = config.dir.join("sui.keystore");
+        fs::write(&keystore, "[]")?;
+        let keystore = serde_json::to_string(&keystore.to_string_lossy()).map_err(std::io::Error::other)?;
+        let content = format!(
+            "---\nkeystore:\n  File: {}\nenvs:\n  - alias: {}\n    rpc: \"{}\"\n    ws: ~\n    basic_auth: ~\nactive_env: {}\nactive_address: ~\n",
+            keystore, NETWORK, rpc_url(), NETWORK
+        );
+        fs::write(config.config_path(), content)?;
+        Ok(config)
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.dir.join("client.yaml")
+    }
+}
+
+impl Drop for IsolatedClientConfig {
+
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_base64_output_after_progress_lines() {
+        let package = parse_output(include_str!("fixtures/build_output.txt")).unwrap();
+        assert_eq!(package.modules, vec![vec![0xa1, 0x1c, 0xeb, 0x0b, 6, 0, 0, 0]; 2]);
+        assert_eq!(package.dependencies, vec![
+            ObjectID::from_hex_literal("0x1").unwrap(),
+            ObjectID::from_hex_literal("0x2").unwrap(),
+            ObjectID::from_hex_literal("0xb24daa1045805db4a6806d0c69cf4f8ce1237c6242d0e7a66cdaa47f8f23d475").unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn rejects_invalid_output() {
+        let invalid = [
+            "BUILDING bassinet\n",
+            "{\"modules\": [",
+            r#"{"dependencies": []}"#,
+            r#"{"modules": ["not base64!"], "dependencies": []}"#,
+            r#"{"modules": [1], "dependencies": []}"#,
+            r#"{"modules": [], "dependencies": ["0xzz"]}"#,
+        ];
+        for stdout in invalid {
+            assert!(matches!(parse_output(stdout), Err(BuildError::InvalidOutput(_))), "{}", stdout);
+        }
+    }
+
+    #[test]
+    fn isolated_config_uses_network_rpc() {
+        let config = IsolatedClientConfig::create().unwrap();
+        let content = fs::read_to_string(config.config_path()).unwrap();
+        assert!(content.contains(&format!("rpc: \"{}\"", rpc_url())));
+        assert!(content.contains(&format!("active_env: {}", NETWORK)));
+        let dir = config.dir.clone();
+        drop(config);
+        assert!(!dir.exists());
+    }
+}
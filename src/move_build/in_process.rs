@@ -0,0 +1,56 @@
+use std::{path::Path, sync::{Arc, OnceLock}, time::Duration};
+
+use async_trait::async_trait;
+use sui_move_build::BuildConfig;
+use tokio::sync::Semaphore;
+
+use super::{parse_diagnostics, BuildError, BuiltPackage, MoveBuilder};
+
+/// 进程内编译(sui-move-build), 不需要 sui CLI
+/// 编译在阻塞线程中运行, 无法取消: 同一时间只运行一个编译, 超时的编译运行结束前后续编译排队等待
+pub struct InProcessBuilder {
+    timeout: Duration,
+}
+
+impl InProcessBuilder {
+
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+/// 所有进程内编译共用的许可, 编译线程结束时释放
+fn build_permits() -> Arc<Semaphore> {
+    static PERMITS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+    PERMITS.get_or_init(|| Arc::new(Semaphore::new(1))).clone()
+}
+
+#[async_trait]
+impl MoveBuilder for InProcessBuilder {
+
+    async fn build(&self, package_dir: &Path) -> Result<BuiltPackage, BuildError> {
+        let package_dir = package_dir.to_path_buf();
+        // 超时包含排队时间; 超时后返回错误, 编译线程仍会运行到结束并一直持有许可
+        let task = async move {
+            let permit = build_permits().acquire_owned().await
+                .map_err(|e| BuildError::InvalidOutput(format!("build permit closed: {}", e)))?;
+            tokio::task::spawn_blocking(move || {
+                let _permit = permit;
+                let mut config = BuildConfig::default();
+                config.print_diags_to_stderr = false;
+                config.run_bytecode_verifier = true;
+                let package = config.build(&package_dir).map_err(|e| {
+                    let output = e.to_string();
+                    BuildError::Compile(parse_diagnostics(&output), output)
+                })?;
+                Ok(BuiltPackage {
+                    modules: package.get_package_bytes(false),
+                    dependencies: package.get_dependency_storage_package_ids(),
+                })
+            }).await
+                .map_err(|e| BuildError::InvalidOutput(format!("build task failed: {}", e)))?
+        };
+        tokio::time::timeout(self.timeout, task).await
+            .map_err(|_| BuildError::Timeout(self.timeout))?
+    }
+}
@@ -0,0 +1,267 @@
+use move_binary_format::{file_format::{Bytecode, Constant, ConstantPoolIndex, SignatureToken}, CompiledModule};
+use move_core_types::account_address::AccountAddress;
+use sui_sdk::types::base_types::ObjectID;
+use thiserror::Error;
+
+use super::BuiltPackage;
+
+/// 模板编译时使用的占位值, 发布前在字节码中替换为创作者的值
+pub const PLACEHOLDER_CREATOR: &str = "0xcccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+pub const PLACEHOLDER_PROVIDER: &str = "0xdddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd";
+pub const PLACEHOLDER_COIN_PACKAGE: &str = "0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee";
+pub const PLACEHOLDER_SYMBOL: &str = "BASSINET_PLACEHOLDER_SYMBOL";
+pub const PLACEHOLDER_NAME: &str = "BASSINET_PLACEHOLDER_NAME";
+pub const PLACEHOLDER_DESCRIPTION: &str = "BASSINET_PLACEHOLDER_DESCRIPTION";
+pub const PLACEHOLDER_ICON_URL: &str = "https://placeholder.bassinet/icon";
+
+#[derive(Error, Debug)]
+pub enum PatchError {
+    #[error("invalid patch value for {0}: {1}")]
+    InvalidValue(&'static str, String),
+    #[error("deserialize module failed: {0}")]
+    Deserialize(String),
+    #[error("serialize module failed: {0}")]
+    Serialize(String),
+    #[error("patched module failed verification: {0}")]
+    Verify(String),
+    #[error("placeholder {0} not found in compiled modules")]
+    NotFound(&'static str),
+}
+
+/// 字节码替换: 地址标识符、地址常量和字节串常量
+#[derive(Debug, Default)]
+pub struct Patch {
+    addresses: Vec<(&'static str, AccountAddress, AccountAddress)>,
+    /// 常量池中的 vector<u8>(BCS编码)
+    byte_strings: Vec<(&'static str, Vec<u8>, Vec<u8>)>,
+}
+
+impl Patch {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn address(mut self, name: &'static str, placeholder: &str, value: &str) -> Result<Self, PatchError> {
+        let parse = |address: &str| AccountAddress::from_hex_literal(address).map_err(|e| PatchError::InvalidValue(name, e.to_string()));
+        self.addresses.push((name, parse(placeholder)?, parse(value)?));
+        Ok(self)
+    }
+
+    pub fn byte_string(mut self, name: &'static str, placeholder: &str, value: &str) -> Result<Self, PatchError> {
+        let encode = |value: &str| bcs::to_bytes(value.as_bytes()).map_err(|e| PatchError::InvalidValue(name, e.to_string()));
+        self.byte_strings.push((name, encode(placeholder)?, encode(value)?));
+        Ok(self)
+    }
+
+    /// 替换全部模块并逐个校验; 每个占位值至少要出现一次, 避免模板变化后发布未替换的字节码
+    pub fn apply(&self, package: &BuiltPackage) -> Result<BuiltPackage, PatchError> {
+        let mut address_found = vec![false; self.addresses.len()];
+        let mut byte_string_found = vec![false; self.byte_strings.len()];
+        let mut modules = Vec::with_capacity(package.modules.len());
+        for bytes in &package.modules {
+            let mut module = CompiledModule::deserialize_with_defaults(bytes).map_err(|e| PatchError::Deserialize(e.to_string()))?;
+            for address in module.address_identifiers.iter_mut() {
+                for (index, (_, placeholder, value)) in self.addresses.iter().enumerate() {
+                    if address == placeholder {
+                        *address = *value;
+                        address_found[index] = true;
+                    }
+                }
+            }
+            for constant in module.constant_pool.iter_mut() {
+                match &constant.type_ {
+                    SignatureToken::Address => {
+                        for (index, (_, placeholder, value)) in self.addresses.iter().enumerate() {
+                            if constant.data == placeholder.into_bytes() {
+                                constant.data = value.to_vec();
+                                address_found[index] = true;
+                            }
+                        }
+                    }
+                    SignatureToken::Vector(inner) if **inner == SignatureToken::U8 => {
+                        for (index, (_, placeholder, value)) in self.byte_strings.iter().enumerate() {
+                            if &constant.data == placeholder {
+                                constant.data = value.clone();
+                                byte_string_found[index] = true;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            // 替换后可能出现重复常量(例如代币符号与名称相同, 创作者与平台地址相同), 校验器不接受重复常量
+            dedup_constants(&mut module)?;
+            move_bytecode_verifier::verify_module_unmetered(&module).map_err(|e| PatchError::Verify(e.to_string()))?;
+            let mut patched = Vec::new();
+            module.serialize_with_version(module.version, &mut patched).map_err(|e| PatchError::Serialize(e.to_string()))?;
+            modules.push(patched);
+        }
+
+        let mut dependencies = Vec::with_capacity(package.dependencies.len());
+        for dependency in &package.dependencies {
+            let mut dependency = *dependency;
+            for (index, (_, placeholder, value)) in self.addresses.iter().enumerate() {
+                if dependency == ObjectID::from(*placeholder) {
+                    dependency = ObjectID::from(*value);
+                    address_found[index] = true;
+                }
+            }
+            dependencies.push(dependency);
+        }
+
+        if let Some(index) = address_found.iter().position(|found| !found) {
+            return Err(PatchError::NotFound(self.addresses[index].0))
+        }
+        if let Some(index) = byte_string_found.iter().position(|found| !found) {
+            return Err(PatchError::NotFound(self.byte_strings[index].0))
+        }
+        Ok(BuiltPackage { modules, dependencies })
+    }
+}
+
+/// 合并常量池中的重复常量, 并把 LdConst 指向保留的常量
+fn dedup_constants(module: &mut CompiledModule) -> Result<(), PatchError> {
+    let mut constants: Vec<Constant> = Vec::with_capacity(module.constant_pool.len());
+    let mut remap = Vec::with_capacity(module.constant_pool.len());
+    for constant in module.constant_pool.drain(..) {
+        let index = match constants.iter().position(|kept| *kept == constant) {
+            Some(index) => index,
+            None => {
+                constants.push(constant);
+                constants.len() - 1
+            }
+        };
+        remap.push(ConstantPoolIndex(index as u16));
+    }
+    module.constant_pool = constants;
+    if module.constant_pool.len() == remap.len() {
+        return Ok(())
+    }
+    for function in module.function_defs.iter_mut() {
+        let Some(code) = function.code.as_mut() else {
+            continue;
+        };
+        for instruction in code.code.iter_mut() {
+            if let Bytecode::LdConst(index) = instruction {
+                *index = *remap.get(index.0 as usize)
+                    .ok_or(PatchError::Verify(format!("constant index {} out of bounds", index.0)))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use move_binary_format::file_format::{empty_module, CodeUnit, FunctionDefinition, FunctionHandle, FunctionHandleIndex, IdentifierIndex, ModuleHandleIndex, Signature, SignatureIndex, Visibility};
+    use move_core_types::identifier::Identifier;
+
+    use super::*;
+
+    fn byte_string(value: &str) -> Constant {
+        Constant { type_: SignatureToken::Vector(Box::new(SignatureToken::U8)), data: bcs::to_bytes(value.as_bytes()).unwrap() }
+    }
+
+    fn address(value: &str) -> Constant {
+        Constant { type_: SignatureToken::Address, data: AccountAddress::from_hex_literal(value).unwrap().to_vec() }
+    }
+
+    /// 模板模块: symbol()/name() 返回字节串常量, creator()/provider() 返回地址常量
+    fn template_package() -> BuiltPackage {
+        let mut module = empty_module();
+        module.identifiers = vec![Identifier::new("bassinet_coin").unwrap()];
+        module.address_identifiers = vec![AccountAddress::from_hex_literal(PLACEHOLDER_COIN_PACKAGE).unwrap()];
+        module.constant_pool = vec![byte_string(PLACEHOLDER_SYMBOL), byte_string(PLACEHOLDER_NAME), address(PLACEHOLDER_CREATOR), address(PLACEHOLDER_PROVIDER)];
+        module.signatures = vec![
+            Signature(vec![]),
+            Signature(vec![SignatureToken::Vector(Box::new(SignatureToken::U8))]),
+            Signature(vec![SignatureToken::Address]),
+        ];
+        for (index, (name, return_)) in [("symbol", 1), ("name", 1), ("creator", 2), ("provider", 2)].into_iter().enumerate() {
+            module.identifiers.push(Identifier::new(name).unwrap());
+            module.function_handles.push(FunctionHandle {
+                module: ModuleHandleIndex(0),
+                name: IdentifierIndex(index as u16 + 1),
+                parameters: SignatureIndex(0),
+                return_: SignatureIndex(return_),
+                type_parameters: vec![],
+            });
+            module.function_defs.push(FunctionDefinition {
+                function: FunctionHandleIndex(index as u16),
+                visibility: Visibility::Public,
+                is_entry: false,
+                acquires_global_resources: vec![],
+                code: Some(CodeUnit {
+                    locals: SignatureIndex(0),
+                    code: vec![Bytecode::LdConst(ConstantPoolIndex(index as u16)), Bytecode::Ret],
+                    jump_tables: vec![],
+                }),
+            });
+        }
+        move_bytecode_verifier::verify_module_unmetered(&module).unwrap();
+        let mut bytes = Vec::new();
+        module.serialize_with_version(module.version, &mut bytes).unwrap();
+        BuiltPackage { modules: vec![bytes], dependencies: vec![ObjectID::from_hex_literal("0x2").unwrap()] }
+    }
+
+    fn patch(creator: &str, provider: &str, symbol: &str, name: &str) -> Patch {
+        Patch::new()
+            .address("coin_package", PLACEHOLDER_COIN_PACKAGE, "0x33").unwrap()
+            .address("creator", PLACEHOLDER_CREATOR, creator).unwrap()
+            .address("platform_provider", PLACEHOLDER_PROVIDER, provider).unwrap()
+            .byte_string("symbol", PLACEHOLDER_SYMBOL, symbol).unwrap()
+            .byte_string("name", PLACEHOLDER_NAME, name).unwrap()
+    }
+
+    fn patched_module(package: &BuiltPackage) -> CompiledModule {
+        assert_eq!(package.modules.len(), 1);
+        CompiledModule::deserialize_with_defaults(&package.modules[0]).unwrap()
+    }
+
+    /// 函数加载的常量
+    fn loaded_constant(module: &CompiledModule, function: u16) -> &Constant {
+        let code = module.function_defs[function as usize].code.as_ref().unwrap();
+        match code.code[0] {
+            Bytecode::LdConst(index) => &module.constant_pool[index.0 as usize],
+            ref instruction => panic!("unexpected instruction {:?}", instruction),
+        }
+    }
+
+    #[test]
+    fn replaces_addresses_and_byte_strings() {
+        let package = patch("0x11", "0x22", "ABC", "A Token").apply(&template_package()).unwrap();
+        let module = patched_module(&package);
+        assert_eq!(module.address_identifiers, vec![AccountAddress::from_hex_literal("0x33").unwrap()]);
+        assert_eq!(module.constant_pool, vec![byte_string("ABC"), byte_string("A Token"), address("0x11"), address("0x22")]);
+        assert_eq!(loaded_constant(&module, 1), &byte_string("A Token"));
+        assert_eq!(package.dependencies, vec![ObjectID::from_hex_literal("0x2").unwrap()]);
+    }
+
+    #[test]
+    fn missing_placeholder_is_rejected() {
+        let patch = patch("0x11", "0x22", "ABC", "A Token")
+            .byte_string("description", PLACEHOLDER_DESCRIPTION, "text").unwrap();
+        let result = patch.apply(&template_package());
+        assert!(matches!(result, Err(PatchError::NotFound("description"))));
+    }
+
+    #[test]
+    fn invalid_address_is_rejected() {
+        let result = Patch::new().address("creator", PLACEHOLDER_CREATOR, "creator");
+        assert!(matches!(result, Err(PatchError::InvalidValue("creator", _))));
+    }
+
+    #[test]
+    fn duplicate_constants_are_merged() {
+        // 代币符号与名称相同, 创作者与平台地址相同
+        let package = patch("0x11", "0x11", "ABC", "ABC").apply(&template_package()).unwrap();
+        let module = patched_module(&package);
+        assert_eq!(module.constant_pool, vec![byte_string("ABC"), address("0x11")]);
+        assert_eq!(loaded_constant(&module, 0), &byte_string("ABC"));
+        assert_eq!(loaded_constant(&module, 1), &byte_string("ABC"));
+        assert_eq!(loaded_constant(&module, 2), &address("0x11"));
+        assert_eq!(loaded_constant(&module, 3), &address("0x11"));
+        move_bytecode_verifier::verify_module_unmetered(&module).unwrap();
+    }
+}
@@ -0,0 +1,43 @@
+use anyhow::anyhow;
+
+use crate::{kv_store::KVStore, repository::{OperationRepository, PortfolioRepository}};
+
+const USAGE: &str = "usage: bassinet-sui portfolio <command>
+  <wallet_address>               show a creator's coin package and NFT collections
+  --collection <collection_id>   show a collection and its creator
+  --list                         list creators with their collection counts
+  --operations <wallet_address>  show a creator's authorization operations";
+
+/// 创作者资产查询子命令, 输出JSON
+pub fn portfolio_command<S: KVStore>(args: &[String], db: S) -> Result<(), anyhow::Error> {
+    let portfolios = PortfolioRepository::new(db.clone());
+    match args.first().map(|arg| arg.as_str()) {
+        Some("--collection") => {
+            let collection_id = args.get(1).ok_or(anyhow!(USAGE))?;
+            let collection = portfolios.find_collection(collection_id)?
+                .ok_or(anyhow!("collection {} not found", collection_id))?;
+            println!("{}", serde_json::to_string_pretty(&collection)?);
+            Ok(())
+        }
+        Some("--list") => {
+            for wallet_address in portfolios.creators()? {
+                let collections = portfolios.collections(&wallet_address)?;
+                println!("{} {} collections", wallet_address, collections.len());
+            }
+            Ok(())
+        }
+        Some("--operations") => {
+            let wallet_address = args.get(1).ok_or(anyhow!(USAGE))?;
+            let operations = OperationRepository::new(db).list(wallet_address)?;
+            println!("{}", serde_json::to_string_pretty(&operations)?);
+            Ok(())
+        }
+        Some(wallet_address) if !wallet_address.starts_with("--") => {
+            let portfolio = portfolios.portfolio(wallet_address)?
+                .ok_or(anyhow!("no portfolio recorded for {}", wallet_address))?;
+            println!("{}", serde_json::to_string_pretty(&portfolio)?);
+            Ok(())
+        }
+        _ => Err(anyhow!(USAGE)),
+    }
+}
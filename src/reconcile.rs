@@ -0,0 +1,451 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use sui_sdk::{rpc_types::SuiObjectDataOptions, types::{base_types::{ObjectID, ObjectType}, object::Owner, parse_sui_struct_tag}, SuiClient};
+
+use crate::{event_listening::get_client, kv_store::{KVStore, WriteBatch}, repository::{CoinPackageRepository, CollectionRepository, CreatorRepository, NftPackageRepository}, sui_service::{BassinetCoinPublishedResult, NftPublishedResult}};
+
+const USAGE: &str = "usage: bassinet-sui reconcile [--repair]";
+
+/// 对象所有者
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainOwner {
+    Address(String),
+    Object(String),
+    Shared,
+    Immutable,
+    Other(String),
+}
+
+/// 链上对象(类型与所有者)
+#[derive(Debug, Clone)]
+pub struct ChainObject {
+    pub object_id: String,
+    /// "package" 或 Move 结构类型
+    pub object_type: String,
+    pub owner: ChainOwner,
+}
+
+/// 链上对象读取, 测试时可替换为模拟实现
+#[async_trait]
+pub trait ChainReader: Send + Sync {
+    /// 对象不存在时返回 None
+    async fn get_object(&self, object_id: &str) -> Result<Option<ChainObject>, anyhow::Error>;
+}
+
+/// 基于 SuiClient 的读取
+pub struct SuiChainReader {
+    client: SuiClient,
+}
+
+impl SuiChainReader {
+
+    pub fn new(client: SuiClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ChainReader for SuiChainReader {
+
+    async fn get_object(&self, object_id: &str) -> Result<Option<ChainObject>, anyhow::Error> {
+        let id = ObjectID::from_hex_literal(object_id).map_err(|e| anyhow!("Invalid object id:{}, {}", object_id, e))?;
+        let response = self.client.read_api()
+            .get_object_with_options(id, SuiObjectDataOptions::new().with_type().with_owner())
+            .await?;
+        let data = match response.data {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let object_type = match data.type_ {
+            Some(ObjectType::Package) => "package".to_owned(),
+            Some(object_type) => object_type.to_string(),
+            None => return Err(anyhow!("Object:{} has no type", object_id)),
+        };
+        let owner = match data.owner {
+            Some(Owner::AddressOwner(address)) => ChainOwner::Address(address.to_string()),
+            Some(Owner::ObjectOwner(address)) => ChainOwner::Object(address.to_string()),
+            Some(Owner::Shared { .. }) => ChainOwner::Shared,
+            Some(Owner::Immutable) => ChainOwner::Immutable,
+            Some(owner) => ChainOwner::Other(owner.to_string()),
+            None => return Err(anyhow!("Object:{} has no owner", object_id)),
+        };
+        Ok(Some(ChainObject { object_id: object_id.to_owned(), object_type, owner }))
+    }
+}
+
+/// 不一致项
+#[derive(Debug)]
+pub struct Mismatch {
+    pub package_id: String,
+    pub object: &'static str,
+    pub object_id: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "package {} {} {}: {}", self.package_id, self.object, self.object_id, self.reason)
+    }
+}
+
+/// 对账报告
+#[derive(Debug, Default)]
+pub struct ReconcileReport {
+    pub checked: usize,
+    pub mismatches: Vec<Mismatch>,
+    /// 映射问题(创作者/collection -> package)
+    pub mappings: Vec<String>,
+    /// 已修复的映射
+    pub repaired: Vec<String>,
+}
+
+impl fmt::Display for ReconcileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for mismatch in &self.mismatches {
+            writeln!(f, "  mismatch {}", mismatch)?;
+        }
+        for mapping in &self.mappings {
+            writeln!(f, "  mapping {}", mapping)?;
+        }
+        for repaired in &self.repaired {
+            writeln!(f, "  repaired {}", repaired)?;
+        }
+        write!(f, "{} packages checked, {} mismatches, {} mapping issues, {} repaired",
+            self.checked, self.mismatches.len(), self.mappings.len(), self.repaired.len())
+    }
+}
+
+/// 期望的链上对象
+struct Expected<'a> {
+    object: &'static str,
+    object_id: &'a str,
+    object_type: String,
+    owner: ChainOwner,
+}
+
+/// 对账子命令
+pub async fn reconcile_command<S: KVStore>(args: &[String], db: S) -> Result<(), anyhow::Error> {
+    let mut repair = false;
+    for arg in args {
+        match arg.as_str() {
+            "--repair" => repair = true,
+            _ => return Err(anyhow!("Unknown argument:{}\n{}", arg, USAGE)),
+        }
+    }
+    let provider = std::env::var("PROVIDER").expect("PROVIDER must be set");
+    let reader = SuiChainReader::new(get_client().await?);
+    let report = reconcile(&reader, &db, &provider, repair).await?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// 逐个检查存储的合约发布结果与链上对象是否一致; repair 时修复创作者和collection映射
+pub async fn reconcile<R: ChainReader, S: KVStore>(reader: &R, db: &S, provider: &str, repair: bool) -> Result<ReconcileReport, anyhow::Error> {
+    let creators = CreatorRepository::new(db.clone());
+    let coin_packages = CoinPackageRepository::new(db.clone());
+    let nft_packages = NftPackageRepository::new(db.clone());
+    let collections = CollectionRepository::new(db.clone());
+    let provider_owner = ChainOwner::Address(normalize_address(provider));
+
+    let mut report = ReconcileReport::default();
+    let mut batch = WriteBatch::new();
+
+    let coins = coin_packages.list()?;
+    for coin in &coins {
+        report.checked += 1;
+        let consistent = check_objects(reader, &coin.package_id, &coin_expectations(coin, &provider_owner), &mut report).await?;
+        let mapped = creators.coin_package_id(&coin.wallet_address)?;
+        if mapped.as_deref() == Some(coin.package_id.as_str()) {
+            continue;
+        }
+        report.mappings.push(format!("creator {} -> {:?}, expected {}", coin.wallet_address, mapped, coin.package_id));
+        // 只在映射缺失或指向没有记录的package时修复, 避免覆盖同一创作者的其他有效记录
+        let dangling = match &mapped {
+            Some(package_id) => coin_packages.find(package_id)?.is_none(),
+            None => true,
+        };
+        if repair && consistent && dangling {
+            creators.stage_coin_package_id(&mut batch, &coin.wallet_address, &coin.package_id);
+            report.repaired.push(format!("creator {} -> {}", coin.wallet_address, coin.package_id));
+        }
+    }
+
+    for nft in nft_packages.list()? {
+        report.checked += 1;
+        let consistent = check_objects(reader, &nft.package_id, &nft_expectations(&nft, &provider_owner), &mut report).await?;
+        let mapped = collections.package_id(&nft.collection_id)?;
+        if mapped.as_deref() == Some(nft.package_id.as_str()) {
+            continue;
+        }
+        report.mappings.push(format!("collection {} -> {:?}, expected {}", nft.collection_id, mapped, nft.package_id));
+        let dangling = match &mapped {
+            Some(package_id) => nft_packages.find(package_id)?.is_none(),
+            None => true,
+        };
+        if repair && consistent && dangling {
+            collections.stage_package_id(&mut batch, &nft.collection_id, &nft.package_id);
+            report.repaired.push(format!("collection {} -> {}", nft.collection_id, nft.package_id));
+        }
+    }
+
+    // 没有对应发布结果的映射只报告, 不删除
+    for (wallet_address, package_id) in creators.list()? {
+        if !coins.iter().any(|coin| coin.package_id == package_id) {
+            report.mappings.push(format!("creator {} -> {} has no coin package record", wallet_address, package_id));
+        }
+    }
+    for (collection_id, package_id) in collections.list()? {
+        if nft_packages.find(&package_id)?.is_none() {
+            report.mappings.push(format!("collection {} -> {} has no nft package record", collection_id, package_id));
+        }
+    }
+
+    if !batch.is_empty() {
+        db.write(batch)?;
+    }
+    Ok(report)
+}
+
+fn coin_expectations<'a>(coin: &'a BassinetCoinPublishedResult, provider: &ChainOwner) -> Vec<Expected<'a>> {
+    let mut expected = vec![
+        Expected { object: "package", object_id: &coin.package_id, object_type: "package".to_owned(), owner: ChainOwner::Immutable },
+        Expected { object: "AdminCap", object_id: &coin.admin_cap_id, object_type: coin.package_id.clone() + "::bassinet_coin::AdminCap", owner: provider.clone() },
+        Expected { object: "TreasuryLock", object_id: &coin.treasury_lock_id, object_type: coin.package_id.clone() + "::bassinet_coin::TreasuryLock", owner: ChainOwner::Shared },
+    ];
+    if let Some(upgrade_cap_id) = &coin.upgrade_cap_id {
+        expected.push(Expected { object: "UpgradeCap", object_id: upgrade_cap_id, object_type: "0x2::package::UpgradeCap".to_owned(), owner: provider.clone() });
+    }
+    expected
+}
+
+fn nft_expectations<'a>(nft: &'a NftPublishedResult, provider: &ChainOwner) -> Vec<Expected<'a>> {
+    let nft_type = nft.package_id.clone() + "::bassinet_nft::BassinetNFT";
+    let mut expected = vec![
+        Expected { object: "package", object_id: &nft.package_id, object_type: "package".to_owned(), owner: ChainOwner::Immutable },
+        Expected { object: "Mint", object_id: &nft.mint_id, object_type: nft.package_id.clone() + "::bassinet_nft::Mint", owner: ChainOwner::Shared },
+        Expected { object: "TransferPolicy", object_id: &nft.policy_id, object_type: format!("0x2::transfer_policy::TransferPolicy<{}>", nft_type), owner: ChainOwner::Shared },
+        Expected { object: "TransferPolicyCap", object_id: &nft.policy_cap_id, object_type: format!("0x2::transfer_policy::TransferPolicyCap<{}>", nft_type), owner: provider.clone() },
+    ];
+    if let Some(upgrade_cap_id) = &nft.upgrade_cap_id {
+        expected.push(Expected { object: "UpgradeCap", object_id: upgrade_cap_id, object_type: "0x2::package::UpgradeCap".to_owned(), owner: provider.clone() });
+    }
+    expected
+}
+
+/// 检查一组对象, 不一致项写入报告; 全部一致时返回 true
+async fn check_objects<R: ChainReader>(reader: &R, package_id: &str, expected: &[Expected<'_>], report: &mut ReconcileReport) -> Result<bool, anyhow::Error> {
+    let mut consistent = true;
+    for item in expected {
+        let reason = match reader.get_object(item.object_id).await? {
+            None => Some("not found".to_owned()),
+            Some(object) if !same_type(&object.object_type, &item.object_type) => {
+                Some(format!("type {}, expected {}", object.object_type, item.object_type))
+            }
+            Some(object) if object.owner != item.owner => {
+                Some(format!("owner {:?}, expected {:?}", object.owner, item.owner))
+            }
+            Some(_) => None,
+        };
+        if let Some(reason) = reason {
+            consistent = false;
+            report.mismatches.push(Mismatch { package_id: package_id.to_owned(), object: item.object, object_id: item.object_id.to_owned(), reason });
+        }
+    }
+    Ok(consistent)
+}
+
+/// 比较类型, 忽略地址的缩写形式
+fn same_type(actual: &str, expected: &str) -> bool {
+    if actual == expected {
+        return true
+    }
+    match (parse_sui_struct_tag(actual), parse_sui_struct_tag(expected)) {
+        (Ok(actual), Ok(expected)) => actual == expected,
+        _ => false,
+    }
+}
+
+fn normalize_address(address: &str) -> String {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    format!("0x{:0>64}", hex.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::kv_store::MemoryStore;
+
+    const PROVIDER: &str = "0x1";
+    const WALLET: &str = "0xc0ffee";
+    const PACKAGE: &str = "0xa1";
+
+    /// 模拟链上读取, 对象不在表中时视为不存在
+    #[derive(Default)]
+    struct MockChainReader {
+        objects: HashMap<String, ChainObject>,
+    }
+
+    impl MockChainReader {
+
+        fn insert(&mut self, object_id: &str, object_type: &str, owner: ChainOwner) {
+            self.objects.insert(object_id.to_owned(), ChainObject { object_id: object_id.to_owned(), object_type: object_type.to_owned(), owner });
+        }
+    }
+
+    #[async_trait]
+    impl ChainReader for MockChainReader {
+
+        async fn get_object(&self, object_id: &str) -> Result<Option<ChainObject>, anyhow::Error> {
+            Ok(self.objects.get(object_id).cloned())
+        }
+    }
+
+    fn provider_owner() -> ChainOwner {
+        ChainOwner::Address(normalize_address(PROVIDER))
+    }
+
+    fn coin() -> BassinetCoinPublishedResult {
+        BassinetCoinPublishedResult {
+            package_id: PACKAGE.to_owned(),
+            admin_cap_id: "0xa2".to_owned(),
+            treasury_lock_id: "0xa3".to_owned(),
+            upgrade_cap_id: None,
+            wallet_address: WALLET.to_owned(),
+            account: "account".to_owned(),
+            network: "testnet".to_owned(),
+            template_version: None,
+            template_variant: None,
+            template_params: None,
+            metadata: None,
+        }
+    }
+
+    /// 与 coin() 一致的链上对象
+    fn chain() -> MockChainReader {
+        let mut reader = MockChainReader::default();
+        reader.insert(PACKAGE, "package", ChainOwner::Immutable);
+        reader.insert("0xa2", "0xa1::bassinet_coin::AdminCap", provider_owner());
+        reader.insert("0xa3", "0xa1::bassinet_coin::TreasuryLock", ChainOwner::Shared);
+        reader
+    }
+
+    fn store(mapped: Option<&str>) -> MemoryStore {
+        let db = MemoryStore::new();
+        CoinPackageRepository::new(db.clone()).save(&coin()).unwrap();
+        if let Some(package_id) = mapped {
+            CreatorRepository::new(db.clone()).save_coin_package_id(WALLET, package_id).unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn consistent_records_report_nothing() {
+        let db = store(Some(PACKAGE));
+        let report = reconcile(&chain(), &db, PROVIDER, true).await.unwrap();
+        assert_eq!(report.checked, 1);
+        assert!(report.mismatches.is_empty());
+        assert!(report.mappings.is_empty());
+        assert!(report.repaired.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_object_is_reported() {
+        let db = store(Some(PACKAGE));
+        let mut reader = chain();
+        reader.objects.remove("0xa3");
+        let report = reconcile(&reader, &db, PROVIDER, false).await.unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].object, "TreasuryLock");
+        assert_eq!(report.mismatches[0].reason, "not found");
+        assert!(report.to_string().contains("mismatch package 0xa1 TreasuryLock 0xa3: not found"));
+    }
+
+    #[tokio::test]
+    async fn wrong_owner_is_reported() {
+        let db = store(Some(PACKAGE));
+        let mut reader = chain();
+        reader.insert("0xa2", "0xa1::bassinet_coin::AdminCap", ChainOwner::Address(normalize_address("0x2")));
+        let report = reconcile(&reader, &db, PROVIDER, false).await.unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].object, "AdminCap");
+        assert!(report.mismatches[0].reason.starts_with("owner "));
+    }
+
+    #[tokio::test]
+    async fn wrong_type_is_reported() {
+        let db = store(Some(PACKAGE));
+        let mut reader = chain();
+        reader.insert("0xa3", "0xa1::bassinet_coin::AdminCap", ChainOwner::Shared);
+        let report = reconcile(&reader, &db, PROVIDER, false).await.unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].object, "TreasuryLock");
+        assert_eq!(report.mismatches[0].reason, "type 0xa1::bassinet_coin::AdminCap, expected 0xa1::bassinet_coin::TreasuryLock");
+    }
+
+    #[tokio::test]
+    async fn abbreviated_addresses_match() {
+        let db = store(Some(PACKAGE));
+        let mut reader = chain();
+        reader.insert("0xa3", &format!("0x{:0>64}::bassinet_coin::TreasuryLock", "a1"), ChainOwner::Shared);
+        let report = reconcile(&reader, &db, PROVIDER, false).await.unwrap();
+        assert!(report.mismatches.is_empty());
+    }
+
+    #[tokio::test]
+    async fn dangling_mapping_is_reported_without_repair() {
+        let db = store(Some("0xdead"));
+        let report = reconcile(&chain(), &db, PROVIDER, false).await.unwrap();
+        assert_eq!(report.mappings, vec![
+            "creator 0xc0ffee -> Some(\"0xdead\"), expected 0xa1".to_owned(),
+            "creator 0xc0ffee -> 0xdead has no coin package record".to_owned(),
+        ]);
+        assert!(report.repaired.is_empty());
+        assert_eq!(CreatorRepository::new(db).coin_package_id(WALLET).unwrap().as_deref(), Some("0xdead"));
+    }
+
+    #[tokio::test]
+    async fn dangling_mapping_is_repaired() {
+        let db = store(Some("0xdead"));
+        let report = reconcile(&chain(), &db, PROVIDER, true).await.unwrap();
+        assert_eq!(report.repaired, vec!["creator 0xc0ffee -> 0xa1".to_owned()]);
+        assert_eq!(CreatorRepository::new(db.clone()).coin_package_id(WALLET).unwrap().as_deref(), Some(PACKAGE));
+        // 修复后再次对账没有问题
+        let report = reconcile(&chain(), &db, PROVIDER, true).await.unwrap();
+        assert!(report.mappings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_mapping_is_repaired() {
+        let db = store(None);
+        let report = reconcile(&chain(), &db, PROVIDER, true).await.unwrap();
+        assert_eq!(report.repaired, vec!["creator 0xc0ffee -> 0xa1".to_owned()]);
+        assert_eq!(CreatorRepository::new(db).coin_package_id(WALLET).unwrap().as_deref(), Some(PACKAGE));
+    }
+
+    #[tokio::test]
+    async fn inconsistent_package_is_not_repaired() {
+        let db = store(Some("0xdead"));
+        let mut reader = chain();
+        reader.objects.remove(PACKAGE);
+        let report = reconcile(&reader, &db, PROVIDER, true).await.unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert!(report.repaired.is_empty());
+        assert_eq!(CreatorRepository::new(db).coin_package_id(WALLET).unwrap().as_deref(), Some("0xdead"));
+    }
+
+    #[tokio::test]
+    async fn mapping_to_another_valid_package_is_not_repaired() {
+        let db = store(Some(PACKAGE));
+        let mut other = coin();
+        other.package_id = "0xb1".to_owned();
+        CoinPackageRepository::new(db.clone()).save(&other).unwrap();
+        let report = reconcile(&chain(), &db, PROVIDER, true).await.unwrap();
+        assert_eq!(report.mappings.len(), 1);
+        assert!(report.repaired.is_empty());
+        assert_eq!(CreatorRepository::new(db).coin_package_id(WALLET).unwrap().as_deref(), Some(PACKAGE));
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use serde_json::Value;
+use sui_sdk::{rpc_types::{SuiTransactionBlockResponseOptions, SuiTransactionBlockResponseQuery, TransactionFilter}, types::base_types::{ObjectID, SuiAddress}, SuiClient};
+
+use crate::{event_listening::get_client, kv_store::{KVStore, WriteBatch}, repository::{CoinPackageRepository, CollectionRepository, CreatorCollection, CreatorRepository, NftPackageRepository, PortfolioRepository, WorkflowStatus}, sui_service::{object_fields, parse_coin_published, parse_nft_published}};
+
+const USAGE: &str = "usage: bassinet-sui recover [--dry-run]";
+
+/// 交易查询分页大小
+const PAGE_SIZE: usize = 50;
+
+/// 索引重建报告
+#[derive(Debug, Default)]
+pub struct RecoveryReport {
+    pub dry_run: bool,
+    pub transactions: usize,
+    pub coin_packages: Vec<String>,
+    pub nft_packages: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+impl fmt::Display for RecoveryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for coin in &self.coin_packages {
+            writeln!(f, "  coin {}", coin)?;
+        }
+        for nft in &self.nft_packages {
+            writeln!(f, "  nft {}", nft)?;
+        }
+        for skipped in &self.skipped {
+            writeln!(f, "  skipped {}", skipped)?;
+        }
+        let mode = if self.dry_run { " (dry run)" } else { "" };
+        write!(f, "{} transactions scanned, {} coin packages, {} nft packages, {} skipped{}",
+            self.transactions, self.coin_packages.len(), self.nft_packages.len(), self.skipped.len(), mode)
+    }
+}
+
+/// 索引重建子命令
+pub async fn recover_command<S: KVStore>(args: &[String], db: S) -> Result<(), anyhow::Error> {
+    let mut dry_run = false;
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            _ => return Err(anyhow!("Unknown argument:{}\n{}", arg, USAGE)),
+        }
+    }
+    let provider = std::env::var("PROVIDER").expect("PROVIDER must be set");
+    let client = get_client().await?;
+    let report = recover(&client, &db, &provider, dry_run).await?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// 扫描平台方地址发出的合约发布交易, 重建创作者 -> 代币合约、collection -> NFT合约的映射
+pub async fn recover<S: KVStore>(client: &SuiClient, db: &S, provider: &str, dry_run: bool) -> Result<RecoveryReport, anyhow::Error> {
+    let creators = CreatorRepository::new(db.clone());
+    let coin_packages = CoinPackageRepository::new(db.clone());
+    let nft_packages = NftPackageRepository::new(db.clone());
+    let collections = CollectionRepository::new(db.clone());
+    let portfolios = PortfolioRepository::new(db.clone());
+
+    let sender = SuiAddress::from_bytes(hex::decode(provider.strip_prefix("0x").unwrap_or(provider))?)?;
+    let query = SuiTransactionBlockResponseQuery::new(
+        Some(TransactionFilter::FromAddress(sender)),
+        Some(SuiTransactionBlockResponseOptions::new().with_effects().with_object_changes()),
+    );
+
+    let mut report = RecoveryReport { dry_run, ..Default::default() };
+    let mut batch = WriteBatch::new();
+    let mut cursor = None;
+    loop {
+        // 按时间正序扫描, 同一创作者/collection多次发布时以最后一次为准
+        let page = client.read_api().query_transaction_blocks(query.clone(), cursor, Some(PAGE_SIZE), false).await?;
+        for response in page.data {
+            report.transactions += 1;
+            if response.status_ok() != Some(true) {
+                continue;
+            }
+            let object_changes = response.object_changes.unwrap_or_default();
+            if let Some(objects) = parse_coin_published(&object_changes) {
+                // TreasuryLock.creator 为创作者钱包地址
+                let fields = object_fields(client, objects.treasury_lock_id).await?;
+                let Some(creator) = fields.get("creator").and_then(|value| value.as_str()).map(|value| value.to_owned()) else {
+                    report.skipped.push(format!("{}: TreasuryLock {} has no creator", response.digest, objects.treasury_lock_id));
+                    continue;
+                };
+                // 账户公钥不在链上, 无法恢复
+                let result = objects.into_result(creator.clone(), String::new());
+                if coin_packages.find(&result.package_id)?.is_none() {
+                    coin_packages.stage_save(&mut batch, &result)?;
+                }
+                creators.stage_coin_package_id(&mut batch, &creator, &result.package_id);
+                report.coin_packages.push(format!("{} -> {}", creator, result.package_id));
+            } else if let Some(objects) = parse_nft_published(&object_changes) {
+                let fields = object_fields(client, objects.mint_id).await?;
+                let creator = fields.get("creator").and_then(|value| value.as_str()).unwrap_or("unknown").to_owned();
+                let Some(collection_id) = fields.get("collection_id").and_then(collection_id_value) else {
+                    report.skipped.push(format!("{}: Mint {} has no collection_id, not configured yet", response.digest, objects.mint_id));
+                    continue;
+                };
+                let result = objects.into_result(collection_id.clone());
+                if nft_packages.find(&result.package_id)?.is_none() {
+                    nft_packages.stage_save(&mut batch, &result)?;
+                }
+                collections.stage_package_id(&mut batch, &collection_id, &result.package_id);
+                // 创作者的collection记录缺失时补充(发布参数不在链上)
+                if creator != "unknown" && portfolios.find_collection(&collection_id)?.is_none() {
+                    let mut entry = CreatorCollection::new(&creator, &collection_id, None, None);
+                    entry.published(&result);
+                    entry.update(WorkflowStatus::Completed, None);
+                    portfolios.stage_collection(&mut batch, &entry)?;
+                }
+                report.nft_packages.push(format!("{} ({}) -> {}", collection_id, creator, result.package_id));
+            }
+        }
+        if !page.has_next_page {
+            break;
+        }
+        cursor = page.next_cursor;
+    }
+
+    if !dry_run && !batch.is_empty() {
+        db.write(batch)?;
+    }
+    Ok(report)
+}
+
+/// collection_id 可能为 String 或 vector<u8>
+fn collection_id_value(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Array(bytes) => {
+            let bytes: Option<Vec<u8>> = bytes.iter().map(|byte| byte.as_u64().map(|byte| byte as u8)).collect();
+            bytes.and_then(|bytes| String::from_utf8(bytes).ok())
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,255 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use sui_sdk::{rpc_types::{CheckpointId, SuiEvent, SuiTransactionBlockResponseOptions}, types::{digests::TransactionDigest, event::EventID}, SuiClient};
+
+use crate::{event_listening::{get_client, listening_events}, events_mq::{event_exists, event_type, replay_events, Config, EventEnvelope}, kv_store::KVStore};
+
+const USAGE: &str = "usage: bassinet-sui replay <AccountBound|DigitalServiceOpened|NftLaunched> [--from <bound>] [--to <bound>] [--ignore-dedup] [--dry-run]
+  bound: event:<tx_digest>:<event_seq> | checkpoint:<sequence_number> | timestamp:<ms>";
+
+/// 重放区间边界
+#[derive(Debug)]
+pub enum ReplayBound {
+    Event(EventID),
+    Checkpoint(u64),
+    Timestamp(u64),
+}
+
+impl ReplayBound {
+
+    pub fn parse(value: &str) -> Result<Self, anyhow::Error> {
+        let (kind, value) = value.split_once(':').ok_or(anyhow!("Invalid bound:{}", value))?;
+        match kind {
+            "event" => {
+                let event_id = EventID::try_from(value.to_owned()).map_err(|e| anyhow!("Invalid event id:{}, {}", value, e))?;
+                Ok(ReplayBound::Event(event_id))
+            }
+            "checkpoint" => Ok(ReplayBound::Checkpoint(value.parse::<u64>()?)),
+            "timestamp" => Ok(ReplayBound::Timestamp(value.parse::<u64>()?)),
+            _ => Err(anyhow!("Invalid bound:{}:{}", kind, value)),
+        }
+    }
+}
+
+/// 重放参数
+#[derive(Debug)]
+pub struct ReplayOptions {
+    pub event_type: String,
+    pub from: Option<ReplayBound>,
+    pub to: Option<ReplayBound>,
+    pub ignore_dedup: bool,
+    pub dry_run: bool,
+}
+
+impl ReplayOptions {
+
+    pub fn parse(args: &[String]) -> Result<Self, anyhow::Error> {
+        let mut iter = args.iter();
+        let event_type = iter.next().ok_or(anyhow!(USAGE))?.to_owned();
+        if !["AccountBound", "DigitalServiceOpened", "NftLaunched"].contains(&event_type.as_str()) {
+            return Err(anyhow!("Unknown event type:{}\n{}", event_type, USAGE))
+        }
+        let mut options = ReplayOptions { event_type, from: None, to: None, ignore_dedup: false, dry_run: false };
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--from" => options.from = Some(ReplayBound::parse(iter.next().ok_or(anyhow!(USAGE))?)?),
+                "--to" => options.to = Some(ReplayBound::parse(iter.next().ok_or(anyhow!(USAGE))?)?),
+                "--ignore-dedup" => options.ignore_dedup = true,
+                "--dry-run" => options.dry_run = true,
+                _ => return Err(anyhow!("Unknown argument:{}\n{}", arg, USAGE)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// 重放事件到bassinet.topic
+pub async fn replay<S: KVStore>(args: &[String], package_id: &str, db: Option<S>, config: Arc<Config>) -> Result<(), anyhow::Error> {
+    let options = ReplayOptions::parse(args)?;
+    if !options.ignore_dedup && db.is_none() {
+        return Err(anyhow!("Store is required unless --ignore-dedup is set"))
+    }
+    let client = get_client().await?;
+    let events = collect_events(&client, package_id, &options).await?;
+    tracing::info!("重放{}条{}事件", events.len(), options.event_type);
+
+    if options.dry_run {
+        for event in &events {
+            let marked = match db.as_ref() {
+                Some(db) => event_exists(event, db)?,
+                None => false,
+            };
+            let skipped = marked && !options.ignore_dedup;
+            let event_type = event_type(event, package_id).unwrap_or_default();
+            let envelope = EventEnvelope::new(event, &event_type);
+            println!(
+                "{} routing_key:bassinet.{} marked:{} {} envelope:{}",
+                envelope.event_id,
+                event_type,
+                marked,
+                if skipped { "skip" } else { "send" },
+                serde_json::to_string(&envelope).unwrap()
+            );
+        }
+        return Ok(())
+    }
+
+    let db = if options.ignore_dedup { None } else { db };
+    replay_events(config, &events, package_id, db).await
+}
+
+/// 按区间收集事件
+async fn collect_events(client: &SuiClient, package_id: &str, options: &ReplayOptions) -> Result<Vec<SuiEvent>, anyhow::Error> {
+    // checkpoint边界按其时间戳定位, 只有时间戳相同的事件需要查询所在checkpoint
+    let start_ms = bound_timestamp(client, &options.from).await?;
+    let end_ms = bound_timestamp(client, &options.to).await?;
+    let mut cursor = match (&options.from, start_ms) {
+        (Some(ReplayBound::Event(event_id)), _) => Some(*event_id),
+        (_, Some(start_ms)) => seek_before(client, package_id, &options.event_type, start_ms).await?,
+        _ => None,
+    };
+    let mut checkpoints: HashMap<TransactionDigest, u64> = HashMap::new();
+    let mut events = Vec::new();
+    loop {
+        let page = listening_events(client, package_id, &options.event_type, cursor, Some(50), false).await?;
+        for event in page.data {
+            if !after_start(client, &event, &options.from, start_ms, &mut checkpoints).await? {
+                continue;
+            }
+            if past_end(client, &event, &options.to, end_ms, &mut checkpoints).await? {
+                return Ok(events)
+            }
+            let reached_end = matches!(&options.to, Some(ReplayBound::Event(end)) if *end == event.id);
+            events.push(event);
+            if reached_end {
+                return Ok(events)
+            }
+        }
+        if !page.has_next_page || page.next_cursor.is_none() {
+            return Ok(events)
+        }
+        cursor = page.next_cursor;
+    }
+}
+
+/// checkpoint和时间戳边界对应的时间戳(毫秒)
+async fn bound_timestamp(client: &SuiClient, bound: &Option<ReplayBound>) -> Result<Option<u64>, anyhow::Error> {
+    match bound {
+        None | Some(ReplayBound::Event(_)) => Ok(None),
+        Some(ReplayBound::Timestamp(timestamp_ms)) => Ok(Some(*timestamp_ms)),
+        Some(ReplayBound::Checkpoint(sequence_number)) => {
+            let checkpoint = client.read_api().get_checkpoint(CheckpointId::SequenceNumber(*sequence_number)).await?;
+            Ok(Some(checkpoint.timestamp_ms))
+        }
+    }
+}
+
+/// 从最新的事件向前查询, 返回时间戳早于start_ms的最后一个事件作为游标; 没有时从头查询
+async fn seek_before(client: &SuiClient, package_id: &str, event_type: &str, start_ms: u64) -> Result<Option<EventID>, anyhow::Error> {
+    let mut cursor = None;
+    loop {
+        let page = listening_events(client, package_id, event_type, cursor, Some(50), true).await?;
+        if let Some(event) = page.data.iter().find(|event| event.timestamp_ms.is_some_and(|ts| ts < start_ms)) {
+            return Ok(Some(event.id))
+        }
+        if !page.has_next_page || page.next_cursor.is_none() {
+            return Ok(None)
+        }
+        cursor = page.next_cursor;
+    }
+}
+
+/// 事件是否在起始边界之后(事件ID边界作为游标, 不包含其本身)
+async fn after_start(client: &SuiClient, event: &SuiEvent, bound: &Option<ReplayBound>, start_ms: Option<u64>, checkpoints: &mut HashMap<TransactionDigest, u64>) -> Result<bool, anyhow::Error> {
+    match bound {
+        None | Some(ReplayBound::Event(_)) => Ok(true),
+        Some(ReplayBound::Timestamp(start)) => Ok(event.timestamp_ms.map(|ts| ts >= *start).unwrap_or(true)),
+        Some(ReplayBound::Checkpoint(start)) => {
+            // checkpoint时间戳单调不减, 晚于起始checkpoint时间戳的事件一定在其之后
+            if event.timestamp_ms.zip(start_ms).is_some_and(|(ts, start_ms)| ts > start_ms) {
+                return Ok(true)
+            }
+            Ok(checkpoint(client, event, checkpoints).await? >= *start)
+        }
+    }
+}
+
+/// 事件是否超出结束边界(事件ID边界在收集时处理, 包含其本身)
+async fn past_end(client: &SuiClient, event: &SuiEvent, bound: &Option<ReplayBound>, end_ms: Option<u64>, checkpoints: &mut HashMap<TransactionDigest, u64>) -> Result<bool, anyhow::Error> {
+    match bound {
+        None | Some(ReplayBound::Event(_)) => Ok(false),
+        Some(ReplayBound::Timestamp(end)) => Ok(event.timestamp_ms.map(|ts| ts > *end).unwrap_or(false)),
+        Some(ReplayBound::Checkpoint(end)) => {
+            // 早于结束checkpoint时间戳的事件一定不在其之后
+            if event.timestamp_ms.zip(end_ms).is_some_and(|(ts, end_ms)| ts < end_ms) {
+                return Ok(false)
+            }
+            Ok(checkpoint(client, event, checkpoints).await? > *end)
+        }
+    }
+}
+
+/// 查询事件所在交易的checkpoint
+async fn checkpoint(client: &SuiClient, event: &SuiEvent, checkpoints: &mut HashMap<TransactionDigest, u64>) -> Result<u64, anyhow::Error> {
+    if let Some(checkpoint) = checkpoints.get(&event.id.tx_digest) {
+        return Ok(*checkpoint)
+    }
+    let response = client.read_api()
+        .get_transaction_with_options(event.id.tx_digest, SuiTransactionBlockResponseOptions::new())
+        .await?;
+    let checkpoint = response.checkpoint.ok_or(anyhow!("Transaction {} not checkpointed", event.id.tx_digest))?;
+    checkpoints.insert(event.id.tx_digest, checkpoint);
+    Ok(checkpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_bounds() {
+        let digest = TransactionDigest::new([1; 32]);
+        let bound = ReplayBound::parse(&format!("event:{}:3", digest)).unwrap();
+        assert!(matches!(bound, ReplayBound::Event(event_id) if event_id.tx_digest == digest && event_id.event_seq == 3));
+        assert!(matches!(ReplayBound::parse("checkpoint:120").unwrap(), ReplayBound::Checkpoint(120)));
+        assert!(matches!(ReplayBound::parse("timestamp:1700000000000").unwrap(), ReplayBound::Timestamp(1_700_000_000_000)));
+    }
+
+    #[test]
+    fn rejects_invalid_bounds() {
+        for value in ["120", "checkpoint:", "checkpoint:-1", "timestamp:soon", "block:1", "event:notadigest:1", "event:"] {
+            assert!(ReplayBound::parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = ReplayOptions::parse(&args(&["NftLaunched", "--from", "checkpoint:10", "--to", "timestamp:20", "--ignore-dedup", "--dry-run"])).unwrap();
+        assert_eq!(options.event_type, "NftLaunched");
+        assert!(matches!(options.from, Some(ReplayBound::Checkpoint(10))));
+        assert!(matches!(options.to, Some(ReplayBound::Timestamp(20))));
+        assert!(options.ignore_dedup && options.dry_run);
+
+        let options = ReplayOptions::parse(&args(&["AccountBound"])).unwrap();
+        assert!(options.from.is_none() && options.to.is_none());
+        assert!(!options.ignore_dedup && !options.dry_run);
+    }
+
+    #[test]
+    fn rejects_invalid_options() {
+        for values in [
+            &[][..],
+            &["Unknown"][..],
+            &["NftLaunched", "--from"][..],
+            &["NftLaunched", "--to", "checkpoint:x"][..],
+            &["NftLaunched", "--limit", "10"][..],
+        ] {
+            assert!(ReplayOptions::parse(&args(values)).is_err(), "{:?}", values);
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{events_mq::EventEnvelope, kv_store::{now_millis, Column, KVStore, StoreError, WriteBatch}, sui_service::{BassinetCoinPublishedResult, NftPublishedResult}};
+
+fn decode<T: DeserializeOwned>(key: &str, value: &str) -> Result<T, StoreError> {
+    serde_json::from_str(value).map_err(|e| StoreError::InvalidRecord(key.to_owned(), e.to_string()))
+}
+
+fn encode<T: Serialize>(key: &str, value: &T) -> Result<String, StoreError> {
+    serde_json::to_string(value).map_err(|e| StoreError::InvalidRecord(key.to_owned(), e.to_string()))
+}
+
+/// 创作者钱包地址 -> 代币合约package_id
+#[derive(Clone)]
+pub struct CreatorRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> CreatorRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn coin_package_id(&self, wallet_address: &str) -> Result<Option<String>, StoreError> {
+        self.store.find(Column::Creators, wallet_address)
+    }
+
+    pub fn save_coin_package_id(&self, wallet_address: &str, package_id: &str) -> Result<(), StoreError> {
+        self.store.save(Column::Creators, wallet_address, package_id)
+    }
+
+    pub fn stage_coin_package_id(&self, batch: &mut WriteBatch, wallet_address: &str, package_id: &str) {
+        batch.put(Column::Creators, wallet_address, package_id);
+    }
+
+    pub fn list(&self) -> Result<Vec<(String, String)>, StoreError> {
+        self.store.list(Column::Creators)
+    }
+}
+
+/// 代币合约发布结果
+#[derive(Clone)]
+pub struct CoinPackageRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> CoinPackageRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn find(&self, package_id: &str) -> Result<Option<BassinetCoinPublishedResult>, StoreError> {
+        self.store.find(Column::CoinPackages, package_id)?.map(|value| decode(package_id, &value)).transpose()
+    }
+
+    pub fn save(&self, result: &BassinetCoinPublishedResult) -> Result<(), StoreError> {
+        self.store.save(Column::CoinPackages, &result.package_id, &encode(&result.package_id, result)?)
+    }
+
+    pub fn stage_save(&self, batch: &mut WriteBatch, result: &BassinetCoinPublishedResult) -> Result<(), StoreError> {
+        batch.put(Column::CoinPackages, &result.package_id, &encode(&result.package_id, result)?);
+        Ok(())
+    }
+
+    pub fn list(&self) -> Result<Vec<BassinetCoinPublishedResult>, StoreError> {
+        self.store.list(Column::CoinPackages)?.iter().map(|(key, value)| decode(key, value)).collect()
+    }
+}
+
+/// NFT合约发布结果
+#[derive(Clone)]
+pub struct NftPackageRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> NftPackageRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn find(&self, package_id: &str) -> Result<Option<NftPublishedResult>, StoreError> {
+        self.store.find(Column::NftPackages, package_id)?.map(|value| decode(package_id, &value)).transpose()
+    }
+
+    pub fn save(&self, result: &NftPublishedResult) -> Result<(), StoreError> {
+        self.store.save(Column::NftPackages, &result.package_id, &encode(&result.package_id, result)?)
+    }
+
+    pub fn stage_save(&self, batch: &mut WriteBatch, result: &NftPublishedResult) -> Result<(), StoreError> {
+        batch.put(Column::NftPackages, &result.package_id, &encode(&result.package_id, result)?);
+        Ok(())
+    }
+
+    pub fn list(&self) -> Result<Vec<NftPublishedResult>, StoreError> {
+        self.store.list(Column::NftPackages)?.iter().map(|(key, value)| decode(key, value)).collect()
+    }
+}
+
+/// collection_id -> NFT合约package_id
+#[derive(Clone)]
+pub struct CollectionRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> CollectionRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn package_id(&self, collection_id: &str) -> Result<Option<String>, StoreError> {
+        self.store.find(Column::Collections, collection_id)
+    }
+
+    pub fn save_package_id(&self, collection_id: &str, package_id: &str) -> Result<(), StoreError> {
+        self.store.save(Column::Collections, collection_id, package_id)
+    }
+
+    pub fn stage_package_id(&self, batch: &mut WriteBatch, collection_id: &str, package_id: &str) {
+        batch.put(Column::Collections, collection_id, package_id);
+    }
+
+    pub fn list(&self) -> Result<Vec<(String, String)>, StoreError> {
+        self.store.list(Column::Collections)
+    }
+}
+
+/// 事件游标
+#[derive(Clone)]
+pub struct CursorRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> CursorRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn find(&self, name: &str) -> Result<Option<String>, StoreError> {
+        self.store.find(Column::Cursors, name)
+    }
+
+    pub fn save(&self, name: &str, event_id: &str) -> Result<(), StoreError> {
+        self.store.save(Column::Cursors, name, event_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkflowStatus {
+    /// 等待依赖(如创作者代币合约)
+    Deferred,
+    /// 合约已发布
+    Published,
+    /// 处理完成
+    Completed,
+    /// 处理失败
+    Failed,
+}
+
+/// 消息处理状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowRecord {
+    pub kind: String,
+    pub key: String,
+    pub status: WorkflowStatus,
+    pub error: Option<String>,
+    pub updated_at: u64,
+}
+
+impl WorkflowRecord {
+
+    pub fn new(kind: &str, key: &str, status: WorkflowStatus, error: Option<String>) -> Self {
+        Self {
+            kind: kind.to_owned(),
+            key: key.to_owned(),
+            status,
+            error,
+            updated_at: now_millis(),
+        }
+    }
+
+    pub fn store_key(&self) -> String {
+        workflow_key(&self.kind, &self.key)
+    }
+}
+
+/// 被延后的NftLaunched消息及其信封, 重新投递时沿用信封的消息属性; 未封装信封的旧消息没有信封
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeferredMessage {
+    pub content: String,
+    pub envelope: Option<EventEnvelope>,
+}
+
+impl DeferredMessage {
+
+    pub fn new(content: &str) -> Self {
+        Self {
+            content: content.to_owned(),
+            envelope: serde_json::from_str(content).ok(),
+        }
+    }
+}
+
+fn workflow_key(kind: &str, key: &str) -> String {
+    kind.to_owned() + ":" + key
+}
+
+/// 消息处理状态及延后的消息
+#[derive(Clone)]
+pub struct WorkflowRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> WorkflowRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn find(&self, kind: &str, key: &str) -> Result<Option<WorkflowRecord>, StoreError> {
+        let store_key = workflow_key(kind, key);
+        self.store.find(Column::Workflows, &store_key)?.map(|value| decode(&store_key, &value)).transpose()
+    }
+
+    pub fn save(&self, record: &WorkflowRecord) -> Result<(), StoreError> {
+        let store_key = record.store_key();
+        self.store.save(Column::Workflows, &store_key, &encode(&store_key, record)?)
+    }
+
+    pub fn stage_save(&self, batch: &mut WriteBatch, record: &WorkflowRecord) -> Result<(), StoreError> {
+        let store_key = record.store_key();
+        batch.put(Column::Workflows, &store_key, &encode(&store_key, record)?);
+        Ok(())
+    }
+
+    /// 与其他记录一起提交
+    pub fn write(&self, batch: WriteBatch) -> Result<(), StoreError> {
+        self.store.write(batch)
+    }
+
+    /// 创作者被延后的NftLaunched消息
+    pub fn deferred_nft_launched(&self, wallet_address: &str) -> Result<Vec<DeferredMessage>, StoreError> {
+        let store_key = workflow_key("deferred_nft_launched", wallet_address);
+        Ok(self.store.find(Column::Workflows, &store_key)?.map(|value| decode(&store_key, &value)).transpose()?.unwrap_or_default())
+    }
+
+    pub fn stage_deferred_nft_launched(&self, batch: &mut WriteBatch, wallet_address: &str, messages: &[DeferredMessage]) -> Result<(), StoreError> {
+        let store_key = workflow_key("deferred_nft_launched", wallet_address);
+        if messages.is_empty() {
+            batch.delete(Column::Workflows, &store_key);
+        } else {
+            batch.put(Column::Workflows, &store_key, &encode(&store_key, &messages)?);
+        }
+        Ok(())
+    }
+}
+
+/// 编译缓存记录(模块为base64)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildCacheEntry {
+    pub modules: Vec<String>,
+    pub dependencies: Vec<String>,
+    pub template_version: String,
+    pub created_at: u64,
+}
+
+/// 合约包源码哈希 -> 编译结果
+#[derive(Clone)]
+pub struct BuildCacheRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> BuildCacheRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn find(&self, key: &str) -> Result<Option<BuildCacheEntry>, StoreError> {
+        self.store.find(Column::BuildCache, key)?.map(|value| decode(key, &value)).transpose()
+    }
+
+    pub fn save(&self, key: &str, entry: &BuildCacheEntry) -> Result<(), StoreError> {
+        self.store.save(Column::BuildCache, key, &encode(key, entry)?)
+    }
+}
+
+/// NFT发布参数
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct LaunchParams {
+    pub limit: u64,
+    pub rewards_quantity: u64,
+    pub minting_price: u64,
+}
+
+/// 创作者的NFT collection及其合约
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorCollection {
+    pub wallet_address: String,
+    pub collection_id: String,
+    pub package_id: Option<String>,
+    pub mint_id: Option<String>,
+    pub policy_id: Option<String>,
+    /// 从链上恢复的记录没有发布参数
+    pub launch: Option<LaunchParams>,
+    /// 发布时使用的模板变体(id@version)
+    pub template_variant: Option<String>,
+    pub status: WorkflowStatus,
+    pub error: Option<String>,
+    /// 铸造授权被撤销的时间
+    #[serde(default)]
+    pub revoked_at: Option<u64>,
+    pub updated_at: u64,
+}
+
+impl CreatorCollection {
+
+    pub fn new(wallet_address: &str, collection_id: &str, launch: Option<LaunchParams>, template_variant: Option<String>) -> Self {
+        Self {
+            wallet_address: wallet_address.to_owned(),
+            collection_id: collection_id.to_owned(),
+            package_id: None,
+            mint_id: None,
+            policy_id: None,
+            launch,
+            template_variant,
+            status: WorkflowStatus::Deferred,
+            error: None,
+            revoked_at: None,
+            updated_at: now_millis(),
+        }
+    }
+
+    /// 记录NFT合约发布结果
+    pub fn published(&mut self, result: &NftPublishedResult) {
+        self.package_id = Some(result.package_id.clone());
+        self.mint_id = Some(result.mint_id.clone());
+        self.policy_id = Some(result.policy_id.clone());
+        if self.template_variant.is_none() {
+            self.template_variant = result.template_variant.clone();
+        }
+    }
+
+    pub fn update(&mut self, status: WorkflowStatus, error: Option<String>) {
+        self.status = status;
+        self.error = error;
+        self.updated_at = now_millis();
+    }
+
+    pub fn store_key(&self) -> String {
+        creator_collection_key(&self.wallet_address, &self.collection_id)
+    }
+}
+
+fn creator_collection_key(wallet_address: &str, collection_id: &str) -> String {
+    wallet_address.to_owned() + ":" + collection_id
+}
+
+/// 创作者名下的代币合约和全部NFT collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorPortfolio {
+    pub wallet_address: String,
+    pub coin: Option<BassinetCoinPublishedResult>,
+    pub collections: Vec<CreatorCollection>,
+}
+
+/// 创作者 -> NFT collection, 以及 collection -> 创作者的反向索引
+#[derive(Clone)]
+pub struct PortfolioRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> PortfolioRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    /// collection所属的创作者
+    pub fn creator(&self, collection_id: &str) -> Result<Option<String>, StoreError> {
+        self.store.find(Column::CollectionCreators, collection_id)
+    }
+
+    pub fn find_collection(&self, collection_id: &str) -> Result<Option<CreatorCollection>, StoreError> {
+        let Some(wallet_address) = self.creator(collection_id)? else {
+            return Ok(None)
+        };
+        let store_key = creator_collection_key(&wallet_address, collection_id);
+        self.store.find(Column::CreatorCollections, &store_key)?.map(|value| decode(&store_key, &value)).transpose()
+    }
+
+    /// 创作者的全部collection(按collection_id排序)
+    pub fn collections(&self, wallet_address: &str) -> Result<Vec<CreatorCollection>, StoreError> {
+        let prefix = creator_collection_key(wallet_address, "");
+        self.store.list_prefix(Column::CreatorCollections, &prefix)?.iter().map(|(key, value)| decode(key, value)).collect()
+    }
+
+    pub fn stage_collection(&self, batch: &mut WriteBatch, collection: &CreatorCollection) -> Result<(), StoreError> {
+        let store_key = collection.store_key();
+        batch.put(Column::CreatorCollections, &store_key, &encode(&store_key, collection)?);
+        batch.put(Column::CollectionCreators, &collection.collection_id, &collection.wallet_address);
+        Ok(())
+    }
+
+    /// 创作者的代币合约和全部collection, 都没有时返回None
+    pub fn portfolio(&self, wallet_address: &str) -> Result<Option<CreatorPortfolio>, StoreError> {
+        let coin = match CreatorRepository::new(self.store.clone()).coin_package_id(wallet_address)? {
+            Some(package_id) => CoinPackageRepository::new(self.store.clone()).find(&package_id)?,
+            None => None,
+        };
+        let collections = self.collections(wallet_address)?;
+        if coin.is_none() && collections.is_empty() {
+            return Ok(None)
+        }
+        Ok(Some(CreatorPortfolio { wallet_address: wallet_address.to_owned(), coin, collections }))
+    }
+
+    /// 有代币合约或collection的全部创作者
+    pub fn creators(&self) -> Result<Vec<String>, StoreError> {
+        let mut creators: Vec<String> = self.store.list(Column::Creators)?.into_iter().map(|(wallet_address, _)| wallet_address).collect();
+        creators.extend(self.store.list(Column::CollectionCreators)?.into_iter().map(|(_, wallet_address)| wallet_address));
+        creators.sort();
+        creators.dedup();
+        Ok(creators)
+    }
+}
+
+/// 授权操作记录(审计)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationRecord {
+    pub id: String,
+    /// revoke_collection, reauthorize_collection, authorize_app, revoke_app
+    pub kind: String,
+    pub wallet_address: String,
+    /// collection_id 或 app对象ID
+    pub target: String,
+    /// 操作参数
+    pub params: Value,
+    /// 请求来源(如 mq 消息的 request_id)
+    pub source: String,
+    pub status: WorkflowStatus,
+    pub tx_digest: Option<String>,
+    pub error: Option<String>,
+    pub created_at: u64,
+}
+
+impl OperationRecord {
+
+    pub fn new(kind: &str, wallet_address: &str, target: &str, params: Value, source: &str) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind: kind.to_owned(),
+            wallet_address: wallet_address.to_owned(),
+            target: target.to_owned(),
+            params,
+            source: source.to_owned(),
+            status: WorkflowStatus::Deferred,
+            error: None,
+            tx_digest: None,
+            created_at: now_millis(),
+        }
+    }
+
+    /// 按创作者和时间排序的键
+    pub fn store_key(&self) -> String {
+        format!("{}:{:020}:{}", self.wallet_address, self.created_at, self.id)
+    }
+}
+
+/// 授权操作记录, 只追加
+#[derive(Clone)]
+pub struct OperationRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> OperationRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn stage_save(&self, batch: &mut WriteBatch, record: &OperationRecord) -> Result<(), StoreError> {
+        let store_key = record.store_key();
+        batch.put(Column::Operations, &store_key, &encode(&store_key, record)?);
+        Ok(())
+    }
+
+    pub fn save(&self, record: &OperationRecord) -> Result<(), StoreError> {
+        let store_key = record.store_key();
+        self.store.save(Column::Operations, &store_key, &encode(&store_key, record)?)
+    }
+
+    /// 创作者的全部操作(按时间排序)
+    pub fn list(&self, wallet_address: &str) -> Result<Vec<OperationRecord>, StoreError> {
+        let prefix = wallet_address.to_owned() + ":";
+        self.store.list_prefix(Column::Operations, &prefix)?.iter().map(|(key, value)| decode(key, value)).collect()
+    }
+}
+
+/// 平台激励领取记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarvestRecord {
+    pub package_id: String,
+    pub treasury_lock_id: String,
+    pub wallet_address: String,
+    /// 领取数量(含精度)
+    pub amount: u64,
+    pub recipient: String,
+    pub tx_digest: String,
+    pub harvested_at: u64,
+}
+
+impl HarvestRecord {
+
+    pub fn store_key(&self) -> String {
+        format!("{}:{:020}", self.package_id, self.harvested_at)
+    }
+}
+
+/// 代币合约的平台激励领取记录, 只追加
+#[derive(Clone)]
+pub struct HarvestRepository<S: KVStore> {
+    store: S,
+}
+
+impl<S: KVStore> HarvestRepository<S> {
+
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    pub fn stage_save(&self, batch: &mut WriteBatch, record: &HarvestRecord) -> Result<(), StoreError> {
+        let store_key = record.store_key();
+        batch.put(Column::Harvests, &store_key, &encode(&store_key, record)?);
+        Ok(())
+    }
+
+    /// 代币合约的全部领取记录(按时间排序)
+    pub fn list(&self, package_id: &str) -> Result<Vec<HarvestRecord>, StoreError> {
+        let prefix = package_id.to_owned() + ":";
+        self.store.list_prefix(Column::Harvests, &prefix)?.iter().map(|(key, value)| decode(key, value)).collect()
+    }
+
+    /// 代币合约累计领取数量
+    pub fn total(&self, package_id: &str) -> Result<u128, StoreError> {
+        Ok(self.list(package_id)?.iter().map(|record| record.amount as u128).sum())
+    }
+}
@@ -1,32 +1,81 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{collections::BTreeMap, path::PathBuf, str::FromStr};
 use anyhow::{anyhow, Ok};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use shared_crypto::intent::Intent;
 use sui_keys::keystore::{AccountKeystore, FileBasedKeystore};
-use sui_sdk::{rpc_types::{Coin, ObjectChange, SuiObjectData, SuiObjectDataFilter, SuiObjectDataOptions, SuiObjectResponseQuery, SuiTransactionBlockEffectsAPI, SuiTransactionBlockResponseOptions}, types::{base_types::{ObjectID, SuiAddress}, parse_sui_struct_tag, programmable_transaction_builder::ProgrammableTransactionBuilder, quorum_driver_types::ExecuteTransactionRequestType, transaction::{Argument, CallArg, Command, ObjectArg, Transaction, TransactionData}, Identifier}, SuiClientBuilder};
+use sui_sdk::{rpc_types::{Coin, ObjectChange, SuiObjectData, SuiObjectDataFilter, SuiObjectDataOptions, SuiObjectResponseQuery, SuiParsedData, SuiTransactionBlockEffectsAPI, SuiTransactionBlockResponse, SuiTransactionBlockResponseOptions}, types::{base_types::{ObjectID, SuiAddress}, parse_sui_struct_tag, programmable_transaction_builder::ProgrammableTransactionBuilder, quorum_driver_types::ExecuteTransactionRequestType, transaction::{Argument, CallArg, Command, ObjectArg, Transaction, TransactionData}, Identifier}, SuiClient, SuiClientBuilder};
 
 use digital_service::OpenDigitalServiceConfig;
 use nft_service::{NftConfigInfo, NftServiceConfig};
 
+use crate::template::source::template_version;
+
+pub mod authorization;
 pub mod digital_service;
 pub mod nft_service;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// 合约发布所在网络
+pub const NETWORK: &str = "testnet";
+
+/// NETWORK 的全节点RPC地址
+pub fn rpc_url() -> String {
+    format!("https://fullnode.{}.sui.io:443", NETWORK)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BassinetCoinPublishedResult {
     pub package_id: String,
     pub admin_cap_id: String,
     pub treasury_lock_id: String,
+    pub upgrade_cap_id: Option<String>,
     pub wallet_address: String,
     pub account: String,
+    pub network: String,
+    /// 发布时使用的模板版本
+    pub template_version: Option<String>,
+    /// 发布时使用的模板变体(id@version)
+    pub template_variant: Option<String>,
+    /// 发布时解析后的模板参数
+    pub template_params: Option<BTreeMap<String, u64>>,
+    /// 发布时的代币信息
+    pub metadata: Option<CoinMetadata>,
+}
+
+/// 代币信息, 重新生成代币合约包时使用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoinMetadata {
+    pub symbol: String,
+    pub name: String,
+    pub description: String,
+    pub icon_url: String,
+}
+
+impl CoinMetadata {
+
+    pub fn from_config(config: &OpenDigitalServiceConfig) -> Self {
+        Self {
+            symbol: config.symbol.clone(),
+            name: config.name.clone(),
+            description: config.description.clone(),
+            icon_url: config.icon_url.clone(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NftPublishedResult {
     pub collection_id: String,
     pub package_id: String,
     pub mint_id: String,
     pub policy_id: String,
     pub policy_cap_id: String,
+    pub upgrade_cap_id: Option<String>,
+    pub network: String,
+    /// 发布时使用的模板版本
+    pub template_version: Option<String>,
+    /// 发布时使用的模板变体(id@version)
+    pub template_variant: Option<String>,
 }
 
 /// 发布代币合约
@@ -98,32 +147,76 @@ pub async fn publish(config: &OpenDigitalServiceConfig, modules: Vec<Vec<u8>>, d
         .await?;
     println!("{}", transaction_response);
     let status = transaction_response.status_ok();
+    if status.is_none() || status.unwrap() == false {
+        let message = format!("{}", transaction_response.effects.unwrap().into_status());
+        return Err(anyhow!(message))
+    }
+    let object_changes = transaction_response.object_changes.unwrap_or_default();
+    let objects = parse_coin_published(&object_changes).ok_or(anyhow!("Bassinet Coin published objects not found"))?;
+    println!("package_id:{:?},admin_cap_id:{:?},treasury_lock_id:{:?}", objects.package_id.to_hex_literal(), objects.admin_cap_id.to_hex_literal(), objects.treasury_lock_id.to_hex_literal());
+    let mut result = objects.into_result(config.wallet_address.clone(), config.account.clone());
+    result.template_version = Some(template_version().to_owned());
+    result.template_variant = Some(config.template.variant.label());
+    result.template_params = Some(config.template.values.iter().map(|(name, value)| (name.to_string(), *value)).collect());
+    result.metadata = Some(CoinMetadata::from_config(config));
+    Ok(result)
+}
+
+/// 代币合约发布交易创建的对象
+#[derive(Debug, Clone)]
+pub struct CoinPublishedObjects {
+    pub package_id: ObjectID,
+    pub admin_cap_id: ObjectID,
+    pub treasury_lock_id: ObjectID,
+    pub upgrade_cap_id: Option<ObjectID>,
+}
+
+impl CoinPublishedObjects {
+
+    pub fn into_result(self, wallet_address: String, account: String) -> BassinetCoinPublishedResult {
+        BassinetCoinPublishedResult {
+            package_id: self.package_id.to_hex_literal(),
+            admin_cap_id: self.admin_cap_id.to_hex_literal(),
+            treasury_lock_id: self.treasury_lock_id.to_hex_literal(),
+            upgrade_cap_id: self.upgrade_cap_id.map(|id| id.to_hex_literal()),
+            wallet_address,
+            account,
+            network: NETWORK.to_owned(),
+            template_version: None,
+            template_variant: None,
+            template_params: None,
+            metadata: None,
+        }
+    }
+}
+
+/// 从交易的ObjectChange中解析代币合约发布结果, 不是代币合约发布交易时返回None
+pub fn parse_coin_published(object_changes: &[ObjectChange]) -> Option<CoinPublishedObjects> {
     let mut id:Option<ObjectID> =  Option::None;
     let mut admin_cap: Option<ObjectID> = Option::None;
     let mut treasury_lock: Option<ObjectID> = Option::None;
-    if status.is_some() && status.unwrap() == true {
-        if transaction_response.object_changes.is_some() {
-            for item in transaction_response.object_changes.unwrap().into_iter() {
-                match item {
-                    ObjectChange::Published{ package_id, version: _, digest: _, modules: _ } => {id = Option::Some(package_id)},
-                    ObjectChange::Created { sender:_, owner:_, object_type, object_id, version:_, digest:_ } => {
-                        if object_type.name.as_str() == "AdminCap" {
-                            admin_cap = Some(object_id);
-                        }else if object_type.name.as_str() == "TreasuryLock" {
-                            treasury_lock = Some(object_id);
-                        }
-                    }
-                    _ => {}
+    let mut upgrade_cap: Option<ObjectID> = Option::None;
+    for item in object_changes {
+        match item {
+            ObjectChange::Published{ package_id, version: _, digest: _, modules: _ } => {id = Option::Some(*package_id)},
+            ObjectChange::Created { sender:_, owner:_, object_type, object_id, version:_, digest:_ } => {
+                if object_type.name.as_str() == "AdminCap" {
+                    admin_cap = Some(*object_id);
+                }else if object_type.name.as_str() == "TreasuryLock" {
+                    treasury_lock = Some(*object_id);
+                }else if object_type.name.as_str() == "UpgradeCap" {
+                    upgrade_cap = Some(*object_id);
                 }
             }
+            _ => {}
         }
-        println!("package_id:{:?},admin_cap_id:{:?},treasury_lock_id:{:?}", id.unwrap().to_hex_literal(), admin_cap.unwrap().to_hex_literal(), treasury_lock.unwrap().to_hex_literal());
-    }else {
-        let message = format!("{}", transaction_response.effects.unwrap().into_status());
-        return Err(anyhow!(message))
     }
-    
-    Ok(BassinetCoinPublishedResult { package_id: id.unwrap().to_hex_literal(), admin_cap_id: admin_cap.unwrap().to_hex_literal(), treasury_lock_id: treasury_lock.unwrap().to_hex_literal(), wallet_address: config.wallet_address.clone(), account: config.account.clone()})
+    Some(CoinPublishedObjects {
+        package_id: id?,
+        admin_cap_id: admin_cap?,
+        treasury_lock_id: treasury_lock?,
+        upgrade_cap_id: upgrade_cap,
+    })
 }
 
 /// 发布NFT代币合约
@@ -194,41 +287,77 @@ pub async fn publish_nft(config: &NftServiceConfig, modules: Vec<Vec<u8>>, depen
         .await?;
     println!("{}", transaction_response);
     let status = transaction_response.status_ok();
+    if status.is_none() || status.unwrap() == false {
+        let message = format!("{}", transaction_response.effects.unwrap().into_status());
+        return Err(anyhow!(message))
+    }
+    let object_changes = transaction_response.object_changes.unwrap_or_default();
+    let objects = parse_nft_published(&object_changes).ok_or(anyhow!("Bassinet NFT published objects not found"))?;
+    println!("package_id:{:?}, mint_id:{:?}, policy_id:{:?}, policy_cap_id:{:?}", objects.package_id.to_hex_literal(), objects.mint_id.to_hex_literal(), objects.policy_id.to_hex_literal(), objects.policy_cap_id.to_hex_literal());
+    let mut result = objects.into_result(config.collection_id.clone());
+    result.template_version = Some(template_version().to_owned());
+    result.template_variant = Some(config.template.variant.label());
+    Ok(result)
+}
+
+/// NFT合约发布交易创建的对象
+#[derive(Debug, Clone)]
+pub struct NftPublishedObjects {
+    pub package_id: ObjectID,
+    pub mint_id: ObjectID,
+    pub policy_id: ObjectID,
+    pub policy_cap_id: ObjectID,
+    pub upgrade_cap_id: Option<ObjectID>,
+}
+
+impl NftPublishedObjects {
+
+    pub fn into_result(self, collection_id: String) -> NftPublishedResult {
+        NftPublishedResult {
+            collection_id,
+            package_id: self.package_id.to_hex_literal(),
+            mint_id: self.mint_id.to_hex_literal(),
+            policy_id: self.policy_id.to_hex_literal(),
+            policy_cap_id: self.policy_cap_id.to_hex_literal(),
+            upgrade_cap_id: self.upgrade_cap_id.map(|id| id.to_hex_literal()),
+            network: NETWORK.to_owned(),
+            template_version: None,
+            template_variant: None,
+        }
+    }
+}
+
+/// 从交易的ObjectChange中解析NFT合约发布结果, 不是NFT合约发布交易时返回None
+pub fn parse_nft_published(object_changes: &[ObjectChange]) -> Option<NftPublishedObjects> {
     let mut id: Option<ObjectID> =  Option::None;
     let mut mint_id: Option<ObjectID> = Option::None;
     let mut policy_id: Option<ObjectID> = Option::None;
     let mut policy_cap_id: Option<ObjectID> = Option::None;
-    if status.is_some() && status.unwrap() == true {
-        if transaction_response.object_changes.is_some() {
-            for item in transaction_response.object_changes.unwrap().into_iter() {
-                match item {
-                    ObjectChange::Published{ package_id, version: _, digest: _, modules: _ } => {id = Option::Some(package_id)},
-                    ObjectChange::Created { sender:_, owner:_, object_type, object_id, version:_, digest:_ } => {
-                        if object_type.name.as_str() == "Mint" {
-                            mint_id = Some(object_id);
-                        }else if object_type.name.as_str() == "TransferPolicyCap" {
-                            policy_cap_id = Some(object_id);
-                        }else if object_type.name.as_str() == "TransferPolicy" {
-                            policy_id = Some(object_id);
-                        }
-                    }
-                    _ => {}
+    let mut upgrade_cap_id: Option<ObjectID> = Option::None;
+    for item in object_changes {
+        match item {
+            ObjectChange::Published{ package_id, version: _, digest: _, modules: _ } => {id = Option::Some(*package_id)},
+            ObjectChange::Created { sender:_, owner:_, object_type, object_id, version:_, digest:_ } => {
+                if object_type.name.as_str() == "Mint" {
+                    mint_id = Some(*object_id);
+                }else if object_type.name.as_str() == "TransferPolicyCap" {
+                    policy_cap_id = Some(*object_id);
+                }else if object_type.name.as_str() == "TransferPolicy" {
+                    policy_id = Some(*object_id);
+                }else if object_type.name.as_str() == "UpgradeCap" {
+                    upgrade_cap_id = Some(*object_id);
                 }
             }
+            _ => {}
         }
-        println!("package_id:{:?}, mint_id:{:?}, policy_id:{:?}, policy_cap_id:{:?}", id.unwrap().to_hex_literal(), mint_id.unwrap().to_hex_literal(), policy_id.unwrap().to_hex_literal(), policy_cap_id.unwrap().to_hex_literal());
-    }else {
-        let message = format!("{}", transaction_response.effects.unwrap().into_status());
-        return Err(anyhow!(message))
     }
-    let result = NftPublishedResult {
-        collection_id: config.collection_id.clone(),
-        package_id: id.unwrap().to_hex_literal(),
-        mint_id: mint_id.unwrap().to_hex_literal(),
-        policy_id: policy_id.unwrap().to_hex_literal(),
-        policy_cap_id: policy_cap_id.unwrap().to_hex_literal(),
-    };
-    Ok(result)
+    Some(NftPublishedObjects {
+        package_id: id?,
+        mint_id: mint_id?,
+        policy_id: policy_id?,
+        policy_cap_id: policy_cap_id?,
+        upgrade_cap_id,
+    })
 }
 
 /// 初始配置NFT合约
@@ -401,6 +530,67 @@ pub async fn init_config_nft(config: &NftServiceConfig, nft_config: &NftConfigIn
     Ok(())
 }
 
+/// 以平台方地址签名并执行交易, 返回交易digest
+pub async fn execute_transaction(provider: &str, ptb: ProgrammableTransactionBuilder, gas_budget: u64, key_store_path: &str) -> Result<String, anyhow::Error> {
+    let transaction_response = execute_transaction_block(provider, ptb, gas_budget, key_store_path).await?;
+    Ok(transaction_response.digest.to_string())
+}
+
+/// 以平台方地址签名并执行交易, 返回完整的交易结果(含余额变化)
+pub async fn execute_transaction_block(provider: &str, ptb: ProgrammableTransactionBuilder, gas_budget: u64, key_store_path: &str) -> Result<SuiTransactionBlockResponse, anyhow::Error> {
+    let sui_test = SuiClientBuilder::default()
+        .build("https://fullnode.testnet.sui.io:443")
+        .await?;
+    let sender = SuiAddress::from_bytes(hex::decode(provider.strip_prefix("0x").unwrap_or(provider))?)?;
+
+    let coins = sui_test
+        .coin_read_api()
+        .get_coins(sender, None, None, None)
+        .await?;
+    let gas_coin = coins.data.into_iter().find(|coin| coin.balance >= gas_budget)
+        .ok_or(anyhow!("No gas coin with balance >= {} for {}", gas_budget, provider))?;
+
+    let gas_price = sui_test.read_api().get_reference_gas_price().await?;
+    let tx_data = TransactionData::new_programmable(
+        sender,
+        vec![gas_coin.object_ref()],
+        ptb.finish(),
+        gas_budget,
+        gas_price,
+    );
+
+    let keystore = FileBasedKeystore::new(&PathBuf::from_str(key_store_path).unwrap())?;
+    let signature = keystore.sign_secure(&sender, &tx_data, Intent::sui_transaction())?;
+
+    let transaction_response = sui_test
+        .quorum_driver_api()
+        .execute_transaction_block(
+            Transaction::from_data(tx_data, vec![signature]),
+            SuiTransactionBlockResponseOptions::full_content(),
+            Some(ExecuteTransactionRequestType::WaitForLocalExecution),
+        )
+        .await?;
+    if transaction_response.status_ok() != Some(true) {
+        let message = match transaction_response.effects {
+            Some(effects) => format!("{}", effects.into_status()),
+            None => format!("transaction {} has no effects", transaction_response.digest),
+        };
+        return Err(anyhow!(message))
+    }
+    Ok(transaction_response)
+}
+
+/// 对象的Move字段
+pub async fn object_fields(client: &SuiClient, object_id: ObjectID) -> Result<Value, anyhow::Error> {
+    let response = client.read_api()
+        .get_object_with_options(object_id, SuiObjectDataOptions::new().with_content())
+        .await?;
+    match response.data.and_then(|data| data.content) {
+        Some(SuiParsedData::MoveObject(object)) => Ok(object.fields.to_json_value()),
+        _ => Err(anyhow!("No Object content,ObjectID={}", object_id)),
+    }
+}
+
 /// 获取指定类型的Object
 pub async fn get_owned_object(object_type: String, address: SuiAddress, package_id: ObjectID, module: String) -> Result<SuiObjectData, anyhow::Error> {
     let sui_test = SuiClientBuilder::default()
@@ -0,0 +1,300 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sui_sdk::{rpc_types::SuiMoveVisibility, types::{base_types::ObjectID, object::Owner, programmable_transaction_builder::ProgrammableTransactionBuilder, transaction::{Argument, CallArg, Command, ObjectArg}, Identifier}};
+
+use crate::event_listening::get_client;
+
+use super::{execute_transaction, get_object, nft_service::NftConfigInfo, BassinetCoinPublishedResult, NftPublishedResult};
+
+/// NFT合约模块
+const NFT_MODULE: &str = "bassinet";
+
+/// 授权交易的gas预算
+const GAS_BUDGET: u64 = 100_000_000;
+
+/// 参数签名中匹配任意对象可变引用的写法
+const ANY_MUT_OBJECT: &str = "&mut *";
+
+/// 被授权的应用: 应用包需提供 authorize(&AdminCap, &mut App, String, u64, u64)
+/// 和 revoke(&AdminCap, &mut App), 在内部调用 bassinet_coin::authorize_app<T>/revoke_auth<T>;
+/// 发送交易前按链上的函数签名检查
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppTarget {
+    pub package_id: String,
+    pub module: String,
+    /// 持有 MintAppCap 的应用对象
+    pub object_id: String,
+}
+
+/// 授权操作命令
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AuthorizationCommand {
+    /// 撤销collection的铸造授权
+    RevokeCollection {
+        collection_id: String,
+    },
+    /// 按新参数重新授权collection
+    ReauthorizeCollection {
+        collection_id: String,
+        limit: u64,
+        rewards_quantity: u64,
+        minting_price: u64,
+    },
+    /// 授权其他应用按创作者的TreasuryLock挖掘激励
+    AuthorizeApp {
+        app: AppTarget,
+        app_name: String,
+        rewards_quantity: u64,
+        minting_limit: u64,
+    },
+    /// 撤销应用的授权
+    RevokeApp {
+        app: AppTarget,
+    },
+}
+
+impl AuthorizationCommand {
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AuthorizationCommand::RevokeCollection { .. } => "revoke_collection",
+            AuthorizationCommand::ReauthorizeCollection { .. } => "reauthorize_collection",
+            AuthorizationCommand::AuthorizeApp { .. } => "authorize_app",
+            AuthorizationCommand::RevokeApp { .. } => "revoke_app",
+        }
+    }
+
+    /// 操作对象: collection_id 或应用对象ID
+    pub fn target(&self) -> &str {
+        match self {
+            AuthorizationCommand::RevokeCollection { collection_id } => collection_id,
+            AuthorizationCommand::ReauthorizeCollection { collection_id, .. } => collection_id,
+            AuthorizationCommand::AuthorizeApp { app, .. } => &app.object_id,
+            AuthorizationCommand::RevokeApp { app } => &app.object_id,
+        }
+    }
+}
+
+/// 撤销collection的铸造授权, 返回交易digest
+/// NFT合约需提供 bassinet::revoke(admin_cap, mint), 在内部调用 bassinet_coin::revoke_auth<T>; 没有时不发送交易
+pub async fn revoke_collection(provider: &str, coin: &BassinetCoinPublishedResult, nft: &NftPublishedResult, key_store_path: &str) -> Result<String, anyhow::Error> {
+    verify_nft_revoke(coin, nft).await?;
+    let mut ptb = ProgrammableTransactionBuilder::new();
+    let admin_cap = ptb.input(object_arg(&coin.admin_cap_id, true).await?)?;
+    let mint = ptb.input(object_arg(&nft.mint_id, true).await?)?;
+    ptb.command(move_call(&nft.package_id, NFT_MODULE, "revoke", vec![admin_cap, mint])?);
+    execute_transaction(provider, ptb, GAS_BUDGET, key_store_path).await
+}
+
+/// 按新参数重新授权collection; revoke_first 时在同一交易中先撤销原授权
+pub async fn reauthorize_collection(provider: &str, coin: &BassinetCoinPublishedResult, nft: &NftPublishedResult, nft_config: &NftConfigInfo, revoke_first: bool, key_store_path: &str) -> Result<String, anyhow::Error> {
+    if revoke_first {
+        verify_nft_revoke(coin, nft).await?;
+    }
+    let mut ptb = ProgrammableTransactionBuilder::new();
+    let admin_cap = ptb.input(object_arg(&coin.admin_cap_id, true).await?)?;
+    let mint = ptb.input(object_arg(&nft.mint_id, true).await?)?;
+    if revoke_first {
+        ptb.command(move_call(&nft.package_id, NFT_MODULE, "revoke", vec![admin_cap, mint])?);
+    }
+    // 参数与 init_config_nft 中的 bassinet::authorize 一致
+    let policy = ptb.input(object_arg(&nft.policy_id, true).await?)?;
+    let policy_cap = ptb.input(object_arg(&nft.policy_cap_id, true).await?)?;
+    let app_name = ptb.pure("Bassinet")?;
+    let description = ptb.pure(&nft_config.description)?;
+    let collection_id = ptb.pure(&nft_config.collection_id)?;
+    let collection_url = ptb.pure(&nft_config.collection_url)?;
+    let limit = ptb.pure(nft_config.limit)?;
+    let rewards_quantity = ptb.pure(nft_config.rewards_quantity)?;
+    let minting_price = ptb.pure(nft_config.minting_price)?;
+    ptb.command(move_call(&nft.package_id, NFT_MODULE, "authorize", vec![
+        admin_cap, mint, policy, policy_cap, app_name, description, collection_id, collection_url, limit, rewards_quantity, minting_price,
+    ])?);
+    execute_transaction(provider, ptb, GAS_BUDGET, key_store_path).await
+}
+
+/// 授权应用按创作者的TreasuryLock挖掘激励
+pub async fn authorize_app(provider: &str, coin: &BassinetCoinPublishedResult, app: &AppTarget, app_name: &str, rewards_quantity: u64, minting_limit: u64, key_store_path: &str) -> Result<String, anyhow::Error> {
+    let expected = [admin_cap_param(coin), ANY_MUT_OBJECT.to_owned(), struct_type("0x1", "string", "String"), "u64".to_owned(), "u64".to_owned()];
+    verify_function(&app.package_id, &app.module, "authorize", &expected).await?;
+    let mut ptb = ProgrammableTransactionBuilder::new();
+    let admin_cap = ptb.input(object_arg(&coin.admin_cap_id, true).await?)?;
+    let app_object = ptb.input(object_arg(&app.object_id, true).await?)?;
+    let app_name = ptb.pure(app_name)?;
+    let rewards_quantity = ptb.pure(rewards_quantity)?;
+    let minting_limit = ptb.pure(minting_limit)?;
+    ptb.command(move_call(&app.package_id, &app.module, "authorize", vec![admin_cap, app_object, app_name, rewards_quantity, minting_limit])?);
+    execute_transaction(provider, ptb, GAS_BUDGET, key_store_path).await
+}
+
+/// 撤销应用的授权
+pub async fn revoke_app(provider: &str, coin: &BassinetCoinPublishedResult, app: &AppTarget, key_store_path: &str) -> Result<String, anyhow::Error> {
+    verify_function(&app.package_id, &app.module, "revoke", &[admin_cap_param(coin), ANY_MUT_OBJECT.to_owned()]).await?;
+    let mut ptb = ProgrammableTransactionBuilder::new();
+    let admin_cap = ptb.input(object_arg(&coin.admin_cap_id, true).await?)?;
+    let app_object = ptb.input(object_arg(&app.object_id, true).await?)?;
+    ptb.command(move_call(&app.package_id, &app.module, "revoke", vec![admin_cap, app_object])?);
+    execute_transaction(provider, ptb, GAS_BUDGET, key_store_path).await
+}
+
+/// NFT合约的 bassinet::revoke(&AdminCap, &mut Mint)
+async fn verify_nft_revoke(coin: &BassinetCoinPublishedResult, nft: &NftPublishedResult) -> Result<(), anyhow::Error> {
+    verify_function(&nft.package_id, NFT_MODULE, "revoke", &[admin_cap_param(coin), ANY_MUT_OBJECT.to_owned()]).await
+        .map_err(|e| anyhow!("collection revocation is not supported by nft package {}: {}", nft.package_id, e))
+}
+
+/// 创作者代币合约的 &AdminCap 参数
+fn admin_cap_param(coin: &BassinetCoinPublishedResult) -> String {
+    format!("&{}", struct_type(&coin.package_id, "bassinet_coin", "AdminCap"))
+}
+
+/// 发送交易前按链上的函数签名检查调用, 避免为不存在或签名不同的函数花费gas
+async fn verify_function(package_id: &str, module: &str, function: &str, expected: &[String]) -> Result<(), anyhow::Error> {
+    let package = ObjectID::from_hex_literal(package_id).map_err(|e| anyhow!("Invalid package id:{}, {}", package_id, e))?;
+    let client = get_client().await?;
+    let modules = client.read_api().get_normalized_move_modules_by_package(package).await?;
+    let name = format!("{}::{}::{}", package_id, module, function);
+    let normalized = modules.get(module)
+        .and_then(|normalized| normalized.exposed_functions.get(function))
+        .ok_or(anyhow!("function {} not found", name))?;
+    if !matches!(normalized.visibility, SuiMoveVisibility::Public) && !normalized.is_entry {
+        return Err(anyhow!("function {} is not callable from a transaction", name))
+    }
+    if !normalized.type_parameters.is_empty() || !normalized.return_.is_empty() {
+        return Err(anyhow!("function {} must take no type parameters and return nothing", name))
+    }
+    let parameters = normalized.parameters.iter()
+        .map(|parameter| serde_json::to_value(parameter).map(|value| render_type(&value)))
+        .collect::<Result<Vec<String>, _>>()?;
+    check_parameters(&name, &parameters, expected)
+}
+
+/// 比较参数签名, 末尾的 &mut TxContext 由运行时提供, 不计入
+fn check_parameters(name: &str, parameters: &[String], expected: &[String]) -> Result<(), anyhow::Error> {
+    let tx_context = format!("&mut {}", struct_type("0x2", "tx_context", "TxContext"));
+    let parameters = match parameters.split_last() {
+        Some((last, rest)) if *last == tx_context => rest,
+        _ => parameters,
+    };
+    let matched = parameters.len() == expected.len() && parameters.iter().zip(expected).all(|(actual, expected)| {
+        match expected.as_str() {
+            ANY_MUT_OBJECT => actual.starts_with("&mut ") && actual.contains("::"),
+            expected => actual == expected,
+        }
+    });
+    if !matched {
+        return Err(anyhow!("function {} has parameters ({}), expected ({})", name, parameters.join(", "), expected.join(", ")))
+    }
+    Ok(())
+}
+
+fn struct_type(address: &str, module: &str, name: &str) -> String {
+    format!("{}::{}::{}", normalize_address(address), module, name)
+}
+
+fn normalize_address(address: &str) -> String {
+    let hex = address.strip_prefix("0x").unwrap_or(address);
+    format!("0x{:0>64}", hex.to_lowercase())
+}
+
+/// SuiMoveNormalizedType 的JSON形式转为 Move 类型写法, 地址补齐为64位
+fn render_type(value: &Value) -> String {
+    let Some((kind, inner)) = value.as_object().and_then(|fields| fields.iter().next()) else {
+        return value.as_str().map(|primitive| primitive.to_lowercase()).unwrap_or(value.to_string())
+    };
+    match kind.as_str() {
+        "Reference" => format!("&{}", render_type(inner)),
+        "MutableReference" => format!("&mut {}", render_type(inner)),
+        "Vector" => format!("vector<{}>", render_type(inner)),
+        "TypeParameter" => format!("T{}", inner),
+        "Struct" => {
+            let inner = inner.get("inner").unwrap_or(inner);
+            let field = |name: &str| inner.get(name).and_then(|value| value.as_str()).unwrap_or_default().to_owned();
+            let type_arguments: Vec<String> = inner.get("typeArguments").and_then(|arguments| arguments.as_array())
+                .map(|arguments| arguments.iter().map(render_type).collect())
+                .unwrap_or_default();
+            let name = struct_type(&field("address"), &field("module"), &field("name"));
+            if type_arguments.is_empty() {
+                name
+            } else {
+                format!("{}<{}>", name, type_arguments.join(", "))
+            }
+        }
+        _ => value.to_string(),
+    }
+}
+
+fn move_call(package_id: &str, module: &str, function: &str, arguments: Vec<Argument>) -> Result<Command, anyhow::Error> {
+    let package = ObjectID::from_hex_literal(package_id).map_err(|e| anyhow!("Invalid package id:{}, {}", package_id, e))?;
+    let module = Identifier::new(module).map_err(|e| anyhow!(e))?;
+    let function = Identifier::new(function).map_err(|e| anyhow!(e))?;
+    Ok(Command::move_call(package, module, function, vec![], arguments))
+}
+
+/// 按对象的所有者生成交易参数(共享对象或拥有的对象)
+async fn object_arg(object_id: &str, mutable: bool) -> Result<CallArg, anyhow::Error> {
+    let id = ObjectID::from_hex_literal(object_id).map_err(|e| anyhow!("Invalid object id:{}, {}", object_id, e))?;
+    let object = get_object(id).await?;
+    let arg = match object.owner {
+        Some(Owner::Shared { initial_shared_version }) => ObjectArg::SharedObject { id, initial_shared_version, mutable },
+        Some(_) => ObjectArg::ImmOrOwnedObject(object.object_ref()),
+        None => return Err(anyhow!("Object:{} has no owner", object_id)),
+    };
+    Ok(CallArg::Object(arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn object(address: &str, module: &str, name: &str) -> Value {
+        json!({"Struct": {"address": address, "module": module, "name": name, "typeArguments": []}})
+    }
+
+    #[test]
+    fn renders_normalized_types() {
+        assert_eq!(render_type(&json!("U64")), "u64");
+        assert_eq!(render_type(&json!({"Vector": "U8"})), "vector<u8>");
+        assert_eq!(render_type(&json!({"Reference": object("0xA1", "bassinet_coin", "AdminCap")})), format!("&0x{:0>64}::bassinet_coin::AdminCap", "a1"));
+        let policy = json!({"MutableReference": {"Struct": {"address": "0x2", "module": "transfer_policy", "name": "TransferPolicy", "typeArguments": [object("0xb", "bassinet_nft", "BassinetNFT")]}}});
+        assert_eq!(render_type(&policy), format!("&mut 0x{:0>64}::transfer_policy::TransferPolicy<0x{:0>64}::bassinet_nft::BassinetNFT>", "2", "b"));
+    }
+
+    #[test]
+    fn matches_parameters_ignoring_tx_context() {
+        let admin_cap = format!("&{}", struct_type("0xa1", "bassinet_coin", "AdminCap"));
+        let mint = format!("&mut {}", struct_type("0xb1", "bassinet", "Mint"));
+        let tx_context = format!("&mut {}", struct_type("0x2", "tx_context", "TxContext"));
+        let expected = [admin_cap.clone(), ANY_MUT_OBJECT.to_owned()];
+        assert!(check_parameters("revoke", &[admin_cap.clone(), mint.clone()], &expected).is_ok());
+        assert!(check_parameters("revoke", &[admin_cap.clone(), mint.clone(), tx_context], &expected).is_ok());
+        // 对象按值传入, AdminCap 属于其他代币合约, 参数数量不同
+        assert!(check_parameters("revoke", &[admin_cap.clone(), mint.replace("&mut ", "")], &expected).is_err());
+        assert!(check_parameters("revoke", &[format!("&{}", struct_type("0xa2", "bassinet_coin", "AdminCap")), mint.clone()], &expected).is_err());
+        assert!(check_parameters("revoke", &[admin_cap, mint, "u64".to_owned()], &expected).is_err());
+        assert!(check_parameters("revoke", &["&mut u64".to_owned()], &[ANY_MUT_OBJECT.to_owned()]).is_err());
+    }
+
+    #[test]
+    fn matches_app_authorize_signature() {
+        let admin_cap = format!("&{}", struct_type("0xa1", "bassinet_coin", "AdminCap"));
+        let expected = [admin_cap.clone(), ANY_MUT_OBJECT.to_owned(), struct_type("0x1", "string", "String"), "u64".to_owned(), "u64".to_owned()];
+        let parameters = [
+            json!({"Reference": object("0xa1", "bassinet_coin", "AdminCap")}),
+            json!({"MutableReference": object("0xc1", "game", "Game")}),
+            object("0x1", "string", "String"),
+            json!("U64"),
+            json!("U64"),
+        ];
+        let rendered: Vec<String> = parameters.iter().map(render_type).collect();
+        assert!(check_parameters("authorize", &rendered, &expected).is_ok());
+        // app_name 为 vector<u8> 时拒绝
+        let mut rendered = rendered;
+        rendered[2] = render_type(&json!({"Vector": "U8"}));
+        assert!(check_parameters("authorize", &rendered, &expected).is_err());
+    }
+}
@@ -1,11 +1,9 @@
-use std::{fs, path::PathBuf, process::{Command, Stdio}};
+use std::path::PathBuf;
 
 use anyhow::{anyhow};
-use fastcrypto::encoding::{Base64, Encoding};
-use serde_json::{Value};
-use sui_sdk::types::base_types::ObjectID;
+use tracing::error;
 
-use crate::{archive::unpack, sui_service::publish, template::bassinet_coin::{bassinet_coin_move_publish_template, bassinet_coin_move_template, bassinet_coin_template}};
+use crate::{archive::unpack, move_build::{patch::{Patch, PLACEHOLDER_CREATOR, PLACEHOLDER_DESCRIPTION, PLACEHOLDER_ICON_URL, PLACEHOLDER_NAME, PLACEHOLDER_PROVIDER, PLACEHOLDER_SYMBOL}, patch_enabled, record_patch_fallback, BuiltPackage, MoveBuilder}, sui_service::publish, workspace::Workspace, template::{bassinet_coin::{bassinet_coin_move_publish_template, bassinet_coin_template}, registry::VariantParams}, validation::CoinEconomics};
 
 use super::BassinetCoinPublishedResult;
 
@@ -20,12 +18,16 @@ pub struct OpenDigitalServiceConfig {
     pub icon_url: String,
     pub creator: String,
     pub provider: String,
-    pub package_id: String
+    pub package_id: String,
+    /// 所选合约模板变体和参数
+    pub template: VariantParams,
+    /// 精度、最大发行量和激励分成
+    pub economics: CoinEconomics
 }
 
 impl OpenDigitalServiceConfig {
 
-    pub fn new(account: String, wallet_address: String, dir: PathBuf, symbol: String, name: String, description: String, icon_url: String, creator: String, provider: String, package_id: String) -> Self {
+    pub fn new(account: String, wallet_address: String, dir: PathBuf, symbol: String, name: String, description: String, icon_url: String, creator: String, provider: String, package_id: String, template: VariantParams, economics: CoinEconomics) -> Self {
         Self{
             account,
             wallet_address,
@@ -36,38 +38,23 @@ impl OpenDigitalServiceConfig {
             icon_url,
             creator,
             provider,
-            package_id
+            package_id,
+            template,
+            economics
         }
     }
 
     /// 开通
-    pub  async fn open(&mut self, key_store_path: &str) -> Result<BassinetCoinPublishedResult, anyhow::Error> {
-        // 设置当前环境(测试环境)
-        let change_env_child = Command::new("sui").arg("client").arg("switch").arg("--env").arg("testnet")
-        .stderr(Stdio::piped())
-        .spawn()
-        .unwrap();
-        let change_env_output = change_env_child.wait_with_output().unwrap();
-        if !change_env_output.status.success() {
-            let stderr = String::from_utf8(change_env_output.stderr).unwrap();
-            return Err(anyhow!(stderr));
+    pub  async fn open(&mut self, builder: &dyn MoveBuilder, key_store_path: &str) -> Result<BassinetCoinPublishedResult, anyhow::Error> {
+        // 创建合约目录(限制在CONTRACTS_DIR_PATH内)
+        let workspace = Workspace::new(&self.dir)?;
+        let dir = workspace.creator_dir(&self.wallet_address)?;
+        if dir.exists() && !dir.is_dir() {
+            return Err(anyhow!("无法创建合约目录,同名文件已存在"))
         }
+        let dir = workspace.create_dir(&dir)?;
 
-        let base_dir = self.wallet_address.clone();
-        // 创建合约目录
-        let dir = self.dir.join(base_dir.strip_prefix("0x").unwrap_or(base_dir.as_str()));
-        if dir.exists(){
-            if !dir.is_dir() {
-                return Err(anyhow!("无法创建合约目录,同名文件已存在"))
-            }
-        }else {
-            let create_dir_result = fs::create_dir(&dir);
-            if create_dir_result.is_err() {
-                return Err(anyhow!(create_dir_result.err().unwrap().to_string()))
-            }
-        }
-
-        let coin_dir = dir.join("bassinet_coin");
+        let coin_dir = workspace.child_dir(&dir, "bassinet_coin")?;
         if coin_dir.exists() {
             return Err(anyhow!("Bassinet Coin合约目录已存在"))
         }
@@ -77,56 +64,68 @@ impl OpenDigitalServiceConfig {
         if copy_result.is_err() {
             return Err(anyhow!(copy_result.err().unwrap().to_string()))
         }
-        let path = dir.join("bassinet_coin").join("sources").join("bassinet_coin.move");
-        // println!("path:{:?}", path.as_os_str());
-        let template_result = bassinet_coin_template(&path, self);
+        let template_result = bassinet_coin_template(&dir.join("bassinet_coin"), self);
         if template_result.is_err() {
             return Err(anyhow!(template_result.err().unwrap().to_string()))
         }
-        let move_result = bassinet_coin_move_template(&dir.join("bassinet_coin").join("Move.toml"), self);
-        if move_result.is_err() {
-            return Err(anyhow!(move_result.err().unwrap().to_string()))
-        }
 
-        // 编译代码
-        let child = Command::new("sui").current_dir(dir.join("bassinet_coin")).arg("move").arg("build").arg("--dump-bytecode-as-base64")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .unwrap();
-    
-        let output = child.wait_with_output().unwrap();
-        if !output.status.success() {
-            let stderr = String::from_utf8(output.stderr).unwrap();
-            return Err(anyhow!(stderr));
-        }
-        // 编译合约
-        let base64_code = String::from_utf8(output.stdout).unwrap();
-        let value : Value = serde_json::from_str(base64_code.as_str()).unwrap();
-        let modules_arr = value["modules"].as_array().unwrap();
-        let mut modules: Vec<Vec<u8>> = Vec::new();
-        for index in 0..modules_arr.len() {
-            modules.push(Base64::decode(modules_arr[index].as_str().unwrap()).unwrap());
-            // modules.push(modules_arr[index].as_str().unwrap().as_bytes().to_vec());
-        }
-        let mut object_ids = Vec::new();
-        let arr = value["dependencies"].as_array().unwrap();
-        for index in 0..arr.len() {
-            object_ids.push(ObjectID::from_hex_literal(arr[index].as_str().unwrap()).map_err(|e| anyhow!(e))?);
-        }
+        // 编译代码(patch模式下替换模板编译结果中的占位值, 失败时按创作者编译)
+        let package = if patch_enabled() {
+            match self.build_patched(builder, &workspace).await {
+                Ok(package) => package,
+                Err(err) => {
+                    let fallbacks = record_patch_fallback();
+                    error!("wallet_address:{}, patched build failed, compiling (fallback #{}):{:?}", self.wallet_address, fallbacks, err);
+                    builder.build(&dir.join("bassinet_coin")).await?
+                }
+            }
+        } else {
+            builder.build(&dir.join("bassinet_coin")).await?
+        };
 
         // 发布合约
-        let publish_result = publish(self, modules.clone(), object_ids, key_store_path).await;
+        let publish_result = publish(self, package.modules, package.dependencies, key_store_path).await;
         if publish_result.is_err() {
             return Err(anyhow!(publish_result.err().unwrap().to_string()))
         }
         let result = publish_result.unwrap();
         // 填充模板
         self.package_id = result.package_id.clone();
-        let move_publish_result = bassinet_coin_move_publish_template(&dir.join("bassinet_coin").join("Move.toml"), self);
+        let move_publish_result = bassinet_coin_move_publish_template(&dir.join("bassinet_coin"), self);
         if move_publish_result.is_err() {
             return Err(anyhow!(move_publish_result.err().unwrap().to_string()))
         }
         Ok(result)
     }
+
+    /// 用占位值渲染并编译模板(相同模板和参数只编译一次), 再替换为该创作者的地址和代币信息
+    async fn build_patched(&self, builder: &dyn MoveBuilder, workspace: &Workspace) -> Result<BuiltPackage, anyhow::Error> {
+        let base_dir = workspace.template_base_dir(&format!("coin-{}", self.template.fingerprint()))?;
+        unpack(&base_dir)?;
+        let placeholder = OpenDigitalServiceConfig::new(
+            String::new(),
+            PLACEHOLDER_CREATOR.to_owned(),
+            self.dir.clone(),
+            PLACEHOLDER_SYMBOL.to_owned(),
+            PLACEHOLDER_NAME.to_owned(),
+            PLACEHOLDER_DESCRIPTION.to_owned(),
+            PLACEHOLDER_ICON_URL.to_owned(),
+            PLACEHOLDER_CREATOR.to_owned(),
+            PLACEHOLDER_PROVIDER.to_owned(),
+            "0x0".to_owned(),
+            self.template.clone(),
+            self.economics
+        );
+        bassinet_coin_template(&base_dir.join("bassinet_coin"), &placeholder)?;
+        let compiled = builder.build(&base_dir.join("bassinet_coin")).await?;
+
+        let patch = Patch::new()
+            .address("creator", PLACEHOLDER_CREATOR, &self.creator)?
+            .address("platform_provider", PLACEHOLDER_PROVIDER, &self.provider)?
+            .byte_string("symbol", PLACEHOLDER_SYMBOL, &self.symbol)?
+            .byte_string("name", PLACEHOLDER_NAME, &self.name)?
+            .byte_string("description", PLACEHOLDER_DESCRIPTION, &self.description)?
+            .byte_string("icon_url", PLACEHOLDER_ICON_URL, &self.icon_url)?;
+        Ok(patch.apply(&compiled)?)
+    }
 }
@@ -1,13 +1,13 @@
-use std::{fs, path::PathBuf, process::{Command, Stdio}};
+use std::path::PathBuf;
 
 use anyhow::{anyhow};
-use fastcrypto::encoding::{Base64, Encoding};
-use serde_json::{Value};
+use serde_json::Value;
 use sui_sdk::types::base_types::ObjectID;
+use tracing::error;
 
-use crate::{archive::unpack_bassinet, sui_service::publish_nft, template::bassinet_nft::{bassinet_nft_move_publish_template, bassinet_nft_move_template}};
+use crate::{archive::{unpack, unpack_bassinet}, move_build::{patch::{Patch, PLACEHOLDER_COIN_PACKAGE, PLACEHOLDER_CREATOR, PLACEHOLDER_DESCRIPTION, PLACEHOLDER_ICON_URL, PLACEHOLDER_NAME, PLACEHOLDER_PROVIDER, PLACEHOLDER_SYMBOL}, patch_enabled, record_patch_fallback, BuiltPackage, MoveBuilder}, sui_service::{publish_nft, BassinetCoinPublishedResult}, workspace::{manager::ensure_coin_workspace, Workspace}, template::{bassinet_coin::{bassinet_coin_move_publish_template, bassinet_coin_template}, bassinet_nft::{bassinet_nft_move_publish_template, bassinet_nft_move_template}, registry::{select_variant, TemplateKind, VariantParams}}, validation::CoinEconomics};
 
-use super::{init_config_nft, NftPublishedResult};
+use super::{digital_service::OpenDigitalServiceConfig, init_config_nft, NftPublishedResult};
 
 #[derive(Debug)]
 pub struct NftServiceConfig {
@@ -21,7 +21,9 @@ pub struct NftServiceConfig {
     pub creator: String,
     pub provider: String,
     pub coin_package_id: String,
-    pub package_id: String
+    pub package_id: String,
+    /// 所选合约模板变体和参数
+    pub template: VariantParams
 }
 
 #[derive(Debug)]
@@ -36,7 +38,7 @@ pub struct NftConfigInfo {
 
 impl NftServiceConfig {
 
-    pub fn new(account: String, wallet_address: String, dir: PathBuf, collection_id: String, /*limit: u64, rewards_quantity: u64, minting_price: u64, */creator: String, provider: String, coin_package_id: String, package_id: String) -> Self {
+    pub fn new(account: String, wallet_address: String, dir: PathBuf, collection_id: String, /*limit: u64, rewards_quantity: u64, minting_price: u64, */creator: String, provider: String, coin_package_id: String, package_id: String, template: VariantParams) -> Self {
         Self{
             account,
             wallet_address,
@@ -48,91 +50,108 @@ impl NftServiceConfig {
             creator,
             provider,
             coin_package_id,
-            package_id
+            package_id,
+            template
         }
     }
 
     /// 发行NFT
-    pub  async fn launch(&mut self, key_store_path: &str) -> Result<NftPublishedResult, anyhow::Error> {
-        // 设置当前环境(测试环境)
-        let change_env_child = Command::new("sui").arg("client").arg("switch").arg("--env").arg("testnet")
-        .stderr(Stdio::piped())
-        .spawn()
-        .unwrap();
-        let change_env_output = change_env_child.wait_with_output().unwrap();
-        if !change_env_output.status.success() {
-            let stderr = String::from_utf8(change_env_output.stderr).unwrap();
-            return Err(anyhow!(stderr));
-        }
-
-        // 创建合约目录
-        let base_dir = self.wallet_address.clone();
-        // 创建合约目录
-        let dir = self.dir.join(base_dir.strip_prefix("0x").unwrap_or(base_dir.as_str()));
-        if !dir.exists(){
-            return Err(anyhow!("该账户合约目录不存在"))
-        }
-        let nft_dir = dir.join(&self.collection_id);
+    pub  async fn launch(&mut self, builder: &dyn MoveBuilder, coin: &BassinetCoinPublishedResult, key_store_path: &str) -> Result<NftPublishedResult, anyhow::Error> {
+        // 创建合约目录(限制在CONTRACTS_DIR_PATH内)
+        let workspace = Workspace::new(&self.dir)?;
+        // 代币合约目录不存在时(已归档或更换了机器)按存储的发布结果重新生成
+        let dir = ensure_coin_workspace(&workspace, coin, &self.provider)?;
+        let nft_dir = workspace.child_dir(&dir, &self.collection_id)?;
         if nft_dir.exists() {
             return Err(anyhow!("合约目录:{}已存在", self.collection_id));
         }
-        let create_dir_result = fs::create_dir(&nft_dir);
-        if create_dir_result.is_err() {
-            return Err(anyhow!(create_dir_result.err().unwrap().to_string()))
-        }
+        let nft_dir = workspace.create_dir(&nft_dir)?;
     
         // 复制代码
         let copy_result= unpack_bassinet(&nft_dir);
         if copy_result.is_err() {
             return Err(anyhow!(copy_result.err().unwrap().to_string()))
         }
-        let path = nft_dir.join("Move.toml");
-        let template_result = bassinet_nft_move_template(&path, self);
+        let template_result = bassinet_nft_move_template(&nft_dir, self);
         if template_result.is_err() {
             return Err(anyhow!(template_result.err().unwrap().to_string()))
         }
 
-        // 编译代码
-        let child = Command::new("sui").current_dir(&nft_dir).arg("move").arg("build").arg("--dump-bytecode-as-base64")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .unwrap();
-    
-        let output = child.wait_with_output().unwrap();
-        if !output.status.success() {
-            let stderr = String::from_utf8(output.stderr).unwrap();
-            return Err(anyhow!(stderr));
-        }
-        // 编译合约
-        let base64_code = String::from_utf8(output.stdout).unwrap();
-        let value : Value = serde_json::from_str(base64_code.as_str()).unwrap();
-        let modules_arr = value["modules"].as_array().unwrap();
-        let mut modules: Vec<Vec<u8>> = Vec::new();
-        for index in 0..modules_arr.len() {
-            modules.push(Base64::decode(modules_arr[index].as_str().unwrap()).unwrap());
-            // modules.push(modules_arr[index].as_str().unwrap().as_bytes().to_vec());
-        }
-        let mut object_ids = Vec::new();
-        let arr = value["dependencies"].as_array().unwrap();
-        for index in 0..arr.len() {
-            object_ids.push(ObjectID::from_hex_literal(arr[index].as_str().unwrap()).map_err(|e| anyhow!(e))?);
-        }
+        // 编译代码(patch模式下替换模板编译结果中的占位值, 失败时按创作者编译)
+        let package = if patch_enabled() {
+            match self.build_patched(builder, &workspace).await {
+                Ok(package) => package,
+                Err(err) => {
+                    let fallbacks = record_patch_fallback();
+                    error!("collection_id:{}, patched build failed, compiling (fallback #{}):{:?}", self.collection_id, fallbacks, err);
+                    builder.build(&nft_dir).await?
+                }
+            }
+        } else {
+            builder.build(&nft_dir).await?
+        };
 
         // 发布NFT合约
-        let publish_result = publish_nft(self, modules.clone(), object_ids, key_store_path).await;
+        let publish_result = publish_nft(self, package.modules, package.dependencies, key_store_path).await;
         if publish_result.is_err() {
             return Err(anyhow!(publish_result.err().unwrap().to_string()))
         }
         let result = publish_result.unwrap();
         self.package_id = result.package_id.clone();
-        let move_publish_result = bassinet_nft_move_publish_template(&nft_dir.join("Move.toml"), self);
+        let move_publish_result = bassinet_nft_move_publish_template(&nft_dir, self);
         if move_publish_result.is_err() {
             return Err(anyhow!(move_publish_result.err().unwrap().to_string()))
         }
         Ok(result)
     }
 
+    /// 用占位值渲染并编译模板(相同模板和参数只编译一次), 再替换为该创作者的地址和代币合约
+    async fn build_patched(&self, builder: &dyn MoveBuilder, workspace: &Workspace) -> Result<BuiltPackage, anyhow::Error> {
+        let base_dir = workspace.template_base_dir(&format!("nft-{}", self.template.fingerprint()))?;
+        // 占位的已发布代币合约包, 作为NFT合约包的本地依赖(../bassinet_coin), 只用到其接口
+        unpack(&base_dir)?;
+        let coin_template = select_variant(TemplateKind::Coin, &Value::Null)?;
+        let economics = CoinEconomics::from_params(&coin_template)?;
+        let coin = OpenDigitalServiceConfig::new(
+            String::new(),
+            PLACEHOLDER_CREATOR.to_owned(),
+            self.dir.clone(),
+            PLACEHOLDER_SYMBOL.to_owned(),
+            PLACEHOLDER_NAME.to_owned(),
+            PLACEHOLDER_DESCRIPTION.to_owned(),
+            PLACEHOLDER_ICON_URL.to_owned(),
+            PLACEHOLDER_CREATOR.to_owned(),
+            PLACEHOLDER_PROVIDER.to_owned(),
+            PLACEHOLDER_COIN_PACKAGE.to_owned(),
+            coin_template,
+            economics
+        );
+        bassinet_coin_template(&base_dir.join("bassinet_coin"), &coin)?;
+        bassinet_coin_move_publish_template(&base_dir.join("bassinet_coin"), &coin)?;
+
+        let nft_dir = workspace.create_dir(&base_dir.join("bassinet"))?;
+        unpack_bassinet(&nft_dir)?;
+        let placeholder = NftServiceConfig::new(
+            String::new(),
+            PLACEHOLDER_CREATOR.to_owned(),
+            self.dir.clone(),
+            self.collection_id.clone(),
+            PLACEHOLDER_CREATOR.to_owned(),
+            PLACEHOLDER_PROVIDER.to_owned(),
+            PLACEHOLDER_COIN_PACKAGE.to_owned(),
+            "0x0".to_owned(),
+            self.template.clone()
+        );
+        bassinet_nft_move_template(&nft_dir, &placeholder)?;
+        let compiled = builder.build(&nft_dir).await?;
+
+        let patch = Patch::new()
+            .address("creator", PLACEHOLDER_CREATOR, &self.creator)?
+            .address("platform_provider", PLACEHOLDER_PROVIDER, &self.provider)?
+            .address("bassinet_coin", PLACEHOLDER_COIN_PACKAGE, &self.coin_package_id)?;
+        Ok(patch.apply(&compiled)?)
+    }
+
     /// 初始化配置
     pub async fn init_config(&self, nft_config: &NftConfigInfo, policy_id: ObjectID, mint_id: ObjectID, key_store_path: &str) -> Result<(), anyhow::Error> {
         init_config_nft(&self, nft_config, policy_id, mint_id, key_store_path).await?;
@@ -1,2 +1,5 @@
 pub mod bassinet_coin;
-pub mod bassinet_nft;
\ No newline at end of file
+pub mod bassinet_nft;
+pub mod engine;
+pub mod registry;
+pub mod source;
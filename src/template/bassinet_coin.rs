@@ -1,70 +1,36 @@
-use std::{collections::HashMap, fs::{self}, path::{Path, PathBuf}};
+use std::{collections::HashMap, path::Path};
 
 use anyhow::anyhow;
-use text_placeholder::Template;
 
-use crate::sui_service::digital_service::OpenDigitalServiceConfig;
+use crate::{sui_service::digital_service::OpenDigitalServiceConfig, template::registry::RenderStage};
 
-/// bassinet_coin/sources/bassinet_coin.move tempalte
-pub fn bassinet_coin_template(dest_path: &PathBuf, config: &OpenDigitalServiceConfig) -> Result<(), anyhow::Error> {
-    let template_path = std::env::var("BASSINET_TEMPLATE_PATH").expect("BASSINET_TEMPLATE_PATH must be set");
-    let template_path = Path::new(&template_path);
-    let path = template_path.join("bassinet_coin_template");
-    let template_content = fs::read_to_string(&path)?;
-    let template = Template::new(&template_content);
-
-    let mut table = HashMap::new();
-    table.insert("symbol", config.symbol.as_str());
-    table.insert("name", config.name.as_str());
-    table.insert("description", config.description.as_str());
-    table.insert("icon_url", config.icon_url.as_str());
-    
-    let content = template.fill_with_hashmap(&table);
-    let result = fs::write(dest_path, content);
-    if result.is_err() {
-        return Err(anyhow!(result.err().unwrap().to_string()))
-    }
-    Ok(())
+/// 编译前渲染 bassinet_coin 合约包(按所选模板变体的文件映射)
+pub fn bassinet_coin_template(package_dir: &Path, config: &OpenDigitalServiceConfig) -> Result<(), anyhow::Error> {
+    config.template.render_stage(RenderStage::Build, package_dir, &values(config)?)
 }
 
-/// bassinet_coin/Move.toml template
-pub fn bassinet_coin_move_template(dest_path: &PathBuf, config: &OpenDigitalServiceConfig) -> Result<(), anyhow::Error> {
-    let template_path = std::env::var("BASSINET_TEMPLATE_PATH").expect("BASSINET_TEMPLATE_PATH must be set");
-    let template_path = Path::new(&template_path);
-    let path = template_path.join("bassinet_coin_move_template");
-    let template_content = fs::read_to_string(path)?;
-    let template = Template::new(&template_content);
-
-    let mut table = HashMap::new();
-    table.insert("package_id", config.package_id.as_str());
-    table.insert("creator", config.creator.as_str());
-    table.insert("provider", config.provider.as_str());
-    
-    let content = template.fill_with_hashmap(&table);
-    let result = fs::write(dest_path, content);
-    if result.is_err() {
-        return Err(anyhow!(result.err().unwrap().to_string()))
-    }
-    Ok(())
+/// 发布后渲染 bassinet_coin/Move.toml(published-at)
+pub fn bassinet_coin_move_publish_template(package_dir: &Path, config: &OpenDigitalServiceConfig) -> Result<(), anyhow::Error> {
+    config.template.render_stage(RenderStage::Publish, package_dir, &values(config)?)
 }
 
-/// bassinet_coin/Move.toml template
-pub fn bassinet_coin_move_publish_template(dest_path: &PathBuf, config: &OpenDigitalServiceConfig) -> Result<(), anyhow::Error> {
-    let template_path = std::env::var("BASSINET_TEMPLATE_PATH").expect("BASSINET_TEMPLATE_PATH must be set");
-    let template_path = Path::new(&template_path);
-    let path = template_path.join("bassinet_coin_move_publish_template");
-    let template_content = fs::read_to_string(path)?;
-    let template = Template::new(&template_content);
+fn values(config: &OpenDigitalServiceConfig) -> Result<HashMap<&str, String>, anyhow::Error> {
+    let economics = &config.economics;
+    economics.validate()?;
+    let max_supply_units = economics.max_supply_units().ok_or(anyhow!("max supply overflows u64"))?;
 
     let mut table = HashMap::new();
-    table.insert("package_id", config.package_id.as_str());
-    table.insert("creator", config.creator.as_str());
-    table.insert("provider", config.provider.as_str());
-    
-    let content = template.fill_with_hashmap(&table);
-    let result = fs::write(dest_path, content);
-    if result.is_err() {
-        return Err(anyhow!(result.err().unwrap().to_string()))
-    }
-    Ok(())
-}
\ No newline at end of file
+    table.insert("symbol", config.symbol.clone());
+    table.insert("name", config.name.clone());
+    table.insert("description", config.description.clone());
+    table.insert("icon_url", config.icon_url.clone());
+    table.insert("package_id", config.package_id.clone());
+    table.insert("creator", config.creator.clone());
+    table.insert("provider", config.provider.clone());
+    table.insert("decimals", economics.decimals.to_string());
+    table.insert("max_supply_units", max_supply_units.to_string());
+    table.insert("minter_percent", economics.minter_percent.to_string());
+    table.insert("creator_percent", economics.creator_percent.to_string());
+    table.insert("provider_percent", economics.provider_percent.to_string());
+    Ok(table)
+}
@@ -1,50 +1,22 @@
-use std::{collections::HashMap, fs::{self}, path::{Path, PathBuf}};
+use std::{collections::HashMap, path::Path};
 
-use anyhow::anyhow;
-use text_placeholder::Template;
+use crate::{sui_service::nft_service::NftServiceConfig, template::registry::RenderStage};
 
-use crate::sui_service::nft_service::NftServiceConfig;
-
-/// bassinet/Move.toml template
-pub fn bassinet_nft_move_template(dest_path: &PathBuf, config: &NftServiceConfig) -> Result<(), anyhow::Error> {
-    let template_path = std::env::var("BASSINET_TEMPLATE_PATH").expect("BASSINET_TEMPLATE_PATH must be set");
-    let template_path = Path::new(&template_path);
-    let path = template_path.join("bassinet_nft_move_template");
-    let template_content = fs::read_to_string(path)?;
-    let template = Template::new(&template_content);
-
-    let mut table = HashMap::new();
-    table.insert("package_id", config.package_id.as_str());
-    table.insert("creator", config.creator.as_str());
-    table.insert("provider", config.provider.as_str());
-    table.insert("bassinet_coin", config.coin_package_id.as_str());
-    
-    let content = template.fill_with_hashmap(&table);
-    let result = fs::write(dest_path, content);
-    if result.is_err() {
-        return Err(anyhow!(result.err().unwrap().to_string()))
-    }
-    Ok(())
+/// 编译前渲染NFT合约包(按所选模板变体的文件映射)
+pub fn bassinet_nft_move_template(package_dir: &Path, config: &NftServiceConfig) -> Result<(), anyhow::Error> {
+    config.template.render_stage(RenderStage::Build, package_dir, &values(config))
 }
 
-/// bassinet/Move.toml template
-pub fn bassinet_nft_move_publish_template(dest_path: &PathBuf, config: &NftServiceConfig) -> Result<(), anyhow::Error> {
-    let template_path = std::env::var("BASSINET_TEMPLATE_PATH").expect("BASSINET_TEMPLATE_PATH must be set");
-    let template_path = Path::new(&template_path);
-    let path = template_path.join("bassinet_nft_move_publish_template");
-    let template_content = fs::read_to_string(path)?;
-    let template = Template::new(&template_content);
+/// 发布后渲染 Move.toml(published-at)
+pub fn bassinet_nft_move_publish_template(package_dir: &Path, config: &NftServiceConfig) -> Result<(), anyhow::Error> {
+    config.template.render_stage(RenderStage::Publish, package_dir, &values(config))
+}
 
+fn values(config: &NftServiceConfig) -> HashMap<&str, String> {
     let mut table = HashMap::new();
-    table.insert("package_id", config.package_id.as_str());
-    table.insert("creator", config.creator.as_str());
-    table.insert("provider", config.provider.as_str());
-    table.insert("bassinet_coin", config.coin_package_id.as_str());
-    
-    let content = template.fill_with_hashmap(&table);
-    let result = fs::write(dest_path, content);
-    if result.is_err() {
-        return Err(anyhow!(result.err().unwrap().to_string()))
-    }
-    Ok(())
-}
\ No newline at end of file
+    table.insert("package_id", config.package_id.clone());
+    table.insert("creator", config.creator.clone());
+    table.insert("provider", config.provider.clone());
+    table.insert("bassinet_coin", config.coin_package_id.clone());
+    table
+}
@@ -0,0 +1,197 @@
+use std::{collections::HashMap, fmt::Write};
+
+use thiserror::Error;
+
+/// 占位符所在的上下文, 决定值的转义或校验方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceholderContext {
+    /// Move 字节串字面量 b"..." 内部
+    MoveByteString,
+    /// 地址(0x开头的十六进制)
+    Address,
+    /// TOML 基本字符串 "..." 内部
+    TomlString,
+    /// Move 无符号整数字面量
+    Integer,
+}
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+    #[error("unterminated placeholder at offset {0}")]
+    Unterminated(usize),
+    #[error("unknown placeholder: {0}")]
+    UnknownPlaceholder(String),
+    #[error("placeholder not filled: {0}")]
+    Unfilled(String),
+    #[error("invalid value for {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+/// 渲染模板: 模板中的占位符必须已声明且有值, 值按占位符上下文转义或校验
+pub fn render(content: &str, placeholders: &[(&str, PlaceholderContext)], values: &HashMap<&str, &str>) -> Result<String, TemplateError> {
+    let mut output = String::with_capacity(content.len());
+    let mut rest = content;
+    let mut offset = 0;
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after.find("}}").ok_or(TemplateError::Unterminated(offset + start))?;
+        let name = after[..end].trim();
+        let context = placeholders.iter()
+            .find(|(placeholder, _)| *placeholder == name)
+            .map(|(_, context)| *context)
+            .ok_or(TemplateError::UnknownPlaceholder(name.to_owned()))?;
+        let value = values.get(name).ok_or(TemplateError::Unfilled(name.to_owned()))?;
+        output.push_str(&escape(name, value, context)?);
+        let consumed = start + 2 + end + 2;
+        offset += consumed;
+        rest = &rest[consumed..];
+    }
+    if let Some(position) = rest.find("}}") {
+        return Err(TemplateError::Unterminated(offset + position))
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// 按上下文转义或校验值
+pub fn escape(name: &str, value: &str, context: PlaceholderContext) -> Result<String, TemplateError> {
+    match context {
+        PlaceholderContext::MoveByteString => Ok(escape_move_byte_string(value)),
+        PlaceholderContext::Address => {
+            let hex = value.strip_prefix("0x").ok_or(TemplateError::InvalidValue(name.to_owned(), "address must start with 0x".to_owned()))?;
+            if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(TemplateError::InvalidValue(name.to_owned(), format!("invalid address {}", value)))
+            }
+            Ok(value.to_owned())
+        }
+        PlaceholderContext::TomlString => Ok(escape_toml_string(value)),
+        PlaceholderContext::Integer => {
+            if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+                return Err(TemplateError::InvalidValue(name.to_owned(), format!("invalid integer {}", value)))
+            }
+            Ok(value.to_owned())
+        }
+    }
+}
+
+/// Move 字节串只接受可打印 ASCII, 其他字节(包括 UTF-8 多字节字符)用 \xHH 表示
+fn escape_move_byte_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => { let _ = write!(escaped, "\\x{:02X}", byte); }
+        }
+    }
+    escaped
+}
+
+fn escape_toml_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => { let _ = write!(escaped, "\\u{:04X}", c as u32); }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLACEHOLDERS: &[(&str, PlaceholderContext)] = &[
+        ("description", PlaceholderContext::MoveByteString),
+        ("provider", PlaceholderContext::Address),
+        ("name", PlaceholderContext::TomlString),
+        ("decimals", PlaceholderContext::Integer),
+    ];
+
+    fn values<'a>(pairs: &[(&'a str, &'a str)]) -> HashMap<&'a str, &'a str> {
+        pairs.iter().copied().collect()
+    }
+
+    #[test]
+    fn move_byte_string_escapes_quotes_backslashes_and_non_ascii() {
+        assert_eq!(escape_move_byte_string(r#"a"b"#), r#"a\"b"#);
+        assert_eq!(escape_move_byte_string(r"a\b"), r"a\\b");
+        assert_eq!(escape_move_byte_string("é"), r"\xC3\xA9");
+        assert_eq!(escape_move_byte_string("a\nb"), r"a\x0Ab");
+        assert_eq!(escape_move_byte_string("plain text 123"), "plain text 123");
+    }
+
+    #[test]
+    fn toml_string_escapes_quotes_backslashes_and_controls() {
+        assert_eq!(escape_toml_string(r#"a"b"#), r#"a\"b"#);
+        assert_eq!(escape_toml_string(r"a\b"), r"a\\b");
+        assert_eq!(escape_toml_string("a\nb\tc\r"), r"a\nb\tc\r");
+        assert_eq!(escape_toml_string("\u{1}"), r"\u0001");
+        assert_eq!(escape_toml_string("币"), "币");
+    }
+
+    #[test]
+    fn rejects_malformed_address() {
+        for value in ["1234", "0x", "0xZZ", &format!("0x{}", "a".repeat(65))] {
+            assert!(matches!(escape("provider", value, PlaceholderContext::Address), Err(TemplateError::InvalidValue(..))), "{value}");
+        }
+        assert_eq!(escape("provider", "0xAb12", PlaceholderContext::Address).unwrap(), "0xAb12");
+    }
+
+    #[test]
+    fn rejects_malformed_integer() {
+        for value in ["", "-1", "1.5", "1_000", "0x10", "1 "] {
+            assert!(matches!(escape("decimals", value, PlaceholderContext::Integer), Err(TemplateError::InvalidValue(..))), "{value}");
+        }
+        assert_eq!(escape("decimals", "6", PlaceholderContext::Integer).unwrap(), "6");
+    }
+
+    #[test]
+    fn unknown_placeholder_is_rejected() {
+        let result = render("x = {{ symbol }}", PLACEHOLDERS, &values(&[]));
+        assert!(matches!(result, Err(TemplateError::UnknownPlaceholder(name)) if name == "symbol"));
+    }
+
+    #[test]
+    fn unfilled_placeholder_is_rejected() {
+        let result = render("x = {{decimals}}", PLACEHOLDERS, &values(&[]));
+        assert!(matches!(result, Err(TemplateError::Unfilled(name)) if name == "decimals"));
+    }
+
+    #[test]
+    fn unterminated_placeholder_is_rejected() {
+        assert!(matches!(render("x = {{decimals", PLACEHOLDERS, &values(&[])), Err(TemplateError::Unterminated(4))));
+        assert!(matches!(render("x = decimals}}", PLACEHOLDERS, &values(&[])), Err(TemplateError::Unterminated(12))));
+    }
+
+    #[test]
+    fn renders_all_contexts() {
+        let content = "name = \"{{name}}\"\nconst D: u8 = {{decimals}};\nconst P: address = {{provider}};\nconst S: vector<u8> = b\"{{description}}\";";
+        let rendered = render(content, PLACEHOLDERS, &values(&[("name", "a\"b"), ("decimals", "9"), ("provider", "0x1"), ("description", "hi")])).unwrap();
+        assert_eq!(rendered, "name = \"a\\\"b\"\nconst D: u8 = 9;\nconst P: address = 0x1;\nconst S: vector<u8> = b\"hi\";");
+    }
+
+    #[test]
+    fn move_injection_in_description_renders_as_inert_bytes() {
+        let content = "const DESCRIPTION: vector<u8> = b\"{{description}}\";\n";
+        let payload = "\";\npublic fun steal() { abort 0 }\nconst X: vector<u8> = b\"\\";
+        let rendered = render(content, PLACEHOLDERS, &values(&[("description", payload)])).unwrap();
+        assert_eq!(rendered, "const DESCRIPTION: vector<u8> = b\"\\\";\\x0Apublic fun steal() { abort 0 }\\x0Aconst X: vector<u8> = b\\\"\\\\\";\n");
+        // 整个值留在一个字节串字面量内: 只有首尾两个未转义的引号, 且只有一行
+        let unescaped_quotes = rendered.match_indices('"').filter(|(i, _)| !is_escaped(&rendered, *i)).count();
+        assert_eq!(unescaped_quotes, 2);
+        assert_eq!(rendered.lines().count(), 1);
+    }
+
+    fn is_escaped(s: &str, index: usize) -> bool {
+        s[..index].bytes().rev().take_while(|b| *b == b'\\').count() % 2 == 1
+    }
+}
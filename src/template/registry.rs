@@ -0,0 +1,397 @@
+use std::{collections::{BTreeMap, HashMap}, fs, path::Path};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::template::{engine::{render, PlaceholderContext}, source::{read_template, template_version}};
+
+/// 未指定 variant 时使用的代币合约模板
+pub const DEFAULT_COIN_VARIANT: &str = "bassinet_coin.standard";
+/// 未指定 variant 时使用的NFT合约模板
+pub const DEFAULT_NFT_VARIANT: &str = "bassinet_nft.standard";
+/// 带版税模块的NFT合约模板
+pub const ROYALTY_NFT_VARIANT: &str = "bassinet_nft.royalty";
+
+/// 模板类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateKind {
+    Coin,
+    Nft,
+}
+
+/// 参数类型, 决定取值范围的上限
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+    U8,
+    U64,
+    /// 百分比 0-100
+    Percent,
+    /// 基点 0-10000
+    BasisPoints,
+}
+
+impl ParamKind {
+
+    fn limit(&self) -> u64 {
+        match self {
+            ParamKind::U8 => u8::MAX as u64,
+            ParamKind::U64 => u64::MAX,
+            ParamKind::Percent => 100,
+            ParamKind::BasisPoints => 10_000,
+        }
+    }
+}
+
+/// 参数定义
+#[derive(Debug)]
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    pub min: u64,
+    pub max: u64,
+    pub default: u64,
+}
+
+/// 一组参数之和必须等于 total(例如奖励分成)
+#[derive(Debug)]
+pub struct ParamTotal {
+    pub params: &'static [&'static str],
+    pub total: u64,
+}
+
+/// 渲染阶段: 编译前, 或发布后写入 published-at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStage {
+    Build,
+    Publish,
+}
+
+/// 模板文件到合约包内路径的映射
+#[derive(Debug)]
+pub struct TemplateFile {
+    pub template: &'static str,
+    pub dest: &'static str,
+    pub stage: RenderStage,
+    pub placeholders: &'static [(&'static str, PlaceholderContext)],
+}
+
+/// 合约模板变体
+#[derive(Debug)]
+pub struct TemplateVariant {
+    pub id: &'static str,
+    pub kind: TemplateKind,
+    pub version: u32,
+    pub params: &'static [ParamSpec],
+    pub totals: &'static [ParamTotal],
+    pub files: &'static [TemplateFile],
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    #[error("unknown template variant: {0}")]
+    UnknownVariant(String),
+    #[error("template variant {0} is not a {1:?} template")]
+    WrongKind(String, TemplateKind),
+    #[error("template variant {0} has no parameter {1}")]
+    UnknownParam(String, String),
+    #[error("parameter {0} must be an unsigned integer: {1}")]
+    InvalidParam(String, String),
+    #[error("parameter {name} must be between {min} and {max}: {value}")]
+    OutOfRange { name: String, value: u64, min: u64, max: u64 },
+    #[error("parameters {params} must sum to {total}, got {actual}")]
+    Total { params: String, total: u64, actual: u64 },
+}
+
+/// bassinet_coin.move 中的占位符
+const COIN_PLACEHOLDERS: [(&str, PlaceholderContext); 9] = [
+    ("symbol", PlaceholderContext::MoveByteString),
+    ("name", PlaceholderContext::MoveByteString),
+    ("description", PlaceholderContext::MoveByteString),
+    ("icon_url", PlaceholderContext::MoveByteString),
+    ("decimals", PlaceholderContext::Integer),
+    ("max_supply_units", PlaceholderContext::Integer),
+    ("minter_percent", PlaceholderContext::Integer),
+    ("creator_percent", PlaceholderContext::Integer),
+    ("provider_percent", PlaceholderContext::Integer),
+];
+
+/// 代币合约 Move.toml 中的占位符
+const COIN_MOVE_PLACEHOLDERS: [(&str, PlaceholderContext); 3] = [
+    ("package_id", PlaceholderContext::Address),
+    ("creator", PlaceholderContext::Address),
+    ("provider", PlaceholderContext::Address),
+];
+
+/// NFT合约 Move.toml 中的占位符
+const NFT_MOVE_PLACEHOLDERS: [(&str, PlaceholderContext); 4] = [
+    ("package_id", PlaceholderContext::Address),
+    ("creator", PlaceholderContext::Address),
+    ("provider", PlaceholderContext::Address),
+    ("bassinet_coin", PlaceholderContext::Address),
+];
+
+/// NFT合约 royalty.move 中的占位符
+const NFT_ROYALTY_PLACEHOLDERS: [(&str, PlaceholderContext); 1] = [
+    ("royalty_bps", PlaceholderContext::Integer),
+];
+
+/// 已注册的模板变体
+pub static VARIANTS: &[TemplateVariant] = &[
+    TemplateVariant {
+        id: DEFAULT_COIN_VARIANT,
+        kind: TemplateKind::Coin,
+        version: 1,
+        params: &[
+            ParamSpec { name: "decimals", kind: ParamKind::U8, min: 0, max: 18, default: 6 },
+            // 最大发行量为整币数量, 合约中的 MAX_SUPPLY 为 max_supply * 10^decimals
+            ParamSpec { name: "max_supply", kind: ParamKind::U64, min: 1, max: u64::MAX, default: 1_000_000_000 },
+            ParamSpec { name: "minter_percent", kind: ParamKind::Percent, min: 0, max: 100, default: 49 },
+            ParamSpec { name: "creator_percent", kind: ParamKind::Percent, min: 0, max: 100, default: 30 },
+            ParamSpec { name: "provider_percent", kind: ParamKind::Percent, min: 0, max: 100, default: 21 },
+        ],
+        totals: &[
+            ParamTotal { params: &["minter_percent", "creator_percent", "provider_percent"], total: 100 },
+        ],
+        files: &[
+            TemplateFile { template: "bassinet_coin_template", dest: "sources/bassinet_coin.move", stage: RenderStage::Build, placeholders: &COIN_PLACEHOLDERS },
+            TemplateFile { template: "bassinet_coin_move_template", dest: "Move.toml", stage: RenderStage::Build, placeholders: &COIN_MOVE_PLACEHOLDERS },
+            TemplateFile { template: "bassinet_coin_move_publish_template", dest: "Move.toml", stage: RenderStage::Publish, placeholders: &COIN_MOVE_PLACEHOLDERS },
+        ],
+    },
+    TemplateVariant {
+        id: DEFAULT_NFT_VARIANT,
+        kind: TemplateKind::Nft,
+        version: 1,
+        params: &[],
+        totals: &[],
+        files: &[
+            TemplateFile { template: "bassinet_nft_move_template", dest: "Move.toml", stage: RenderStage::Build, placeholders: &NFT_MOVE_PLACEHOLDERS },
+            TemplateFile { template: "bassinet_nft_move_publish_template", dest: "Move.toml", stage: RenderStage::Publish, placeholders: &NFT_MOVE_PLACEHOLDERS },
+        ],
+    },
+    TemplateVariant {
+        id: ROYALTY_NFT_VARIANT,
+        kind: TemplateKind::Nft,
+        version: 1,
+        params: &[
+            // 版税基点, 500 = 5%; 授权(bassinet::authorize)时写入NFT交易策略的版税规则
+            ParamSpec { name: "royalty_bps", kind: ParamKind::BasisPoints, min: 0, max: 10_000, default: 500 },
+        ],
+        totals: &[],
+        files: &[
+            TemplateFile { template: "bassinet_nft_royalty_template", dest: "sources/royalty.move", stage: RenderStage::Build, placeholders: &NFT_ROYALTY_PLACEHOLDERS },
+            TemplateFile { template: "bassinet_nft_move_template", dest: "Move.toml", stage: RenderStage::Build, placeholders: &NFT_MOVE_PLACEHOLDERS },
+            TemplateFile { template: "bassinet_nft_move_publish_template", dest: "Move.toml", stage: RenderStage::Publish, placeholders: &NFT_MOVE_PLACEHOLDERS },
+        ],
+    },
+];
+
+/// 按ID查找模板变体
+pub fn find_variant(id: &str) -> Option<&'static TemplateVariant> {
+    VARIANTS.iter().find(|variant| variant.id == id)
+}
+
+/// 从消息中选择模板变体(variant)并校验参数(params), 未指定时使用该类别的默认变体
+pub fn select_variant(kind: TemplateKind, payload: &Value) -> Result<VariantParams, RegistryError> {
+    let id = match payload.get("variant").and_then(|variant| variant.as_str()) {
+        Some(id) => id,
+        None => match kind {
+            TemplateKind::Coin => DEFAULT_COIN_VARIANT,
+            TemplateKind::Nft => DEFAULT_NFT_VARIANT,
+        },
+    };
+    let variant = find_variant(id).ok_or(RegistryError::UnknownVariant(id.to_owned()))?;
+    if variant.kind != kind {
+        return Err(RegistryError::WrongKind(id.to_owned(), kind))
+    }
+    variant.resolve(payload.get("params"))
+}
+
+impl TemplateVariant {
+
+    /// 记录在发布结果中的标识: id@version
+    pub fn label(&self) -> String {
+        format!("{}@{}", self.id, self.version)
+    }
+
+    /// 按参数定义校验消息中的参数, 未提供的参数取默认值
+    pub fn resolve(&'static self, params: Option<&Value>) -> Result<VariantParams, RegistryError> {
+        let mut values: BTreeMap<&'static str, u64> = self.params.iter().map(|spec| (spec.name, spec.default)).collect();
+        if let Some(params) = params.filter(|params| !params.is_null()) {
+            let params = params.as_object().ok_or(RegistryError::InvalidParam("params".to_owned(), params.to_string()))?;
+            for (name, value) in params {
+                let spec = self.params.iter()
+                    .find(|spec| spec.name == name.as_str())
+                    .ok_or(RegistryError::UnknownParam(self.id.to_owned(), name.to_owned()))?;
+                // 消息中的数值也可能以字符串传递
+                let number = match value {
+                    Value::Number(number) => number.as_u64(),
+                    Value::String(text) => text.parse::<u64>().ok(),
+                    _ => None,
+                };
+                let number = number.ok_or(RegistryError::InvalidParam(name.to_owned(), value.to_string()))?;
+                values.insert(spec.name, number);
+            }
+        }
+        for spec in self.params {
+            let value = values[spec.name];
+            let max = spec.max.min(spec.kind.limit());
+            if value < spec.min || value > max {
+                return Err(RegistryError::OutOfRange { name: spec.name.to_owned(), value, min: spec.min, max })
+            }
+        }
+        for total in self.totals {
+            let actual = total.params.iter().map(|name| values.get(name).copied().unwrap_or(0)).fold(0u64, u64::saturating_add);
+            if actual != total.total {
+                return Err(RegistryError::Total { params: total.params.join("+"), total: total.total, actual })
+            }
+        }
+        Ok(VariantParams { variant: self, values })
+    }
+}
+
+/// 选定的模板变体和校验后的参数
+#[derive(Debug, Clone)]
+pub struct VariantParams {
+    pub variant: &'static TemplateVariant,
+    pub values: BTreeMap<&'static str, u64>,
+}
+
+impl VariantParams {
+
+    /// 模板版本、变体和参数的摘要, 相同摘要的合约包除占位值外编译结果相同
+    pub fn fingerprint(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(template_version().as_bytes());
+        hasher.update([0u8]);
+        hasher.update(self.variant.label().as_bytes());
+        for (name, value) in &self.values {
+            hasher.update([0u8]);
+            hasher.update(name.as_bytes());
+            hasher.update(value.to_le_bytes());
+        }
+        hex::encode(&hasher.finalize()[..8])
+    }
+
+    /// 渲染该阶段的全部模板文件到合约包目录; 参数与 values 一起填充占位符, 同名时 values 优先
+    pub fn render_stage(&self, stage: RenderStage, package_dir: &Path, values: &HashMap<&str, String>) -> Result<(), anyhow::Error> {
+        let params: Vec<(&str, String)> = self.values.iter().map(|(name, value)| (*name, value.to_string())).collect();
+        let mut table: HashMap<&str, &str> = params.iter().map(|(name, value)| (*name, value.as_str())).collect();
+        for (name, value) in values {
+            table.insert(*name, value.as_str());
+        }
+        for file in self.variant.files.iter().filter(|file| file.stage == stage) {
+            let template = read_template(file.template)?;
+            let content = render(&template, file.placeholders, &table)?;
+            let dest = package_dir.join(file.dest);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(dest, content)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{move_build::patch::{PLACEHOLDER_DESCRIPTION, PLACEHOLDER_ICON_URL, PLACEHOLDER_NAME, PLACEHOLDER_SYMBOL}, validation::CoinEconomics};
+
+    fn values(params: &VariantParams) -> Vec<(&'static str, u64)> {
+        params.values.iter().map(|(name, value)| (*name, *value)).collect()
+    }
+
+    #[test]
+    fn selects_default_variants() {
+        let coin = select_variant(TemplateKind::Coin, &json!({})).unwrap();
+        assert_eq!(coin.variant.id, DEFAULT_COIN_VARIANT);
+        assert_eq!(values(&coin), vec![("creator_percent", 30), ("decimals", 6), ("max_supply", 1_000_000_000), ("minter_percent", 49), ("provider_percent", 21)]);
+        let nft = select_variant(TemplateKind::Nft, &json!({"params": null})).unwrap();
+        assert_eq!(nft.variant.id, DEFAULT_NFT_VARIANT);
+        assert!(nft.values.is_empty());
+    }
+
+    #[test]
+    fn selects_royalty_variant() {
+        let nft = select_variant(TemplateKind::Nft, &json!({"variant": ROYALTY_NFT_VARIANT})).unwrap();
+        assert_eq!(nft.variant.label(), "bassinet_nft.royalty@1");
+        assert_eq!(values(&nft), vec![("royalty_bps", 500)]);
+        // 数值也可以字符串传递
+        let nft = select_variant(TemplateKind::Nft, &json!({"variant": ROYALTY_NFT_VARIANT, "params": {"royalty_bps": "250"}})).unwrap();
+        assert_eq!(values(&nft), vec![("royalty_bps", 250)]);
+        let nft = select_variant(TemplateKind::Nft, &json!({"variant": ROYALTY_NFT_VARIANT, "params": {"royalty_bps": 10_000}})).unwrap();
+        assert_eq!(values(&nft), vec![("royalty_bps", 10_000)]);
+    }
+
+    #[test]
+    fn royalty_out_of_range_is_rejected() {
+        let result = select_variant(TemplateKind::Nft, &json!({"variant": ROYALTY_NFT_VARIANT, "params": {"royalty_bps": 10_001}}));
+        assert_eq!(result.unwrap_err(), RegistryError::OutOfRange { name: "royalty_bps".to_owned(), value: 10_001, min: 0, max: 10_000 });
+    }
+
+    #[test]
+    fn royalty_is_not_a_standard_param() {
+        let result = select_variant(TemplateKind::Nft, &json!({"params": {"royalty_bps": 500}}));
+        assert_eq!(result.unwrap_err(), RegistryError::UnknownParam(DEFAULT_NFT_VARIANT.to_owned(), "royalty_bps".to_owned()));
+    }
+
+    #[test]
+    fn unknown_and_mismatched_variants_are_rejected() {
+        assert_eq!(select_variant(TemplateKind::Nft, &json!({"variant": "bassinet_nft.gold"})).unwrap_err(), RegistryError::UnknownVariant("bassinet_nft.gold".to_owned()));
+        assert_eq!(select_variant(TemplateKind::Coin, &json!({"variant": ROYALTY_NFT_VARIANT})).unwrap_err(), RegistryError::WrongKind(ROYALTY_NFT_VARIANT.to_owned(), TemplateKind::Coin));
+    }
+
+    #[test]
+    fn invalid_params_are_rejected() {
+        let coin = find_variant(DEFAULT_COIN_VARIANT).unwrap();
+        assert!(matches!(coin.resolve(Some(&json!([1, 2]))), Err(RegistryError::InvalidParam(name, _)) if name == "params"));
+        assert!(matches!(coin.resolve(Some(&json!({"decimals": -1}))), Err(RegistryError::InvalidParam(name, _)) if name == "decimals"));
+        assert!(matches!(coin.resolve(Some(&json!({"decimals": "six"}))), Err(RegistryError::InvalidParam(name, _)) if name == "decimals"));
+        assert_eq!(coin.resolve(Some(&json!({"decimals": 19}))).unwrap_err(), RegistryError::OutOfRange { name: "decimals".to_owned(), value: 19, min: 0, max: 18 });
+        assert_eq!(coin.resolve(Some(&json!({"max_supply": 0}))).unwrap_err(), RegistryError::OutOfRange { name: "max_supply".to_owned(), value: 0, min: 1, max: u64::MAX });
+    }
+
+    #[test]
+    fn reward_split_must_total_100() {
+        let coin = find_variant(DEFAULT_COIN_VARIANT).unwrap();
+        let resolved = coin.resolve(Some(&json!({"minter_percent": 50, "creator_percent": 30, "provider_percent": 20}))).unwrap();
+        assert_eq!(resolved.values["minter_percent"], 50);
+        assert_eq!(coin.resolve(Some(&json!({"minter_percent": 50}))).unwrap_err(), RegistryError::Total {
+            params: "minter_percent+creator_percent+provider_percent".to_owned(),
+            total: 100,
+            actual: 101,
+        });
+    }
+
+    #[test]
+    fn variant_ids_are_unique() {
+        for (index, variant) in VARIANTS.iter().enumerate() {
+            assert!(VARIANTS[index + 1..].iter().all(|other| other.id != variant.id), "{}", variant.id);
+        }
+    }
+
+    #[test]
+    fn checked_in_coin_source_is_rendered_with_placeholders() {
+        let template = select_variant(TemplateKind::Coin, &Value::Null).unwrap();
+        let economics = CoinEconomics::from_params(&template).unwrap();
+        let max_supply_units = economics.max_supply_units().unwrap().to_string();
+        let (decimals, minter, creator, provider) = (economics.decimals.to_string(), economics.minter_percent.to_string(), economics.creator_percent.to_string(), economics.provider_percent.to_string());
+        let values: HashMap<&str, &str> = HashMap::from([
+            ("symbol", PLACEHOLDER_SYMBOL),
+            ("name", PLACEHOLDER_NAME),
+            ("description", PLACEHOLDER_DESCRIPTION),
+            ("icon_url", PLACEHOLDER_ICON_URL),
+            ("decimals", decimals.as_str()),
+            ("max_supply_units", max_supply_units.as_str()),
+            ("minter_percent", minter.as_str()),
+            ("creator_percent", creator.as_str()),
+            ("provider_percent", provider.as_str()),
+        ]);
+        let rendered = render(include_str!("../../templates/bassinet_coin_template"), &COIN_PLACEHOLDERS, &values).unwrap();
+        assert_eq!(rendered, include_str!("../../templates/bassinet_coin/sources/bassinet_coin.move"));
+    }
+}
@@ -0,0 +1,85 @@
+use std::{fs, io, path::{Path, PathBuf}, sync::OnceLock};
+
+use sha2::{Digest, Sha256};
+
+use crate::workspace::safe_entry_path;
+
+include!(concat!(env!("OUT_DIR"), "/embedded_templates.rs"));
+
+/// 覆盖目录下参与版本计算的文件
+const OVERRIDE_FILES: [&str; 8] = [
+    "bassinet_coin.tar.gz",
+    "bassinet.tar.gz",
+    "bassinet_coin_template",
+    "bassinet_coin_move_template",
+    "bassinet_coin_move_publish_template",
+    "bassinet_nft_move_template",
+    "bassinet_nft_move_publish_template",
+    "bassinet_nft_royalty_template",
+];
+
+/// 模板覆盖目录(BASSINET_TEMPLATE_PATH), 未设置时使用嵌入的模板
+pub fn override_dir() -> Option<PathBuf> {
+    std::env::var("BASSINET_TEMPLATE_PATH").ok().filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+/// 当前使用的模板版本, 记录在发布结果中
+pub fn template_version() -> &'static str {
+    static VERSION: OnceLock<String> = OnceLock::new();
+    VERSION.get_or_init(|| match override_dir() {
+        Some(dir) => override_version(&dir),
+        None => EMBEDDED_VERSION.to_owned(),
+    })
+}
+
+/// 覆盖目录的版本: override- 加文件内容的 sha256
+fn override_version(dir: &Path) -> String {
+    let mut hasher = Sha256::new();
+    for name in OVERRIDE_FILES {
+        if let Ok(content) = fs::read(dir.join(name)) {
+            hasher.update(name.as_bytes());
+            hasher.update([0u8]);
+            hasher.update(&content);
+        }
+    }
+    let digest = hasher.finalize();
+    let hash: String = digest.iter().take(8).map(|byte| format!("{:02x}", byte)).collect();
+    format!("override-{}", hash)
+}
+
+/// 读取单个模板文件
+pub fn read_template(name: &str) -> Result<String, io::Error> {
+    if let Some(dir) = override_dir() {
+        return fs::read_to_string(dir.join(name))
+    }
+    let (_, content) = EMBEDDED_FILES.iter()
+        .find(|(file, _)| *file == name)
+        .ok_or(io::Error::new(io::ErrorKind::NotFound, format!("template {} not embedded", name)))?;
+    String::from_utf8(content.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// 写出嵌入的Move包; strip_package 为 true 时去掉包目录名, 直接写到 dest 下
+pub fn write_embedded_package(package: &str, dest: &Path, strip_package: bool) -> Result<(), io::Error> {
+    let prefix = package.to_owned() + "/";
+    let mut found = false;
+    for (name, content) in EMBEDDED_FILES {
+        let Some(relative) = name.strip_prefix(&prefix) else {
+            continue;
+        };
+        found = true;
+        let relative = if strip_package { relative } else { name };
+        let relative = Path::new(relative);
+        if !safe_entry_path(relative) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsafe template path: {}", name)))
+        }
+        let path = dest.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content)?;
+    }
+    if !found {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("template package {} not embedded", package)))
+    }
+    Ok(())
+}
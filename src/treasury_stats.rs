@@ -0,0 +1,349 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sui_sdk::{types::{base_types::ObjectID, TypeTag}, SuiClient};
+use tokio::time::{sleep, Duration};
+use tracing::{error, info};
+
+use crate::{event_listening::get_client, events_mq::{treasury_snapshot_producer, Config}, kv_store::{now_millis, KVStore}, repository::{CoinPackageRepository, CollectionRepository, CreatorRepository, NftPackageRepository, OperationRepository, PortfolioRepository, WorkflowStatus}, sui_service::{object_fields, BassinetCoinPublishedResult}};
+
+const USAGE: &str = "usage: bassinet-sui stats <command>
+  <wallet_address>               show a creator's TreasuryLock and MintAppCap statistics
+  --collection <collection_id>   show a collection's MintAppCap statistics";
+
+/// 默认每小时发送一次快照
+const DEFAULT_SNAPSHOT_INTERVAL_SECS: u64 = 60 * 60;
+
+/// 每页读取的动态字段数量
+const DYNAMIC_FIELD_PAGE_SIZE: usize = 50;
+
+/// TreasuryLock 统计(数量含精度)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreasuryStats {
+    pub package_id: String,
+    pub treasury_lock_id: String,
+    pub max_supply: u64,
+    pub total_supply: u64,
+    pub minting_counter: u64,
+    /// 剩余激励
+    pub remaining_rewards: u64,
+    pub creator: String,
+    pub creator_rewards: u64,
+    pub platform_provider: String,
+    pub platform_provider_rewards: u64,
+}
+
+/// MintAppCap 统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MintAppStats {
+    /// 持有 MintAppCap 的应用对象
+    pub app_id: String,
+    pub app_name: String,
+    pub app_type: String,
+    pub rewards_quantity: u64,
+    pub minting_limit: u64,
+    pub minting_counter: u64,
+    pub total_rewards: u64,
+}
+
+/// collection的铸造统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionStats {
+    pub collection_id: String,
+    pub package_id: String,
+    pub mint_id: String,
+    /// 授权被撤销后为空
+    pub apps: Vec<MintAppStats>,
+}
+
+/// 创作者的代币合约统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatorStats {
+    pub wallet_address: String,
+    pub treasury: TreasuryStats,
+    pub collections: Vec<CollectionStats>,
+    /// 通过授权操作授权的其他应用
+    pub apps: Vec<MintAppStats>,
+    /// 读取失败的collection和应用, 不影响其他统计
+    #[serde(default)]
+    pub errors: Vec<String>,
+    pub fetched_at: u64,
+}
+
+/// 全部创作者的统计快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreasurySnapshotMessage {
+    pub creators: Vec<CreatorStats>,
+    pub snapshot_at: u64,
+}
+
+/// 统计查询子命令, 输出JSON
+pub async fn stats_command<S: KVStore>(args: &[String], db: S) -> Result<(), anyhow::Error> {
+    let client = get_client().await?;
+    match args.first().map(|arg| arg.as_str()) {
+        Some("--collection") => {
+            let collection_id = args.get(1).ok_or(anyhow!(USAGE))?;
+            let stats = collection_stats(&client, &db, collection_id).await?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+            Ok(())
+        }
+        Some(wallet_address) if !wallet_address.starts_with("--") => {
+            let stats = creator_stats(&client, &db, wallet_address).await?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+            Ok(())
+        }
+        _ => Err(anyhow!(USAGE)),
+    }
+}
+
+/// 定期发送全部创作者的统计快照
+pub async fn publish_snapshots_periodically<S: KVStore>(db: S, cfg: Arc<Config>) {
+    let interval = std::env::var("STATS_SNAPSHOT_INTERVAL_SECS")
+        .map(|s| s.parse::<u64>().expect("can't parse STATS_SNAPSHOT_INTERVAL_SECS"))
+        .unwrap_or(DEFAULT_SNAPSHOT_INTERVAL_SECS);
+    loop {
+        sleep(Duration::from_secs(interval)).await;
+        match snapshot(&db).await {
+            Ok(message) => {
+                info!("统计快照:{}个创作者", message.creators.len());
+                let _ = treasury_snapshot_producer::produce_treasury_snapshot(cfg.clone(), &message).await;
+            }
+            Err(err) => error!("统计快照失败:{err:?}"),
+        }
+    }
+}
+
+/// 全部创作者的统计, 单个创作者读取失败时跳过
+pub async fn snapshot<S: KVStore>(db: &S) -> Result<TreasurySnapshotMessage, anyhow::Error> {
+    let client = get_client().await?;
+    let mut creators = Vec::new();
+    for (wallet_address, _) in CreatorRepository::new(db.clone()).list()? {
+        match creator_stats(&client, db, &wallet_address).await {
+            Ok(stats) => creators.push(stats),
+            Err(err) => error!("wallet_address:{}, read stats failed:{:?}", wallet_address, err),
+        }
+    }
+    Ok(TreasurySnapshotMessage { creators, snapshot_at: now_millis() })
+}
+
+/// 创作者的TreasuryLock、各collection及已授权应用的MintAppCap
+pub async fn creator_stats<S: KVStore>(client: &SuiClient, db: &S, wallet_address: &str) -> Result<CreatorStats, anyhow::Error> {
+    let package_id = CreatorRepository::new(db.clone()).coin_package_id(wallet_address)?
+        .ok_or(anyhow!("no coin package recorded for {}", wallet_address))?;
+    let coin = CoinPackageRepository::new(db.clone()).find(&package_id)?
+        .ok_or(anyhow!("coin package {} record not found", package_id))?;
+    let treasury = fetch_treasury(client, &coin).await?;
+
+    // 单个collection或应用读取失败时记录错误并继续
+    let mut errors = Vec::new();
+    let mut collections = Vec::new();
+    for collection in PortfolioRepository::new(db.clone()).collections(wallet_address)? {
+        if collection.mint_id.is_none() {
+            continue;
+        }
+        match collection_stats(client, db, &collection.collection_id).await {
+            Ok(stats) => collections.push(stats),
+            Err(err) => errors.push(format!("collection {}: {}", collection.collection_id, err)),
+        }
+    }
+
+    let mut apps = Vec::new();
+    for app_id in authorized_apps(db, wallet_address)? {
+        match fetch_app_caps(client, &coin.package_id, &app_id).await {
+            Ok(caps) => apps.extend(caps),
+            Err(err) => errors.push(format!("app {}: {}", app_id, err)),
+        }
+    }
+    Ok(CreatorStats { wallet_address: wallet_address.to_owned(), treasury, collections, apps, errors, fetched_at: now_millis() })
+}
+
+/// collection的NFT合约Mint对象上的MintAppCap
+pub async fn collection_stats<S: KVStore>(client: &SuiClient, db: &S, collection_id: &str) -> Result<CollectionStats, anyhow::Error> {
+    let package_id = CollectionRepository::new(db.clone()).package_id(collection_id)?
+        .ok_or(anyhow!("collection {} has no nft package", collection_id))?;
+    let nft = NftPackageRepository::new(db.clone()).find(&package_id)?
+        .ok_or(anyhow!("nft package {} record not found", package_id))?;
+    let wallet_address = PortfolioRepository::new(db.clone()).creator(collection_id)?
+        .ok_or(anyhow!("collection {} has no creator", collection_id))?;
+    let coin_package_id = CreatorRepository::new(db.clone()).coin_package_id(&wallet_address)?
+        .ok_or(anyhow!("no coin package recorded for {}", wallet_address))?;
+    let apps = fetch_app_caps(client, &coin_package_id, &nft.mint_id).await?;
+    Ok(CollectionStats { collection_id: collection_id.to_owned(), package_id, mint_id: nft.mint_id, apps })
+}
+
+/// 按授权操作记录得到当前已授权的应用对象
+fn authorized_apps<S: KVStore>(db: &S, wallet_address: &str) -> Result<BTreeSet<String>, anyhow::Error> {
+    let mut apps = BTreeSet::new();
+    for operation in OperationRepository::new(db.clone()).list(wallet_address)? {
+        if operation.status != WorkflowStatus::Completed {
+            continue;
+        }
+        match operation.kind.as_str() {
+            "authorize_app" => { apps.insert(operation.target); }
+            "revoke_app" => { apps.remove(&operation.target); }
+            _ => {}
+        }
+    }
+    Ok(apps)
+}
+
+/// 读取并解析TreasuryLock
+pub async fn fetch_treasury(client: &SuiClient, coin: &BassinetCoinPublishedResult) -> Result<TreasuryStats, anyhow::Error> {
+    let lock_id = ObjectID::from_hex_literal(&coin.treasury_lock_id)?;
+    let fields = object_fields(client, lock_id).await?;
+    decode_treasury(coin, &fields)
+}
+
+/// TreasuryLock 的字段
+fn decode_treasury(coin: &BassinetCoinPublishedResult, fields: &Value) -> Result<TreasuryStats, anyhow::Error> {
+    Ok(TreasuryStats {
+        package_id: coin.package_id.clone(),
+        treasury_lock_id: coin.treasury_lock_id.clone(),
+        max_supply: u64_field(fields, "max_supply")?,
+        total_supply: u64_field(fields, "total_supply")?,
+        minting_counter: u64_field(fields, "minting_counter")?,
+        remaining_rewards: u64_field(fields, "total_rewards")?,
+        creator: string_field(fields, "creator")?,
+        creator_rewards: u64_field(fields, "creator_rewards")?,
+        platform_provider: string_field(fields, "platform_provider")?,
+        platform_provider_rewards: u64_field(fields, "platform_provider_rewards")?,
+    })
+}
+
+/// 读取应用对象上 <coin_package>::bassinet_coin::AppKey<T> 动态字段中的MintAppCap
+pub async fn fetch_app_caps(client: &SuiClient, coin_package_id: &str, app_id: &str) -> Result<Vec<MintAppStats>, anyhow::Error> {
+    let coin_package = ObjectID::from_hex_literal(coin_package_id)?;
+    let app = ObjectID::from_hex_literal(app_id)?;
+    let mut apps = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = client.read_api().get_dynamic_fields(app, cursor, Some(DYNAMIC_FIELD_PAGE_SIZE)).await?;
+        for field in page.data {
+            let is_app_key = match &field.name.type_ {
+                TypeTag::Struct(tag) => ObjectID::from(tag.address) == coin_package && tag.module.as_str() == "bassinet_coin" && tag.name.as_str() == "AppKey",
+                _ => false,
+            };
+            if !is_app_key {
+                continue;
+            }
+            let fields = object_fields(client, field.object_id).await?;
+            apps.push(decode_app_cap(app_id, &fields).map_err(|e| anyhow!("dynamic field {}: {}", field.object_id, e))?);
+        }
+        if !page.has_next_page {
+            break;
+        }
+        cursor = page.next_cursor;
+    }
+    Ok(apps)
+}
+
+/// 动态字段 Field<AppKey<T>, MintAppCap<T>> 中的MintAppCap
+fn decode_app_cap(app_id: &str, fields: &Value) -> Result<MintAppStats, anyhow::Error> {
+    let cap = fields.get("value").ok_or(anyhow!("dynamic field has no value"))?;
+    Ok(MintAppStats {
+        app_id: app_id.to_owned(),
+        app_name: string_field(cap, "app_name")?,
+        app_type: string_field(cap, "app_type")?,
+        rewards_quantity: u64_field(cap, "rewards_quantity")?,
+        minting_limit: u64_field(cap, "minting_limit")?,
+        minting_counter: u64_field(cap, "minting_counter")?,
+        total_rewards: u64_field(cap, "total_rewards")?,
+    })
+}
+
+fn u64_field(fields: &Value, name: &str) -> Result<u64, anyhow::Error> {
+    fields.get(name).and_then(u64_value).ok_or(anyhow!("invalid field {}: {:?}", name, fields.get(name)))
+}
+
+fn string_field(fields: &Value, name: &str) -> Result<String, anyhow::Error> {
+    fields.get(name).and_then(|value| value.as_str()).map(|value| value.to_owned()).ok_or(anyhow!("invalid field {}: {:?}", name, fields.get(name)))
+}
+
+/// u64 和 Balance 字段显示为数字字符串
+pub fn u64_value(value: &Value) -> Option<u64> {
+    match value {
+        Value::String(value) => value.parse::<u64>().ok(),
+        Value::Number(value) => value.as_u64(),
+        Value::Object(fields) => fields.get("value").and_then(u64_value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// TreasuryLock 的字段(get_object content.fields)
+    const TREASURY_LOCK_FIXTURE: &str = r#"{
+        "id": {"id": "0x5c3f8d4e0b1a2c3d4e5f60718293a4b5c6d7e8f90123456789abcdef01234567"},
+        "max_supply": "1000000000000",
+        "total_supply": "2500000000",
+        "minting_counter": "25",
+        "total_rewards": "997500000000",
+        "creator": "0x00000000000000000000000000000000000000000000000000000000000000aa",
+        "creator_rewards": "1500000000",
+        "platform_provider": "0x00000000000000000000000000000000000000000000000000000000000000bb",
+        "platform_provider_rewards": "1000000000"
+    }"#;
+
+    /// 动态字段 Field<AppKey<T>, MintAppCap<T>> 的字段
+    const MINT_APP_CAP_FIXTURE: &str = r#"{
+        "id": {"id": "0x8e2b4f6a0c1d2e3f405162738495a6b7c8d9e0f1a2b3c4d5e6f708192a3b4c5d"},
+        "name": {"dummy_field": false},
+        "value": {
+            "app_name": "Bassinet NFT",
+            "app_type": "0x7d1c::bassinet::Mint",
+            "rewards_quantity": "100000000",
+            "minting_limit": "1000",
+            "minting_counter": "12",
+            "total_rewards": "1200000000"
+        }
+    }"#;
+
+    #[test]
+    fn u64_value_reads_rpc_encodings() {
+        assert_eq!(u64_value(&json!("18446744073709551615")), Some(u64::MAX));
+        assert_eq!(u64_value(&json!(42)), Some(42));
+        assert_eq!(u64_value(&json!({"value": "7"})), Some(7));
+        assert_eq!(u64_value(&json!("18446744073709551616")), None);
+        assert_eq!(u64_value(&json!("-1")), None);
+        assert_eq!(u64_value(&json!(-1)), None);
+        assert_eq!(u64_value(&json!(1.5)), None);
+        assert_eq!(u64_value(&json!({"balance": "7"})), None);
+        assert_eq!(u64_value(&Value::Null), None);
+    }
+
+    #[test]
+    fn decodes_mint_app_cap_field() {
+        let fields: Value = serde_json::from_str(MINT_APP_CAP_FIXTURE).unwrap();
+        let cap = decode_app_cap("0xapp", &fields).unwrap();
+        assert_eq!(cap.app_id, "0xapp");
+        assert_eq!(cap.app_name, "Bassinet NFT");
+        assert_eq!(cap.app_type, "0x7d1c::bassinet::Mint");
+        assert_eq!((cap.rewards_quantity, cap.minting_limit, cap.minting_counter, cap.total_rewards), (100000000, 1000, 12, 1200000000));
+
+        let mut missing = fields.clone();
+        missing["value"].as_object_mut().unwrap().remove("minting_limit");
+        assert!(decode_app_cap("0xapp", &missing).is_err());
+        assert!(decode_app_cap("0xapp", &json!({"name": {"dummy_field": false}})).is_err());
+    }
+
+    #[test]
+    fn decodes_treasury_lock() {
+        let coin: BassinetCoinPublishedResult = serde_json::from_value(json!({
+            "package_id": "0xc01", "admin_cap_id": "0xa01", "treasury_lock_id": "0xb01", "upgrade_cap_id": null,
+            "wallet_address": "0xaa", "account": "creator", "network": "testnet",
+        })).unwrap();
+        let fields: Value = serde_json::from_str(TREASURY_LOCK_FIXTURE).unwrap();
+        let treasury = decode_treasury(&coin, &fields).unwrap();
+        assert_eq!(treasury.treasury_lock_id, "0xb01");
+        assert_eq!(treasury.max_supply, 1000000000000);
+        assert_eq!(treasury.remaining_rewards, 997500000000);
+        assert_eq!(treasury.creator_rewards, 1500000000);
+        assert_eq!(treasury.platform_provider, "0x00000000000000000000000000000000000000000000000000000000000000bb");
+    }
+}
@@ -0,0 +1,377 @@
+use reqwest::Url;
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::{repository::LaunchParams, template::registry::{select_variant, RegistryError, TemplateKind, VariantParams}};
+
+/// 代币符号最大长度
+const MAX_SYMBOL_LEN: usize = 16;
+/// 代币名称最大长度(字符)
+const MAX_NAME_LEN: usize = 64;
+/// 代币描述最大长度(字符)
+const MAX_DESCRIPTION_LEN: usize = 1000;
+/// 图标地址最大长度
+const MAX_ICON_URL_LEN: usize = 512;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
+    #[error("symbol must be 1-{max} ASCII letters, digits or '_': {0}", max = MAX_SYMBOL_LEN)]
+    InvalidSymbol(String),
+    #[error("name must be 1-{max} characters without control characters", max = MAX_NAME_LEN)]
+    InvalidName,
+    #[error("description must be at most {max} characters without control characters", max = MAX_DESCRIPTION_LEN)]
+    InvalidDescription,
+    #[error("icon_url must be an http(s) url of at most {max} ASCII characters: {0}", max = MAX_ICON_URL_LEN)]
+    InvalidIconUrl(String),
+    #[error("{0} must be an unsigned integer: {1}")]
+    InvalidNumber(&'static str, String),
+    #[error("{0} is not a valid Sui address: {1}")]
+    InvalidAddress(&'static str, String),
+    #[error(transparent)]
+    Template(#[from] RegistryError),
+    #[error("reward split must sum to 100: {0}/{1}/{2}")]
+    InvalidSplit(u8, u8, u8),
+    #[error("max supply {0} with {1} decimals must be positive and fit in u64")]
+    InvalidSupply(u64, u8),
+}
+
+/// DigitalServiceOpened 消息中的代币发布请求
+#[derive(Debug, Clone)]
+pub struct CoinPublishRequest {
+    pub public_key: String,
+    pub address: String,
+    pub symbol: String,
+    pub name: String,
+    pub description: String,
+    pub icon_url: String,
+    /// 合约模板变体(variant)和参数(params), 按变体的参数定义校验
+    pub template: VariantParams,
+    pub economics: CoinEconomics,
+}
+
+impl CoinPublishRequest {
+
+    /// 从消息中读取并校验
+    pub fn from_payload(value: &Value) -> Result<Self, ValidationError> {
+        let field = |name: &'static str| value.get(name).and_then(|value| value.as_str()).ok_or(ValidationError::MissingField(name));
+        let template = select_variant(TemplateKind::Coin, value)?;
+        let request = CoinPublishRequest {
+            public_key: field("public_key")?.to_owned(),
+            address: field("address")?.to_owned(),
+            symbol: field("symbol")?.to_owned(),
+            name: field("name")?.to_owned(),
+            description: field("description")?.to_owned(),
+            icon_url: field("icon_url")?.to_owned(),
+            economics: CoinEconomics::from_params(&template)?,
+            template,
+        };
+        request.validate()?;
+        Ok(request)
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_address("address", &self.address)?;
+        validate_symbol(&self.symbol)?;
+        validate_name(&self.name)?;
+        validate_description(&self.description)?;
+        validate_icon_url(&self.icon_url)?;
+        self.economics.validate()
+    }
+}
+
+/// NftLaunched 消息中的NFT发布请求
+#[derive(Debug, Clone)]
+pub struct NftLaunchRequest {
+    pub public_key: String,
+    pub address: String,
+    pub collection_id: String,
+    pub launch: LaunchParams,
+    /// 合约模板变体(variant)和参数(params), 按变体的参数定义校验
+    pub template: VariantParams,
+}
+
+impl NftLaunchRequest {
+
+    /// 从消息中读取并校验; limit、rewards_quantity、minting_price 为十进制字符串
+    pub fn from_payload(value: &Value) -> Result<Self, ValidationError> {
+        let field = |name: &'static str| value.get(name).and_then(|value| value.as_str()).filter(|value| !value.is_empty()).ok_or(ValidationError::MissingField(name));
+        let number = |name: &'static str| field(name).and_then(|value| value.parse::<u64>().map_err(|_| ValidationError::InvalidNumber(name, value.to_owned())));
+        let request = NftLaunchRequest {
+            public_key: field("public_key")?.to_owned(),
+            address: field("address")?.to_owned(),
+            collection_id: field("collection_id")?.to_owned(),
+            launch: LaunchParams {
+                limit: number("limit")?,
+                rewards_quantity: number("rewards_quantity")?,
+                minting_price: number("minting_price")?,
+            },
+            template: select_variant(TemplateKind::Nft, value)?,
+        };
+        validate_address("address", &request.address)?;
+        Ok(request)
+    }
+}
+
+/// 代币经济参数: 精度、最大发行量(整币数量)和挖掘激励分成百分比
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoinEconomics {
+    pub decimals: u8,
+    pub max_supply: u64,
+    pub minter_percent: u8,
+    pub creator_percent: u8,
+    pub provider_percent: u8,
+}
+
+impl CoinEconomics {
+
+    /// 从模板参数(decimals, max_supply, minter_percent, creator_percent, provider_percent)读取
+    pub fn from_params(params: &VariantParams) -> Result<Self, ValidationError> {
+        let value = |name: &'static str| params.values.get(name).copied().ok_or(ValidationError::MissingField(name));
+        let small = |name: &'static str| value(name).and_then(|value| u8::try_from(value)
+            .map_err(|_| ValidationError::Template(RegistryError::OutOfRange { name: name.to_owned(), value, min: 0, max: u8::MAX as u64 })));
+        let economics = CoinEconomics {
+            decimals: small("decimals")?,
+            max_supply: value("max_supply")?,
+            minter_percent: small("minter_percent")?,
+            creator_percent: small("creator_percent")?,
+            provider_percent: small("provider_percent")?,
+        };
+        economics.validate()?;
+        Ok(economics)
+    }
+
+    /// 分成之和为100, 含精度的最大发行量不超过u64
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let total = self.minter_percent as u32 + self.creator_percent as u32 + self.provider_percent as u32;
+        if total != 100 {
+            return Err(ValidationError::InvalidSplit(self.minter_percent, self.creator_percent, self.provider_percent))
+        }
+        if self.max_supply == 0 || self.max_supply_units().is_none() {
+            return Err(ValidationError::InvalidSupply(self.max_supply, self.decimals))
+        }
+        Ok(())
+    }
+
+    /// 含精度的最大发行量(合约中的 MAX_SUPPLY)
+    pub fn max_supply_units(&self) -> Option<u64> {
+        10u64.checked_pow(self.decimals as u32).and_then(|unit| self.max_supply.checked_mul(unit))
+    }
+}
+
+/// 符号为 ASCII(链上为 ascii::String)
+pub fn validate_symbol(symbol: &str) -> Result<(), ValidationError> {
+    let valid = !symbol.is_empty()
+        && symbol.len() <= MAX_SYMBOL_LEN
+        && symbol.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(ValidationError::InvalidSymbol(symbol.to_owned()))
+    }
+    Ok(())
+}
+
+pub fn validate_name(name: &str) -> Result<(), ValidationError> {
+    let len = name.chars().count();
+    if name.trim().is_empty() || len > MAX_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(ValidationError::InvalidName)
+    }
+    Ok(())
+}
+
+pub fn validate_description(description: &str) -> Result<(), ValidationError> {
+    let len = description.chars().count();
+    if len > MAX_DESCRIPTION_LEN || description.chars().any(|c| c.is_control() && c != '\n') {
+        return Err(ValidationError::InvalidDescription)
+    }
+    Ok(())
+}
+
+/// 图标地址为 ASCII(链上为 Url)
+pub fn validate_icon_url(icon_url: &str) -> Result<(), ValidationError> {
+    let invalid = || ValidationError::InvalidIconUrl(icon_url.to_owned());
+    if icon_url.len() > MAX_ICON_URL_LEN || !icon_url.is_ascii() || icon_url.chars().any(|c| c.is_ascii_whitespace() || c.is_ascii_control()) {
+        return Err(invalid())
+    }
+    let url = Url::parse(icon_url).map_err(|_| invalid())?;
+    if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none() {
+        return Err(invalid())
+    }
+    Ok(())
+}
+
+/// 完整的 Sui 地址: 0x + 64位十六进制
+pub fn validate_address(field: &'static str, address: &str) -> Result<(), ValidationError> {
+    let valid = address.strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+    if !valid {
+        return Err(ValidationError::InvalidAddress(field, address.to_owned()))
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const ADDRESS: &str = "0x00000000000000000000000000000000000000000000000000000000000000aa";
+
+    fn launch_payload() -> Value {
+        json!({
+            "public_key": "key",
+            "address": ADDRESS,
+            "collection_id": "c1",
+            "limit": "100",
+            "rewards_quantity": "10",
+            "minting_price": "5",
+        })
+    }
+
+    #[test]
+    fn nft_launch_request_parses_payload() {
+        let request = NftLaunchRequest::from_payload(&launch_payload()).unwrap();
+        assert_eq!(request.address, ADDRESS);
+        assert_eq!(request.collection_id, "c1");
+        assert_eq!((request.launch.limit, request.launch.rewards_quantity, request.launch.minting_price), (100, 10, 5));
+    }
+
+    #[test]
+    fn nft_launch_request_rejects_invalid_fields() {
+        let mut payload = launch_payload();
+        payload["limit"] = json!("-1");
+        assert_eq!(NftLaunchRequest::from_payload(&payload).unwrap_err(), ValidationError::InvalidNumber("limit", "-1".to_owned()));
+
+        let mut payload = launch_payload();
+        payload["minting_price"] = json!(5);
+        assert_eq!(NftLaunchRequest::from_payload(&payload).unwrap_err(), ValidationError::MissingField("minting_price"));
+
+        let mut payload = launch_payload();
+        payload["address"] = json!("0xaa");
+        assert_eq!(NftLaunchRequest::from_payload(&payload).unwrap_err(), ValidationError::InvalidAddress("address", "0xaa".to_owned()));
+
+        let mut payload = launch_payload();
+        payload.as_object_mut().unwrap().remove("collection_id");
+        assert_eq!(NftLaunchRequest::from_payload(&payload).unwrap_err(), ValidationError::MissingField("collection_id"));
+
+        assert_eq!(NftLaunchRequest::from_payload(&Value::Null).unwrap_err(), ValidationError::MissingField("public_key"));
+    }
+
+    fn coin_payload() -> Value {
+        json!({
+            "public_key": "key",
+            "address": ADDRESS,
+            "symbol": "BASS_1",
+            "name": "Bassinet",
+            "description": "creator coin\nline two",
+            "icon_url": "https://example.com/icon.png",
+        })
+    }
+
+    fn economics() -> CoinEconomics {
+        CoinEconomics { decimals: 6, max_supply: 1_000_000_000, minter_percent: 49, creator_percent: 30, provider_percent: 21 }
+    }
+
+    #[test]
+    fn coin_publish_request_parses_payload() {
+        let request = CoinPublishRequest::from_payload(&coin_payload()).unwrap();
+        assert_eq!(request.address, ADDRESS);
+        assert_eq!(request.symbol, "BASS_1");
+        assert_eq!(request.economics, economics());
+
+        let mut payload = coin_payload();
+        payload["params"] = json!({"decimals": 9, "max_supply": 1000, "minter_percent": 50, "creator_percent": 50, "provider_percent": 0});
+        let request = CoinPublishRequest::from_payload(&payload).unwrap();
+        assert_eq!(request.economics, CoinEconomics { decimals: 9, max_supply: 1000, minter_percent: 50, creator_percent: 50, provider_percent: 0 });
+    }
+
+    #[test]
+    fn coin_publish_request_rejects_invalid_fields() {
+        assert_eq!(CoinPublishRequest::from_payload(&Value::Null).unwrap_err(), ValidationError::MissingField("public_key"));
+
+        let mut payload = coin_payload();
+        payload.as_object_mut().unwrap().remove("icon_url");
+        assert_eq!(CoinPublishRequest::from_payload(&payload).unwrap_err(), ValidationError::MissingField("icon_url"));
+
+        let mut payload = coin_payload();
+        payload["symbol"] = json!("BASS-1");
+        assert_eq!(CoinPublishRequest::from_payload(&payload).unwrap_err(), ValidationError::InvalidSymbol("BASS-1".to_owned()));
+
+        let mut payload = coin_payload();
+        payload["address"] = json!("aa");
+        assert_eq!(CoinPublishRequest::from_payload(&payload).unwrap_err(), ValidationError::InvalidAddress("address", "aa".to_owned()));
+
+        let mut payload = coin_payload();
+        payload["params"] = json!({"decimals": 19});
+        assert!(matches!(CoinPublishRequest::from_payload(&payload).unwrap_err(), ValidationError::Template(_)));
+
+        let mut payload = coin_payload();
+        payload["params"] = json!({"decimals": 18, "max_supply": 19});
+        assert_eq!(CoinPublishRequest::from_payload(&payload).unwrap_err(), ValidationError::InvalidSupply(19, 18));
+    }
+
+    #[test]
+    fn validates_symbol() {
+        assert!(validate_symbol("A").is_ok());
+        assert!(validate_symbol("BASS_coin_123456").is_ok());
+        assert_eq!(validate_symbol("").unwrap_err(), ValidationError::InvalidSymbol(String::new()));
+        assert!(validate_symbol("BASS_coin_1234567").is_err());
+        assert!(validate_symbol("BASS COIN").is_err());
+        assert!(validate_symbol("币").is_err());
+    }
+
+    #[test]
+    fn validates_name() {
+        assert!(validate_name("Bassinet 币").is_ok());
+        assert!(validate_name(&"名".repeat(MAX_NAME_LEN)).is_ok());
+        assert_eq!(validate_name(" ").unwrap_err(), ValidationError::InvalidName);
+        assert!(validate_name(&"名".repeat(MAX_NAME_LEN + 1)).is_err());
+        assert!(validate_name("Bass\ninet").is_err());
+    }
+
+    #[test]
+    fn validates_description() {
+        assert!(validate_description("").is_ok());
+        assert!(validate_description("line one\nline two").is_ok());
+        assert!(validate_description(&"描".repeat(MAX_DESCRIPTION_LEN)).is_ok());
+        assert_eq!(validate_description(&"描".repeat(MAX_DESCRIPTION_LEN + 1)).unwrap_err(), ValidationError::InvalidDescription);
+        assert!(validate_description("tab\there").is_err());
+    }
+
+    #[test]
+    fn validates_icon_url() {
+        assert!(validate_icon_url("https://example.com/icon.png").is_ok());
+        assert!(validate_icon_url("http://127.0.0.1:8080/icon.png").is_ok());
+        assert_eq!(validate_icon_url("ftp://example.com/icon.png").unwrap_err(), ValidationError::InvalidIconUrl("ftp://example.com/icon.png".to_owned()));
+        assert!(validate_icon_url("icon.png").is_err());
+        assert!(validate_icon_url("https://example.com/a b.png").is_err());
+        assert!(validate_icon_url("https://例子.com/icon.png").is_err());
+        assert!(validate_icon_url("data:image/png;base64,AAAA").is_err());
+        assert!(validate_icon_url(&format!("https://example.com/{}", "a".repeat(MAX_ICON_URL_LEN))).is_err());
+    }
+
+    #[test]
+    fn validates_address() {
+        assert!(validate_address("address", ADDRESS).is_ok());
+        assert!(validate_address("address", &format!("0x{}", "AB".repeat(32))).is_ok());
+        assert_eq!(validate_address("PROVIDER", "0xaa").unwrap_err(), ValidationError::InvalidAddress("PROVIDER", "0xaa".to_owned()));
+        assert!(validate_address("address", &ADDRESS[2..]).is_err());
+        assert!(validate_address("address", &format!("0x{}", "g".repeat(64))).is_err());
+        assert!(validate_address("address", &format!("0x{}", "a".repeat(65))).is_err());
+    }
+
+    #[test]
+    fn validates_economics() {
+        assert!(economics().validate().is_ok());
+        assert!(CoinEconomics { decimals: 0, max_supply: u64::MAX, ..economics() }.validate().is_ok());
+
+        let split = CoinEconomics { minter_percent: 50, creator_percent: 30, provider_percent: 21, ..economics() };
+        assert_eq!(split.validate().unwrap_err(), ValidationError::InvalidSplit(50, 30, 21));
+        let zero = CoinEconomics { max_supply: 0, ..economics() };
+        assert_eq!(zero.validate().unwrap_err(), ValidationError::InvalidSupply(0, 6));
+        let overflow = CoinEconomics { decimals: 18, max_supply: 19, ..economics() };
+        assert_eq!(overflow.validate().unwrap_err(), ValidationError::InvalidSupply(19, 18));
+        assert_eq!(CoinEconomics { decimals: 18, max_supply: 18, ..economics() }.max_supply_units(), Some(18 * 10u64.pow(18)));
+    }
+}
@@ -0,0 +1,233 @@
+use std::{fs, io, path::{Component, Path, PathBuf}};
+
+use thiserror::Error;
+
+pub mod manager;
+
+/// 目录名最大长度
+const MAX_SEGMENT_LEN: usize = 128;
+
+#[derive(Error, Debug)]
+pub enum WorkspaceError {
+    #[error("workspace io failed: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid directory name: {0:?}")]
+    InvalidSegment(String),
+    #[error("path escapes workspace root: {0}")]
+    Escapes(PathBuf),
+    #[error("path is not a directory: {0}")]
+    NotDirectory(PathBuf),
+}
+
+/// 合约工作目录(CONTRACTS_DIR_PATH), 所有派生路径都限制在根目录内
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+
+    /// 根目录不存在时创建, 并规范化
+    pub fn new(root: &Path) -> Result<Self, WorkspaceError> {
+        fs::create_dir_all(root)?;
+        Ok(Self { root: root.canonicalize()? })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 创作者目录: <root>/<钱包地址去掉0x>
+    pub fn creator_dir(&self, wallet_address: &str) -> Result<PathBuf, WorkspaceError> {
+        let segment = wallet_address.strip_prefix("0x").unwrap_or(wallet_address);
+        let valid = segment.len() == 64 && segment.chars().all(|c| c.is_ascii_hexdigit());
+        if !valid {
+            return Err(WorkspaceError::InvalidSegment(wallet_address.to_owned()))
+        }
+        Ok(self.root.join(segment))
+    }
+
+    /// 创作者目录下的子目录, 如 bassinet_coin 或 collection_id
+    pub fn child_dir(&self, parent: &Path, name: &str) -> Result<PathBuf, WorkspaceError> {
+        let parent = self.confine(parent)?;
+        Ok(parent.join(safe_segment(name)?))
+    }
+
+    /// 模板编译目录: <root>/_base/<name>, 不与创作者目录(64位十六进制)冲突
+    pub fn template_base_dir(&self, name: &str) -> Result<PathBuf, WorkspaceError> {
+        let base = self.create_dir(&self.root.join("_base"))?;
+        let dir = self.child_dir(&base, name)?;
+        self.create_dir(&dir)
+    }
+
+    /// 创建目录(已存在时复用), 返回规范化后的路径
+    pub fn create_dir(&self, path: &Path) -> Result<PathBuf, WorkspaceError> {
+        if !path.exists() {
+            // 先确认父目录位于根目录内再创建, 避免经符号链接在根目录外创建目录
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                return Err(WorkspaceError::Escapes(path.to_path_buf()))
+            };
+            fs::create_dir(self.confine(parent)?.join(name))?;
+        }
+        let path = self.confine(path)?;
+        if !path.is_dir() {
+            return Err(WorkspaceError::NotDirectory(path))
+        }
+        Ok(path)
+    }
+
+    /// 规范化已存在的路径并确认其位于根目录内(解析符号链接)
+    pub fn confine(&self, path: &Path) -> Result<PathBuf, WorkspaceError> {
+        let canonical = path.canonicalize()?;
+        if !canonical.starts_with(&self.root) {
+            return Err(WorkspaceError::Escapes(canonical))
+        }
+        Ok(canonical)
+    }
+}
+
+/// 单级目录名: 只允许字母、数字、'-'、'_'
+pub fn safe_segment(name: &str) -> Result<&str, WorkspaceError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_SEGMENT_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(WorkspaceError::InvalidSegment(name.to_owned()))
+    }
+    Ok(name)
+}
+
+/// 归档条目路径必须为相对路径且不含 ..
+pub fn safe_entry_path(path: &Path) -> bool {
+    path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALLET: &str = "0x00000000000000000000000000000000000000000000000000000000000000aa";
+
+    struct TempRoot {
+        dir: PathBuf,
+    }
+
+    impl TempRoot {
+
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("bassinet-workspace-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(dir.join("root")).unwrap();
+            fs::create_dir_all(dir.join("outside")).unwrap();
+            Self { dir }
+        }
+
+        fn workspace(&self) -> Workspace {
+            Workspace::new(&self.dir.join("root")).unwrap()
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn creator_dir_accepts_wallet_addresses() {
+        let temp = TempRoot::new();
+        let workspace = temp.workspace();
+        let expected = workspace.root().join(&WALLET[2..]);
+        assert_eq!(workspace.creator_dir(WALLET).unwrap(), expected);
+        assert_eq!(workspace.creator_dir(&WALLET[2..]).unwrap(), expected);
+    }
+
+    #[test]
+    fn creator_dir_rejects_malformed_wallets() {
+        let temp = TempRoot::new();
+        let workspace = temp.workspace();
+        let malformed = [
+            String::new(),
+            "0x".to_owned(),
+            "0xaa".to_owned(),
+            format!("0x{}", "g".repeat(64)),
+            format!("0x{}", "a".repeat(65)),
+            format!("0x../{}", "a".repeat(61)),
+            format!("/{}", "a".repeat(63)),
+        ];
+        for wallet in malformed {
+            assert!(matches!(workspace.creator_dir(&wallet), Err(WorkspaceError::InvalidSegment(_))), "{}", wallet);
+        }
+    }
+
+    #[test]
+    fn safe_segment_rejects_paths() {
+        assert_eq!(safe_segment("collection-1_a").unwrap(), "collection-1_a");
+        for name in ["", ".", "..", "a/b", "a\\b", "/a", "a.b", "名"] {
+            assert!(matches!(safe_segment(name), Err(WorkspaceError::InvalidSegment(_))), "{}", name);
+        }
+        assert!(safe_segment(&"a".repeat(MAX_SEGMENT_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn child_dir_rejects_unsafe_names() {
+        let temp = TempRoot::new();
+        let workspace = temp.workspace();
+        let root = workspace.root().to_path_buf();
+        assert_eq!(workspace.child_dir(&root, "bassinet_coin").unwrap(), root.join("bassinet_coin"));
+        assert!(matches!(workspace.child_dir(&root, ".."), Err(WorkspaceError::InvalidSegment(_))));
+        assert!(matches!(workspace.child_dir(&root, "../outside"), Err(WorkspaceError::InvalidSegment(_))));
+        assert!(matches!(workspace.child_dir(&temp.dir.join("outside"), "a"), Err(WorkspaceError::Escapes(_))));
+    }
+
+    #[test]
+    fn confine_rejects_paths_outside_root() {
+        let temp = TempRoot::new();
+        let workspace = temp.workspace();
+        let inside = workspace.create_dir(&workspace.root().join("inside")).unwrap();
+        assert_eq!(workspace.confine(&inside.join("..").join("inside")).unwrap(), inside);
+        assert!(matches!(workspace.confine(&workspace.root().join("..").join("outside")), Err(WorkspaceError::Escapes(_))));
+        assert!(matches!(workspace.confine(&workspace.root().join("missing")), Err(WorkspaceError::Io(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn confine_rejects_symlinks_escaping_root() {
+        let temp = TempRoot::new();
+        let workspace = temp.workspace();
+        let link = workspace.root().join("link");
+        std::os::unix::fs::symlink(temp.dir.join("outside"), &link).unwrap();
+        assert!(matches!(workspace.confine(&link), Err(WorkspaceError::Escapes(_))));
+        assert!(matches!(workspace.child_dir(&link, "a"), Err(WorkspaceError::Escapes(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn create_dir_does_not_create_outside_root() {
+        let temp = TempRoot::new();
+        let workspace = temp.workspace();
+        let link = workspace.root().join("link");
+        std::os::unix::fs::symlink(temp.dir.join("outside"), &link).unwrap();
+        assert!(matches!(workspace.create_dir(&link.join("created")), Err(WorkspaceError::Escapes(_))));
+        assert!(!temp.dir.join("outside").join("created").exists());
+        assert!(matches!(workspace.create_dir(&link), Err(WorkspaceError::Escapes(_))));
+    }
+
+    #[test]
+    fn create_dir_reuses_existing_directories() {
+        let temp = TempRoot::new();
+        let workspace = temp.workspace();
+        let dir = workspace.create_dir(&workspace.root().join("dir")).unwrap();
+        assert_eq!(workspace.create_dir(&dir).unwrap(), dir);
+        fs::write(dir.join("file"), b"").unwrap();
+        assert!(matches!(workspace.create_dir(&dir.join("file")), Err(WorkspaceError::NotDirectory(_))));
+    }
+
+    #[test]
+    fn safe_entry_path_rejects_escapes() {
+        assert!(safe_entry_path(Path::new("bassinet_coin/sources/bassinet_coin.move")));
+        assert!(safe_entry_path(Path::new("./Move.toml")));
+        assert!(!safe_entry_path(Path::new("../Move.toml")));
+        assert!(!safe_entry_path(Path::new("sources/../../Move.toml")));
+        assert!(!safe_entry_path(Path::new("/etc/passwd")));
+    }
+}
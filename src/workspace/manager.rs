@@ -0,0 +1,205 @@
+use std::{fmt, fs, io, path::{Path, PathBuf}};
+
+use anyhow::anyhow;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::{archive::{archive_dir, unpack}, kv_store::{now_millis, KVStore}, move_build::patch::{PLACEHOLDER_DESCRIPTION, PLACEHOLDER_ICON_URL, PLACEHOLDER_NAME, PLACEHOLDER_SYMBOL}, repository::{CoinPackageRepository, CreatorRepository}, sui_service::{digital_service::OpenDigitalServiceConfig, BassinetCoinPublishedResult, CoinMetadata}, template::{bassinet_coin::{bassinet_coin_move_publish_template, bassinet_coin_template}, registry::{find_variant, select_variant, TemplateKind}, source::template_version}, validation::CoinEconomics};
+
+use super::Workspace;
+
+const USAGE: &str = "usage: bassinet-sui workspace <command>
+  regenerate <wallet_address>   regenerate a creator's coin workspace from stored metadata
+  archive <wallet_address>      archive a creator's workspace into _archive and remove it
+  gc [--dry-run]                remove Move build artifacts";
+
+/// 归档目录: <root>/_archive
+const ARCHIVE_DIR: &str = "_archive";
+
+/// 清理报告
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub removed: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+impl fmt::Display for GcReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for path in &self.removed {
+            writeln!(f, "  removed {}", path.display())?;
+        }
+        let mode = if self.dry_run { " (dry run)" } else { "" };
+        write!(f, "{} build directories, {} bytes{}", self.removed.len(), self.bytes, mode)
+    }
+}
+
+/// 工作目录子命令
+pub fn workspace_command<S: KVStore>(args: &[String], db: S) -> Result<(), anyhow::Error> {
+    let command = args.first().ok_or(anyhow!(USAGE))?;
+    let dir_path = std::env::var("CONTRACTS_DIR_PATH").expect("CONTRACTS_DIR_PATH must be set");
+    let workspace = Workspace::new(Path::new(&dir_path))?;
+    match command.as_str() {
+        "regenerate" => {
+            let wallet_address = args.get(1).ok_or(anyhow!(USAGE))?;
+            let provider = std::env::var("PROVIDER").expect("PROVIDER must be set");
+            let package_id = CreatorRepository::new(db.clone()).coin_package_id(wallet_address)?
+                .ok_or(anyhow!("no coin package recorded for {}", wallet_address))?;
+            let coin = CoinPackageRepository::new(db).find(&package_id)?
+                .ok_or(anyhow!("coin package {} record not found", package_id))?;
+            let dir = regenerate_coin_workspace(&workspace, &coin, &provider)?;
+            println!("regenerated {}", dir.display());
+            Ok(())
+        }
+        "archive" => {
+            let wallet_address = args.get(1).ok_or(anyhow!(USAGE))?;
+            let file = archive_creator(&workspace, wallet_address)?;
+            println!("archived to {}", file.display());
+            Ok(())
+        }
+        "gc" => {
+            let mut dry_run = false;
+            for arg in &args[1..] {
+                match arg.as_str() {
+                    "--dry-run" => dry_run = true,
+                    _ => return Err(anyhow!("Unknown argument:{}\n{}", arg, USAGE)),
+                }
+            }
+            let report = collect_garbage(&workspace, dry_run)?;
+            println!("{}", report);
+            Ok(())
+        }
+        command => Err(anyhow!("Unknown argument:{}\n{}", command, USAGE)),
+    }
+}
+
+/// 确保创作者的代币合约目录存在(NFT合约包通过 ../bassinet_coin 依赖它), 不存在时重新生成
+pub fn ensure_coin_workspace(workspace: &Workspace, coin: &BassinetCoinPublishedResult, provider: &str) -> Result<PathBuf, anyhow::Error> {
+    let dir = workspace.creator_dir(&coin.wallet_address)?;
+    if dir.join("bassinet_coin").join("Move.toml").is_file() {
+        return Ok(dir)
+    }
+    info!("wallet_address:{}, coin workspace missing, regenerating", coin.wallet_address);
+    regenerate_coin_workspace(workspace, coin, provider)
+}
+
+/// 按存储的发布结果重新生成已发布形式的代币合约包(published-at 为链上package_id)
+/// 使用发布时记录的模板参数和代币信息; 旧记录没有时使用默认参数和占位值
+pub fn regenerate_coin_workspace(workspace: &Workspace, coin: &BassinetCoinPublishedResult, provider: &str) -> Result<PathBuf, anyhow::Error> {
+    if coin.template_version.as_deref() != Some(template_version()) {
+        warn!("package:{}, published with template {:?}, regenerating with {}", coin.package_id, coin.template_version, template_version());
+    }
+    // 按发布时的模板变体生成, 变体已不存在时使用默认变体
+    let variant = coin.template_variant.as_deref()
+        .and_then(|label| label.split('@').next())
+        .and_then(find_variant)
+        .filter(|variant| variant.kind == TemplateKind::Coin);
+    let params = coin.template_params.as_ref().map(serde_json::to_value).transpose()?;
+    let template = match variant {
+        Some(variant) => variant.resolve(params.as_ref())?,
+        None => select_variant(TemplateKind::Coin, &Value::Null)?,
+    };
+    if params.is_none() {
+        warn!("package:{}, template params not recorded, regenerating with defaults", coin.package_id);
+    }
+    let metadata = coin.metadata.clone().unwrap_or_else(|| {
+        warn!("package:{}, coin metadata not recorded, regenerating with placeholders", coin.package_id);
+        CoinMetadata {
+            symbol: PLACEHOLDER_SYMBOL.to_owned(),
+            name: PLACEHOLDER_NAME.to_owned(),
+            description: PLACEHOLDER_DESCRIPTION.to_owned(),
+            icon_url: PLACEHOLDER_ICON_URL.to_owned(),
+        }
+    });
+    let economics = CoinEconomics::from_params(&template)?;
+
+    let dir = workspace.create_dir(&workspace.creator_dir(&coin.wallet_address)?)?;
+    let coin_dir = workspace.child_dir(&dir, "bassinet_coin")?;
+    if coin_dir.exists() {
+        fs::remove_dir_all(&coin_dir)?;
+    }
+    unpack(&dir)?;
+    let config = OpenDigitalServiceConfig::new(
+        coin.account.clone(),
+        coin.wallet_address.clone(),
+        workspace.root().to_path_buf(),
+        metadata.symbol,
+        metadata.name,
+        metadata.description,
+        metadata.icon_url,
+        coin.wallet_address.clone(),
+        provider.to_owned(),
+        coin.package_id.clone(),
+        template,
+        economics
+    );
+    bassinet_coin_template(&coin_dir, &config)?;
+    bassinet_coin_move_publish_template(&coin_dir, &config)?;
+    Ok(dir)
+}
+
+/// 将创作者目录压缩为 <root>/_archive/<目录名>-<毫秒>.tar.gz 后删除, 之后需要时可重新生成
+pub fn archive_creator(workspace: &Workspace, wallet_address: &str) -> Result<PathBuf, anyhow::Error> {
+    let dir = workspace.confine(&workspace.creator_dir(wallet_address)?)?;
+    let name = dir.file_name().ok_or(anyhow!("invalid creator directory {}", dir.display()))?.to_string_lossy().into_owned();
+    let archive_dir = workspace.create_dir(&workspace.root().join(ARCHIVE_DIR))?;
+    let file = archive_dir.join(format!("{}-{}.tar.gz", name, now_millis()));
+
+    archive_dir(&dir, &name, &file)?;
+    fs::remove_dir_all(&dir)?;
+    Ok(file)
+}
+
+/// 删除合约包的编译产物(与 Move.toml 同级的 build 目录)
+pub fn collect_garbage(workspace: &Workspace, dry_run: bool) -> Result<GcReport, anyhow::Error> {
+    let mut report = GcReport { dry_run, ..Default::default() };
+    let mut build_dirs = Vec::new();
+    find_build_dirs(workspace.root(), 0, &mut build_dirs)?;
+    for build_dir in build_dirs {
+        let build_dir = workspace.confine(&build_dir)?;
+        report.bytes += dir_size(&build_dir)?;
+        if !dry_run {
+            fs::remove_dir_all(&build_dir)?;
+        }
+        report.removed.push(build_dir);
+    }
+    Ok(report)
+}
+
+/// 合约包最深位于 <root>/_base/<name>/bassinet, 不再向下查找
+const MAX_PACKAGE_DEPTH: usize = 3;
+
+fn find_build_dirs(dir: &Path, depth: usize, build_dirs: &mut Vec<PathBuf>) -> Result<(), io::Error> {
+    if dir.join("Move.toml").is_file() {
+        let build_dir = dir.join("build");
+        if build_dir.is_dir() {
+            build_dirs.push(build_dir);
+        }
+        return Ok(())
+    }
+    if depth >= MAX_PACKAGE_DEPTH {
+        return Ok(())
+    }
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        // 不跟随符号链接
+        if entry.file_type()?.is_dir() && entry.file_name() != ARCHIVE_DIR {
+            find_build_dirs(&entry.path(), depth + 1, build_dirs)?;
+        }
+    }
+    Ok(())
+}
+
+fn dir_size(dir: &Path) -> Result<u64, io::Error> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
@@ -0,0 +1,40 @@
+/*
+/// Module: bassinet
+module bassinet::bassinet;
+*/
+
+// For Move coding conventions, see
+// https://docs.sui.io/concepts/sui-move-concepts/conventions
+
+module bassinet::bassinet;
+
+use sui::transfer_policy::{TransferPolicy, TransferPolicyCap};
+use std::string::{String};
+use bassinet_coin::bassinet_coin::{Self, AdminCap};
+use bassinet::bassinet_nft::{Self, BassinetNFT, Mint};
+use bassinet::royalty;
+
+/// 配置铸造参数并授权NFT合约按创作者的TreasuryLock挖掘激励, 按 royalty 模块加入版税规则
+/// 已授权时需先调用 revoke
+entry fun authorize(
+    admin_cap: &AdminCap,
+    self: &mut Mint,
+    policy: &mut TransferPolicy<BassinetNFT>,
+    policy_cap: &TransferPolicyCap<BassinetNFT>,
+    app_name: String,
+    description: vector<u8>,
+    collection_id: vector<u8>,
+    collection_url: vector<u8>,
+    limit: u64,
+    rewards_quantity: u64,
+    minting_price: u64
+) {
+    royalty::add(policy, policy_cap);
+    bassinet_nft::configure(self, app_name, description, collection_id, collection_url, limit, minting_price);
+    bassinet_coin::authorize_app<BassinetNFT>(admin_cap, bassinet_nft::uid_mut(self), app_name, rewards_quantity, limit);
+}
+
+/// 撤销激励授权, 撤销后不能铸造
+entry fun revoke(admin_cap: &AdminCap, self: &mut Mint) {
+    bassinet_coin::revoke_auth<BassinetNFT>(admin_cap, bassinet_nft::uid_mut(self));
+}
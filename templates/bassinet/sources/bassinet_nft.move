@@ -0,0 +1,171 @@
+/*
+/// Module: bassinet_nft
+module bassinet::bassinet_nft;
+*/
+
+// For Move coding conventions, see
+// https://docs.sui.io/concepts/sui-move-concepts/conventions
+
+module bassinet::bassinet_nft;
+
+use sui::coin::{Self, Coin};
+use sui::balance::{Self, Balance};
+use sui::sui::SUI;
+use sui::display;
+use sui::package;
+use sui::transfer_policy;
+use sui::url::{Self, Url};
+use std::string::{String};
+use bassinet_coin::bassinet_coin::{Self, TreasuryLock};
+
+const ENotAuthorized: u64 = 0;
+const ELimitReached: u64 = 1;
+const EIncorrectPayment: u64 = 2;
+const ENotCreator: u64 = 3;
+const ENoProfits: u64 = 4;
+
+/// NFT
+public struct BassinetNFT has key, store {
+    id: UID,
+    name: String,
+    description: String,
+    image_url: Url,
+    collection_id: String,
+    // 编号, 从1开始
+    number: u64,
+}
+
+/// 铸造配置, 激励授权(MintAppCap)挂在 id 下
+public struct Mint has key {
+    id: UID,
+    app_name: String,
+    description: String,
+    collection_id: String,
+    collection_url: Url,
+    // 铸造数量限制
+    limit: u64,
+    // 已铸造数量
+    minted: u64,
+    // 铸造价格(MIST)
+    minting_price: u64,
+    // 铸造收入, 归创作者
+    profits: Balance<SUI>,
+}
+
+public struct BASSINET_NFT has drop {}
+
+/// 初始调用: 创建交易策略和铸造配置, 授权前不能铸造
+fun init(otw: BASSINET_NFT, ctx: &mut TxContext) {
+    let publisher = package::claim(otw, ctx);
+
+    let mut nft_display = display::new<BassinetNFT>(&publisher, ctx);
+    nft_display.add(b"name".to_string(), b"{name} #{number}".to_string());
+    nft_display.add(b"description".to_string(), b"{description}".to_string());
+    nft_display.add(b"image_url".to_string(), b"{image_url}".to_string());
+    nft_display.update_version();
+
+    let (policy, policy_cap) = transfer_policy::new<BassinetNFT>(&publisher, ctx);
+    transfer::public_share_object(policy);
+    transfer::public_transfer(policy_cap, ctx.sender());
+    transfer::public_transfer(nft_display, ctx.sender());
+    transfer::public_transfer(publisher, ctx.sender());
+
+    let mint = Mint {
+        id: object::new(ctx),
+        app_name: b"".to_string(),
+        description: b"".to_string(),
+        collection_id: b"".to_string(),
+        collection_url: url::new_unsafe_from_bytes(b""),
+        limit: 0,
+        minted: 0,
+        minting_price: 0,
+        profits: balance::zero<SUI>(),
+    };
+    transfer::share_object(mint)
+}
+
+/// 铸造NFT, 同时按授权从创作者代币合约领取激励
+public fun mint(self: &mut Mint, lock: &mut TreasuryLock, payment: Coin<SUI>, ctx: &mut TxContext): (BassinetNFT, Coin<bassinet_coin::BASSINET_COIN>) {
+    assert!(bassinet_coin::is_authorized<BassinetNFT>(&self.id), ENotAuthorized);
+    assert!(self.minted < self.limit, ELimitReached);
+    assert!(payment.value() == self.minting_price, EIncorrectPayment);
+    balance::join(&mut self.profits, payment.into_balance());
+
+    self.minted = self.minted + 1;
+    let nft = BassinetNFT {
+        id: object::new(ctx),
+        name: self.app_name,
+        description: self.description,
+        image_url: self.collection_url,
+        collection_id: self.collection_id,
+        number: self.minted,
+    };
+    let rewards = bassinet_coin::mint<BassinetNFT>(&mut self.id, lock, ctx);
+    (nft, rewards)
+}
+
+/// 铸造NFT并转给调用者
+entry fun mint_to_sender(self: &mut Mint, lock: &mut TreasuryLock, payment: Coin<SUI>, ctx: &mut TxContext) {
+    let (nft, rewards) = mint(self, lock, payment, ctx);
+    transfer::public_transfer(nft, ctx.sender());
+    if (rewards.value() > 0) {
+        transfer::public_transfer(rewards, ctx.sender());
+    } else {
+        rewards.destroy_zero();
+    };
+}
+
+/// 创作者领取铸造收入
+entry fun take_profits(self: &mut Mint, ctx: &mut TxContext) {
+    assert!(ctx.sender() == @creator, ENotCreator);
+    let amount = self.profits.value();
+    assert!(amount > 0, ENoProfits);
+    transfer::public_transfer(coin::take(&mut self.profits, amount, ctx), @creator);
+}
+
+// === Package ===
+
+/// 更新铸造配置
+public(package) fun configure(
+    self: &mut Mint,
+    app_name: String,
+    description: vector<u8>,
+    collection_id: vector<u8>,
+    collection_url: vector<u8>,
+    limit: u64,
+    minting_price: u64
+) {
+    self.app_name = app_name;
+    self.description = description.to_string();
+    self.collection_id = collection_id.to_string();
+    self.collection_url = url::new_unsafe_from_bytes(collection_url);
+    self.limit = limit;
+    self.minting_price = minting_price;
+}
+
+/// 激励授权挂载的UID
+public(package) fun uid_mut(self: &mut Mint): &mut UID {
+    &mut self.id
+}
+
+// === Accessors ===
+
+public fun minted(self: &Mint): u64 {
+    self.minted
+}
+
+public fun limit(self: &Mint): u64 {
+    self.limit
+}
+
+public fun minting_price(self: &Mint): u64 {
+    self.minting_price
+}
+
+public fun number(nft: &BassinetNFT): u64 {
+    nft.number
+}
+
+public fun collection_id(nft: &BassinetNFT): String {
+    nft.collection_id
+}
@@ -0,0 +1,47 @@
+/// Module: royalty
+/// 默认不收取版税; bassinet_nft.royalty 变体按 royalty_bps 渲染 bassinet_nft_royalty_template 覆盖本文件
+module bassinet::royalty;
+
+use sui::coin::Coin;
+use sui::sui::SUI;
+use sui::transfer_policy::{Self, TransferPolicy, TransferPolicyCap, TransferRequest};
+
+/// 版税基点, 10000 = 100%
+const ROYALTY_BPS: u16 = 0;
+
+const EIncorrectAmount: u64 = 0;
+
+/// 版税规则
+public struct Rule has drop {}
+
+/// 版税规则配置
+public struct Config has store, drop {
+    royalty_bps: u16,
+}
+
+/// 版税基点
+public fun royalty_bps(): u16 {
+    ROYALTY_BPS
+}
+
+/// 按成交价计算版税
+public fun royalty_amount(price: u64): u64 {
+    (((price as u128) * (ROYALTY_BPS as u128) / 10_000) as u64)
+}
+
+/// 在交易策略中加入版税规则; 版税为0或规则已存在时不变
+public fun add<T>(policy: &mut TransferPolicy<T>, cap: &TransferPolicyCap<T>) {
+    if (ROYALTY_BPS == 0 || transfer_policy::has_rule<T, Rule>(policy)) {
+        return
+    };
+    transfer_policy::add_rule(Rule {}, policy, cap, Config { royalty_bps: ROYALTY_BPS })
+}
+
+/// 支付版税, 版税进入交易策略余额, 由 TransferPolicyCap 持有者提取
+public fun pay<T>(policy: &mut TransferPolicy<T>, request: &mut TransferRequest<T>, payment: Coin<SUI>) {
+    let config: &Config = transfer_policy::get_rule(Rule {}, policy);
+    let amount = (((request.paid() as u128) * (config.royalty_bps as u128) / 10_000) as u64);
+    assert!(payment.value() == amount, EIncorrectAmount);
+    transfer_policy::add_to_balance(Rule {}, policy, payment);
+    transfer_policy::add_receipt(Rule {}, request)
+}
@@ -14,8 +14,8 @@ use sui::dynamic_field as df;
 use std::type_name::{Self};
 use std::string::{String};
 
-// 10亿10^6
-const MAX_SUPPLY: u64 = 1_000_000_000_000_000;
+// 最大发行量(含精度)
+const MAX_SUPPLY: u64 = 1000000000000000;
 
 /// For when there's no profits to claim.
 const ENoProfits: u64 = 0;
@@ -70,10 +70,10 @@ fun init(witness: BASSINET_COIN, ctx: &mut TxContext) {
     let (mut treasury_cap, metadata) = coin::create_currency(
         witness,
         6,
-        b"BASSINET_COIN",
-        b"",
-        b"",
-        option::none(),
+        b"BASSINET_PLACEHOLDER_SYMBOL",
+        b"BASSINET_PLACEHOLDER_NAME",
+        b"BASSINET_PLACEHOLDER_DESCRIPTION",
+        option::some(sui::url::new_unsafe_from_bytes(b"https://placeholder.bassinet/icon")),
         ctx,
     );
     // Freezing this object makes the metadata immutable, including the title, name, and icon image.
@@ -83,7 +83,7 @@ fun init(witness: BASSINET_COIN, ctx: &mut TxContext) {
     let creator = @creator;
     let platform_provider = @platform_provider;
 
-    // 发行10亿
+    // 一次性发行最大发行量
     let minting_coin = coin::mint(&mut treasury_cap, MAX_SUPPLY, ctx);
     transfer::public_freeze_object(treasury_cap);
 
@@ -241,10 +241,10 @@ fun take_creator_profits(self: &mut TreasuryLock, ctx: &mut TxContext): Coin<BAS
 
 /// 领取平台激励
 fun take_provider_profits(self: &mut TreasuryLock, ctx: &mut TxContext): Coin<BASSINET_COIN> {
-    let amount = balance::value(&self.creator_rewards);
+    let amount = balance::value(&self.platform_provider_rewards);
     assert!(amount > 0, ENoProfits);
     // Take a transferable `Coin` from a `Balance`
-    coin::take(&mut self.creator_rewards, amount, ctx)
+    coin::take(&mut self.platform_provider_rewards, amount, ctx)
 }
 
 /// 是否平台方